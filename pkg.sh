@@ -2,6 +2,17 @@
 
 linux_wrapper_template=$(cat ./linux-wrapper-template.sh)
 
+# The launcher checks the patch list and its own updates against these keys,
+# which are built in. Cross.toml passes them through to the builds.
+if [ -z "$AECO_MANIFEST_PUBLIC_KEY" ]; then
+    echo "AECO_MANIFEST_PUBLIC_KEY must be set to build release packages" >&2
+    exit 1
+fi
+export AECO_MANIFEST_PUBLIC_KEY
+if [ -n "$AECO_LAUNCHER_PUBLIC_KEY" ]; then
+    export AECO_LAUNCHER_PUBLIC_KEY
+fi
+
 # Clear target dir
 cargo clean
 
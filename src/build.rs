@@ -1,8 +1,12 @@
 use std::process::Command;
 
+/// The public keys the launcher checks signatures with, given at build time
+const PUBLIC_KEY_ENVS: [&str; 2] = ["AECO_MANIFEST_PUBLIC_KEY", "AECO_LAUNCHER_PUBLIC_KEY"];
+
 fn main() {
     git_hash();
     git_changes();
+    check_public_keys();
     println!("cargo:rustc-rerun-if-changed=.git/HEAD");
 }
 
@@ -54,3 +58,28 @@ fn git_changes() {
     };
     println!("cargo:rustc-env=GIT_CHANGES={}", git_changes);
 }
+
+/// Fails release builds which have no key to check the patch list with,
+/// since they would trust whatever the server sends. Builds which are
+/// meant to be unsigned have to say so with the `unsigned` feature.
+fn check_public_keys() {
+    for env in PUBLIC_KEY_ENVS {
+        println!("cargo:rerun-if-env-changed={}", env);
+        if let Ok(key) = std::env::var(env) {
+            let key = key.trim();
+            if key.len() != 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+                panic!("{} is not a hex encoded Ed25519 public key", env);
+            }
+        }
+    }
+
+    let release = std::env::var("PROFILE").map_or(false, |profile| profile == "release");
+    let unsigned = std::env::var_os("CARGO_FEATURE_UNSIGNED").is_some();
+    if release && !unsigned && std::env::var_os(PUBLIC_KEY_ENVS[0]).is_none() {
+        panic!(
+            "Release builds need {} to be set, or the `unsigned` feature to build without \
+             checking signatures",
+            PUBLIC_KEY_ENVS[0]
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use crate::message::{GUIMessage, PatchMessage, PatchPreview, PatchStatus, RepairSummary};
+use crate::patcher::PatchWorker;
+use serde_json::json;
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+use std::sync::mpsc::{Receiver, Sender};
+
+const USAGE: &str = r#"Usage: aeco-launcher [patch|verify|status|diagnose|optimize] [--json] [--server URL] [--portable]
+                    [--big-picture] [--no-elevate] [--json-progress]
+
+  patch         Install or update the game
+  verify        Check every file, and repair any which are damaged
+  status        Show what patching would change, without changing anything
+  diagnose      Test each step of reaching the patch server, to tell whether
+                a problem is with the connection or the server
+  optimize      Give back the space left in the game's archives by files
+                which were replaced since they were last optimized
+
+  --json        Print progress as one JSON object per line
+  --server URL  Patch from this server instead of the configured one
+  --portable    Keep everything next to the launcher, with paths relative to
+                it, so it can be run from a USB stick. Putting a file named
+                portable.txt next to the launcher does the same.
+  --big-picture Fill the screen with a layout which works with a controller,
+                start the game once it is up to date, and exit with the
+                game's exit code once it closes. For Steam's Big Picture
+                mode and the Steam Deck.
+  --no-elevate  Don't ask for administrator rights when the game directory
+                can't be changed without them
+  --json-progress
+                Run without a window for another program to drive: print
+                everything the launcher would show as one JSON object per
+                line, like {"event": "info", "data": "Server is online"},
+                and read commands from standard input the same way, like
+                {"command": "retry"}, {"command": "play"} or
+                {"command": "cancel"}. The launcher exits after
+                {"command": "close"} or once standard input is closed.
+
+Exit codes: 0 on success, 1 if the command failed, 2 for bad usage"#;
+
+/// Everything given on the command line
+pub struct Args {
+    /// What to do instead of opening the GUI
+    pub command: Option<Command>,
+    /// The patch server to use instead of the configured one
+    pub server: Option<String>,
+    /// Only show what patching would change
+    pub check_only: bool,
+    pub portable: bool,
+    pub big_picture: bool,
+    pub no_elevate: bool,
+    /// Let another program drive the launcher over standard input and output
+    pub json_progress: bool,
+}
+
+impl Args {
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut message = None;
+        let mut json = false;
+        let mut server = None;
+        let mut check_only = false;
+        let mut portable = false;
+        let mut big_picture = false;
+        let mut no_elevate = false;
+        let mut json_progress = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "patch" => message = Some(GUIMessage::Retry),
+                "verify" => message = Some(GUIMessage::Verify),
+                "status" => message = Some(GUIMessage::Preview),
+                "diagnose" => message = Some(GUIMessage::TestConnection),
+                "optimize" => message = Some(GUIMessage::OptimizeArchives),
+                "--json" => json = true,
+                "--check-only" => check_only = true,
+                "--portable" => portable = true,
+                "--big-picture" => big_picture = true,
+                "--no-elevate" => no_elevate = true,
+                "--json-progress" => json_progress = true,
+                "--server" => match args.next() {
+                    Some(url) => server = Some(url),
+                    None => return Err(format!("--server needs a URL\n\n{USAGE}")),
+                },
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                other => match other.strip_prefix("--server=") {
+                    Some(url) => server = Some(url.to_string()),
+                    None => return Err(format!("Unknown argument '{other}'\n\n{USAGE}")),
+                },
+            }
+        }
+
+        if json_progress && message.is_some() {
+            return Err(format!(
+                "--json-progress can't be combined with a command\n\n{USAGE}"
+            ));
+        }
+        let command = match message {
+            Some(message) => Some(Command { message, json }),
+            None if json => return Err(format!("--json needs a command\n\n{USAGE}")),
+            None => None,
+        };
+        Ok(Self {
+            command,
+            server,
+            check_only,
+            portable,
+            big_picture,
+            no_elevate,
+            json_progress,
+        })
+    }
+}
+
+/// What the launcher was asked to do from the command line
+pub struct Command {
+    message: GUIMessage,
+    json: bool,
+}
+
+impl Command {
+    /// Runs the command without a GUI, printing what happens to stdout. On
+    /// Windows the launcher has no console of its own, so the output only
+    /// shows up when it is redirected to a file or another program.
+    pub fn run(self, worker: PatchWorker, rx: Receiver<PatchMessage>) -> ExitCode {
+        std::thread::spawn(move || worker.run_once(self.message));
+
+        let mut output = Output {
+            json: self.json,
+            succeeded: false,
+        };
+        // The worker closes the channel once it is done
+        for message in rx {
+            output.print(message);
+        }
+
+        if output.succeeded {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs the launcher for another program to drive, such as a mod manager.
+/// Every message the GUI would get is printed as a line of JSON, and
+/// commands are read from standard input as the GUI would send them.
+pub fn run_json_progress(
+    worker: PatchWorker,
+    first_message: GUIMessage,
+    tx: Sender<GUIMessage>,
+    rx: Receiver<PatchMessage>,
+) -> ExitCode {
+    let worker = std::thread::spawn(move || worker.run(first_message));
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<GUIMessage>(&line) {
+                Ok(message) => {
+                    if tx.send(message).is_err() {
+                        return;
+                    }
+                }
+                Err(why) => eprintln!("Invalid command '{line}': {why}"),
+            }
+        }
+        // Nobody is left to send commands
+        let _ = tx.send(GUIMessage::Close);
+    });
+
+    // The worker closes the channel once it is done
+    let mut stdout = std::io::stdout();
+    for message in rx {
+        let line = match serde_json::to_string(&message) {
+            Ok(line) => line,
+            Err(why) => {
+                eprintln!("Could not write a message as JSON: {why}");
+                continue;
+            }
+        };
+        // The other program is gone if this fails
+        if writeln!(stdout, "{line}")
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            return ExitCode::SUCCESS;
+        }
+    }
+
+    // Exit like the game did if the worker waited for it, once the worker
+    // is done closing
+    match worker.join() {
+        Ok(Some(exit_code)) => std::process::exit(exit_code),
+        Ok(None) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+/// Reports an invalid command line
+pub fn usage_error(message: &str) -> ExitCode {
+    eprintln!("{message}");
+    ExitCode::from(2)
+}
+
+struct Output {
+    json: bool,
+    /// Whether the command finished what it was asked to do
+    succeeded: bool,
+}
+
+impl Output {
+    fn print(&mut self, message: PatchMessage) {
+        match message {
+            PatchMessage::Error(text, details) => {
+                if self.json {
+                    self.event(json!({ "event": "error", "message": text, "details": details }));
+                } else {
+                    println!("{text}");
+                    if let Some(advice) = details.category.advice() {
+                        println!("{advice}");
+                    }
+                    eprint!("{details}");
+                }
+            }
+            PatchMessage::Info(text) => self.event(json!({ "event": "info", "message": text })),
+            PatchMessage::Downloading(text, progress) => self.event(json!({
+                "event": "progress",
+                "message": text,
+                "progress": progress,
+            })),
+            PatchMessage::Transfer(text, progress, rate) => self.event(json!({
+                "event": "progress",
+                "message": format!("{text} - {rate}"),
+                "progress": progress,
+            })),
+            PatchMessage::Versions {
+                installed,
+                available,
+            } => self.event(json!({
+                "event": "versions",
+                "installed": installed,
+                "available": available,
+            })),
+            PatchMessage::UpdateSize(size) => {
+                self.event(json!({ "event": "update_size", "bytes": size }))
+            }
+            PatchMessage::Summary(report) => {
+                if self.json {
+                    self.event(json!({ "event": "summary", "report": report }));
+                } else {
+                    println!("{report}");
+                }
+            }
+            PatchMessage::Server(Some(url)) => {
+                if self.json {
+                    self.event(json!({ "event": "server", "url": url }));
+                } else {
+                    println!("Using patch server {url}");
+                }
+            }
+            PatchMessage::ServerDetails(details) => {
+                if let Some(message) = details.message {
+                    self.event(json!({ "event": "server_message", "message": message }));
+                }
+                if let Some(end) = details.maintenance_end {
+                    self.event(json!({ "event": "maintenance_end", "timestamp": end }));
+                }
+            }
+            PatchMessage::Changelog { version, text } => {
+                if self.json {
+                    self.event(json!({ "event": "changelog", "version": version, "text": text }));
+                } else {
+                    println!("{text}");
+                }
+            }
+            PatchMessage::FileResult {
+                path,
+                action,
+                bytes,
+            } => {
+                if self.json {
+                    self.event(json!({
+                        "event": "file",
+                        "path": path,
+                        "action": action,
+                        "bytes": bytes,
+                    }));
+                } else {
+                    println!("{action}: {path}");
+                }
+            }
+            PatchMessage::ConnectionReport(report) => {
+                self.succeeded = report.is_ok();
+                if self.json {
+                    self.event(json!({ "event": "connection_report", "report": report }));
+                } else {
+                    print!("{report}");
+                }
+            }
+            PatchMessage::PatchStatus(status) => self.status(status),
+            // Only of interest to the GUI
+            PatchMessage::Components { .. }
+            | PatchMessage::GameLanguages { .. }
+            | PatchMessage::ForeignFiles(_)
+            | PatchMessage::FirstRun
+            | PatchMessage::GameCrashed { .. }
+            | PatchMessage::AutoLaunch(_)
+            | PatchMessage::LaunchTargets(_)
+            | PatchMessage::LogDir(_)
+            | PatchMessage::RepairNeeded(_)
+            | PatchMessage::ConfirmHooks(_)
+            | PatchMessage::CrashReports
+            | PatchMessage::Heartbeat
+            | PatchMessage::AccountLogin(_)
+            | PatchMessage::UpdateAvailable
+            | PatchMessage::Profiles { .. }
+            | PatchMessage::Realms { .. }
+            | PatchMessage::Settings(_)
+            | PatchMessage::State(_)
+            | PatchMessage::Server(None)
+            | PatchMessage::News(_) => {}
+        }
+    }
+
+    fn status(&mut self, status: PatchStatus) {
+        let name = match status {
+            PatchStatus::Finished => {
+                self.succeeded = true;
+                "finished"
+            }
+            PatchStatus::Offline => "offline",
+            PatchStatus::Unauthorized => "unauthorized",
+            PatchStatus::LauncherOutdated => "launcher_outdated",
+            PatchStatus::Maintenance => "maintenance",
+            PatchStatus::GameRunning => "game_running",
+            PatchStatus::Uninstalled => "uninstalled",
+            PatchStatus::Repaired(summary) => {
+                self.succeeded = summary.failed == 0;
+                self.repaired(&summary);
+                "repaired"
+            }
+            PatchStatus::Preview(preview) => {
+                self.succeeded = true;
+                self.preview(&preview);
+                "preview"
+            }
+            PatchStatus::Error => {
+                // Something can still go wrong after the game is patched
+                self.succeeded = false;
+                "error"
+            }
+            // Nothing to report until something else happens
+            PatchStatus::Working | PatchStatus::Close => return,
+        };
+
+        if self.json {
+            self.event(json!({ "event": "status", "status": name }));
+        }
+    }
+
+    fn repaired(&self, summary: &RepairSummary) {
+        if self.json {
+            self.event(json!({
+                "event": "repair",
+                "checked": summary.checked,
+                "repaired": summary.repaired,
+                "failed": summary.failed,
+            }));
+        } else {
+            println!("{summary}");
+        }
+    }
+
+    fn preview(&self, preview: &PatchPreview) {
+        if self.json {
+            self.event(json!({
+                "event": "preview",
+                "up_to_date": preview.is_up_to_date(),
+                "base_game": preview.base_game,
+                "files": preview.files,
+                "download_size": preview.download_size,
+                "archives": preview.archives,
+            }));
+        } else {
+            println!("{preview}");
+        }
+    }
+
+    /// Prints an event as a line of JSON, or its message as plain text
+    fn event(&self, event: serde_json::Value) {
+        if self.json {
+            println!("{event}");
+        } else if let Some(message) = event.get("message").and_then(|m| m.as_str()) {
+            println!("{message}");
+        }
+    }
+}
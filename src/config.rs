@@ -0,0 +1,664 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The name of the launcher's configuration file, which is stored next to
+/// the launcher
+pub const CONFIG_FILE: &str = "launcher.toml";
+
+/// The channel which everyone is on unless they choose otherwise
+pub const STABLE_CHANNEL: &str = "stable";
+
+/// User configuration for the launcher. Every field has a default, so a
+/// missing file or a file which only sets some values is valid.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub network: NetworkConfig,
+    pub patch: PatchConfig,
+    pub game: GameConfig,
+    pub account: AccountConfig,
+    pub ui: UiConfig,
+    pub diagnostics: DiagnosticsConfig,
+    /// Separate installations of the game which the launcher can switch
+    /// between
+    pub profiles: Vec<Profile>,
+    /// The game servers in each region, which the player chooses between
+    pub realms: Vec<Realm>,
+}
+
+/// An installation of the game, such as one for the live servers and one for
+/// the public test realm
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Profile {
+    pub name: String,
+    /// Where this installation is. Relative paths are relative to the
+    /// launcher, and without one, the installation is in a directory named
+    /// after the profile next to the launcher.
+    #[serde(default)]
+    pub game_dir: Option<PathBuf>,
+    #[serde(default = "stable_channel")]
+    pub channel: String,
+    /// The patch server to use instead of the usual one
+    #[serde(default)]
+    pub server: Option<String>,
+}
+
+/// A game server in one region, such as NA, EU or JP
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Realm {
+    pub name: String,
+    /// The host and port the game connects to, which is also what the
+    /// launcher measures the ping to
+    pub address: String,
+    /// Arguments to start the game with when this realm is chosen, where
+    /// `{address}` is replaced with the realm's address
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+fn stable_channel() -> String {
+    STABLE_CHANNEL.to_string()
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct PatchConfig {
+    /// Which release of the game to follow, such as "stable", "beta" or
+    /// "ptr". Channels other than stable have their own patch list and
+    /// patch files on the server.
+    pub channel: String,
+    /// Stay on this version of the game once it is installed, instead of
+    /// updating to newer versions
+    pub pinned_version: Option<String>,
+    /// Optional components to install, such as HD textures or voice packs.
+    /// Components which are turned off later are removed like any other
+    /// file which is no longer part of the game.
+    pub components: Vec<String>,
+    /// The game's language packs to install, by language code, such as
+    /// "en" or "ja". Like components, packs which are turned off later are
+    /// removed.
+    pub languages: Vec<String>,
+    /// What to do with game files which are no longer part of the game.
+    /// Files which were never part of the game are left alone.
+    pub removed_files: RemovedFiles,
+    /// Skip hashing files which haven't changed since they were last
+    /// checked. Repairing the installation always hashes every file.
+    pub hash_cache: bool,
+    /// How many threads hash files while checking the game. 0 uses one
+    /// for each CPU core.
+    pub hash_threads: usize,
+    /// Optimize archives as soon as files in them are replaced. This can
+    /// take longer than the patch itself, so by default it is left for the
+    /// "Optimize archives" action.
+    pub optimize_archives_while_patching: bool,
+    /// Keep the computer responsive while patching, at the cost of
+    /// patching more slowly. This overrides `hash_threads` and
+    /// `network.max_connections_per_host`.
+    pub low_impact: bool,
+    /// How often to check for a new patch while the launcher is left open,
+    /// in minutes. 0 turns this off.
+    pub recheck_minutes: u64,
+    /// Where the game is installed, if not next to the launcher. Relative
+    /// paths are relative to the launcher.
+    pub game_dir: Option<PathBuf>,
+    /// The name of the profile in use, if any. A profile's settings take the
+    /// place of `game_dir`, `channel` and `server`.
+    pub profile: Option<String>,
+    /// The patch server to use instead of the usual one. The
+    /// `AECO_PATCH_SERVER` environment variable and the `--server` option
+    /// take precedence over this.
+    pub server: Option<String>,
+}
+
+impl Default for PatchConfig {
+    fn default() -> Self {
+        Self {
+            channel: STABLE_CHANNEL.to_string(),
+            pinned_version: None,
+            components: Vec::new(),
+            languages: vec!["en".to_string()],
+            removed_files: RemovedFiles::Quarantine,
+            hash_cache: true,
+            hash_threads: 0,
+            optimize_archives_while_patching: false,
+            low_impact: false,
+            recheck_minutes: 30,
+            game_dir: None,
+            profile: None,
+            server: None,
+        }
+    }
+}
+
+impl PatchConfig {
+    pub fn recheck_interval(&self) -> Option<Duration> {
+        seconds(self.recheck_minutes.saturating_mul(60))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemovedFiles {
+    /// Leave them where they are
+    Keep,
+    /// Move them into the `removed` directory
+    Quarantine,
+    Delete,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// A proxy to send all requests through. If this is not set, the standard
+    /// `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment
+    /// variables are used instead.
+    pub proxy: Option<ProxyConfig>,
+    /// Credentials for patch servers which require them. The launcher asks
+    /// for a username and password if the server rejects these. They are
+    /// kept with the session rather than in the config file.
+    #[serde(skip)]
+    pub auth: Option<AuthConfig>,
+    /// Downloads larger than this many MiB are kept in a temporary file
+    /// instead of memory until they have finished
+    pub memory_download_limit_mb: u64,
+    /// Downloads which are larger than this many MiB, before or after
+    /// decompression, are refused
+    pub max_download_size_mb: u64,
+    /// How many times a download is attempted before giving up
+    pub download_attempts: u32,
+    /// Extract the base game while it is downloading instead of saving the
+    /// whole ZIP first. This needs half the disk space, but an interrupted
+    /// download has to start over from the beginning.
+    pub stream_base_game: bool,
+    /// How many connections to download the base game with at once
+    pub download_segments: u64,
+    /// Download the base game over BitTorrent when it isn't being streamed.
+    /// HTTP is used if this fails.
+    pub torrent: bool,
+    /// The BitTorrent client to use. It must accept aria2's options.
+    pub torrent_client: String,
+    /// How long to keep seeding after the download has finished. Patching
+    /// waits until seeding is done.
+    pub torrent_seed_minutes: u32,
+    /// How long to wait for a connection to the server. 0 waits forever.
+    pub connect_timeout_secs: u64,
+    /// How long a download may go without receiving any data. 0 waits
+    /// forever.
+    pub read_timeout_secs: u64,
+    /// How often to send TCP keep-alive probes. 0 disables them.
+    pub tcp_keepalive_secs: u64,
+    /// Let HTTP/2 connections grow their flow control window to suit the
+    /// connection, which helps on fast, high latency links
+    pub http2_adaptive_window: bool,
+    /// The most requests which may be sent to the patch server at once. Over
+    /// HTTP/2 they share a single connection, otherwise each one needs its
+    /// own connection.
+    pub max_connections_per_host: usize,
+    /// Refuse to talk to the patch server over plain HTTP
+    pub https_only: bool,
+    /// The most KiB per second which all downloads together may use. 0 means
+    /// there is no limit.
+    pub max_download_kbps: u64,
+    /// A CA certificate (PEM or DER) to trust instead of the system's
+    /// certificate authorities, so only a server whose certificate it signed
+    /// is accepted. It has to be a CA certificate; to accept a server by its
+    /// own certificate, pin its key with `pinned_public_keys` instead.
+    /// Relative paths are relative to the launcher.
+    pub pinned_certificate: Option<PathBuf>,
+    /// Only accept a patch server whose certificate has one of these public
+    /// keys, whoever signed it. Each one is the SHA-256 hash of the
+    /// certificate's SubjectPublicKeyInfo in base64, as printed by
+    /// `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der |
+    /// openssl dgst -sha256 -binary | base64`. Renewing the certificate with
+    /// the same key keeps it working; list the new key as well before
+    /// changing to it.
+    pub pinned_public_keys: Vec<String>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            auth: None,
+            memory_download_limit_mb: 64,
+            max_download_size_mb: 4096,
+            download_attempts: 5,
+            stream_base_game: false,
+            download_segments: 4,
+            torrent: false,
+            torrent_client: "aria2c".to_string(),
+            torrent_seed_minutes: 0,
+            connect_timeout_secs: 15,
+            read_timeout_secs: 30,
+            tcp_keepalive_secs: 60,
+            http2_adaptive_window: true,
+            max_connections_per_host: 6,
+            https_only: true,
+            max_download_kbps: 0,
+            pinned_certificate: None,
+            pinned_public_keys: Vec::new(),
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        seconds(self.connect_timeout_secs)
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        seconds(self.read_timeout_secs)
+    }
+
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        seconds(self.tcp_keepalive_secs)
+    }
+
+    pub fn memory_download_limit(&self) -> u64 {
+        self.memory_download_limit_mb.saturating_mul(MIB)
+    }
+
+    pub fn max_download_size(&self) -> u64 {
+        self.max_download_size_mb.saturating_mul(MIB)
+    }
+
+    /// The download speed limit in bytes per second, where 0 means there is
+    /// no limit
+    pub fn download_limit(&self) -> u64 {
+        self.max_download_kbps.saturating_mul(KIB)
+    }
+}
+
+const KIB: u64 = 1024;
+const MIB: u64 = 1024 * KIB;
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GameConfig {
+    /// Extra arguments to start the game with, after the ones the launcher
+    /// always passes
+    pub launch_args: Vec<String>,
+    /// Keep the launcher open for a minute after starting the game, and say
+    /// so if it crashes in that time
+    pub monitor_launch: bool,
+    /// Start the game by itself once patching finishes without errors,
+    /// after a short countdown which the player can cancel
+    pub auto_launch: bool,
+    /// Check the game's programs against the digests they were last patched
+    /// with before starting it, so damaged files are noticed before the game
+    /// crashes
+    pub quick_check: bool,
+    /// How much CPU time the game gets compared to other programs
+    pub priority: Priority,
+    /// The CPU cores the game may run on, counting from 0. Empty for all of
+    /// them.
+    pub cpu_affinity: Vec<usize>,
+    /// The name of the realm the player chose. Without one, the first realm
+    /// is used.
+    pub realm: Option<String>,
+    /// A command to run before the game starts, as the program followed by
+    /// its arguments, such as to start a voice chat overlay. The game starts
+    /// once it finishes.
+    pub pre_launch_hook: Vec<String>,
+    /// A command to run once the game exits. The launcher stays open until
+    /// then to run it.
+    pub post_exit_hook: Vec<String>,
+    /// How many seconds a hook may run for before it is stopped. 0 means
+    /// the default.
+    pub hook_timeout_secs: u64,
+    /// The hooks the player has agreed to run. A hook which changes is
+    /// asked about again.
+    pub approved_hooks: Vec<String>,
+    /// Environment variables to start the game with, on top of the
+    /// launcher's own, such as `WINEPREFIX` or `DXVK_HUD`. This is a table,
+    /// so it has to come last.
+    pub environment: BTreeMap<String, String>,
+}
+
+/// Logging in to a game account before the game starts
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct AccountConfig {
+    /// Where to log in. Without one, the game starts without logging in.
+    /// The launcher posts `{"username": ..., "password": ...}` as JSON, and
+    /// expects `{"token": ..., "expires_in": ...}` back, where `expires_in`
+    /// is an optional number of seconds.
+    pub login_url: Option<String>,
+    /// An argument to start the game with, where `{token}` is replaced with
+    /// the session token. Empty for none.
+    pub token_arg: String,
+    /// An environment variable to start the game with, which holds the
+    /// session token. Empty for none.
+    pub token_env: String,
+}
+
+impl Default for AccountConfig {
+    fn default() -> Self {
+        Self {
+            login_url: None,
+            token_arg: String::new(),
+            token_env: "AECO_SESSION_TOKEN".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// The language to show the launcher in, as a language code such as
+    /// "en" or "ja". If this is empty, the system's language is used.
+    pub language: String,
+    pub theme: Theme,
+    /// The color of the progress bar, buttons and status text, as a hex
+    /// color such as "#4e804e". If this is empty, the launcher's own green is
+    /// used.
+    pub accent_color: String,
+    /// Show a desktop notification when patching finishes or fails
+    pub notifications: bool,
+    /// Read out the launcher's text as it is focused, for players who use
+    /// a screen reader
+    pub screen_reader: bool,
+    /// Show what the launcher is doing on the player's Discord profile
+    pub discord_presence: bool,
+    /// Where the window was when the launcher was last closed
+    pub window: Option<WindowState>,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            language: String::new(),
+            theme: Theme::Auto,
+            accent_color: String::new(),
+            notifications: true,
+            screen_reader: false,
+            discord_presence: true,
+            window: None,
+        }
+    }
+}
+
+/// Reports which help the launcher's developers fix problems players run
+/// into
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DiagnosticsConfig {
+    /// Whether to send a report when the launcher crashes. Reports are
+    /// always written to the log folder either way.
+    pub crash_reports: CrashReports,
+    /// Where to send crash reports. Without one, they go to the address
+    /// built into the launcher, if it has one.
+    pub crash_report_url: Option<String>,
+    /// Share how patches went, such as how long they took, how much they
+    /// downloaded and why they failed, without anything which says who the
+    /// player is
+    pub telemetry: bool,
+    /// Where to send those statistics. Without one, they go to the address
+    /// built into the launcher, if it has one.
+    pub telemetry_url: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CrashReports {
+    /// Ask the player about each crash
+    #[default]
+    Ask,
+    Send,
+    Never,
+}
+
+/// The launcher window's position and size, and the size of the monitor it
+/// was on, which tells whether it is still on the same monitor. The window
+/// can't be resized, so its size is only used to keep it on the screen.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct WindowState {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub monitor_width: f32,
+    pub monitor_height: f32,
+}
+
+/// Whether the launcher is dark or light
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Follows the system's theme
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+/// How much CPU time the game gets compared to other programs. Raising it
+/// above normal needs administrator rights on some systems.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Idle,
+    BelowNormal,
+    #[default]
+    Normal,
+    AboveNormal,
+    High,
+}
+
+/// The settings which can be changed from the launcher's settings screen.
+/// Everything else is only in the config file.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Settings {
+    /// Where the game is installed. `None` means the default location.
+    pub game_dir: Option<PathBuf>,
+    pub max_download_kbps: u64,
+    /// 0 means one for each CPU core
+    pub hash_threads: usize,
+    pub max_downloads: usize,
+    pub low_impact: bool,
+    pub channel: String,
+    pub language: String,
+    pub theme: Theme,
+    pub accent_color: String,
+    pub notifications: bool,
+    pub screen_reader: bool,
+    pub discord_presence: bool,
+    pub launch_args: Vec<String>,
+    pub environment: BTreeMap<String, String>,
+    pub monitor_launch: bool,
+    pub auto_launch: bool,
+    pub quick_check: bool,
+    pub priority: Priority,
+    pub cpu_affinity: Vec<usize>,
+    pub crash_reports: CrashReports,
+    pub telemetry: bool,
+    pub torrent: bool,
+    /// Whether the torrent client could be found. Torrents can't be used
+    /// without it. Only the worker fills this in.
+    #[serde(skip)]
+    pub torrent_client_found: bool,
+}
+
+/// How many downloads may run at once in low impact mode
+const LOW_IMPACT_DOWNLOADS: usize = 2;
+
+/// How long hooks may run for unless the config says otherwise
+const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many CPU cores there are to work with, which is 1 if it can't be
+/// found out
+pub fn cpu_cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |cores| cores.get())
+}
+
+/// Converts a number of seconds from the config into a Duration, where 0
+/// means the setting is disabled
+fn seconds(secs: u64) -> Option<Duration> {
+    match secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ProxyConfig {
+    /// Example: `http://proxy.example.com:8080`
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Hosts which should be connected to directly. A leading `.` or `*.`
+    /// matches all subdomains, and `*` matches every host.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+/// How to authenticate with the patch server
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AuthConfig {
+    Basic { username: String, password: String },
+    Bearer { token: String },
+}
+
+impl Config {
+    /// The settings shown on the settings screen. While an install profile
+    /// is in use, its game directory and channel are shown instead.
+    pub fn settings(&self) -> Settings {
+        let (game_dir, channel) = match self.active_profile() {
+            Some(profile) => (profile.game_dir.clone(), profile.channel.clone()),
+            None => (self.patch.game_dir.clone(), self.patch.channel.clone()),
+        };
+        Settings {
+            game_dir,
+            max_download_kbps: self.network.max_download_kbps,
+            hash_threads: self.patch.hash_threads,
+            max_downloads: self.network.max_connections_per_host,
+            low_impact: self.patch.low_impact,
+            channel,
+            language: self.ui.language.clone(),
+            theme: self.ui.theme,
+            accent_color: self.ui.accent_color.clone(),
+            notifications: self.ui.notifications,
+            screen_reader: self.ui.screen_reader,
+            discord_presence: self.ui.discord_presence,
+            launch_args: self.game.launch_args.clone(),
+            environment: self.game.environment.clone(),
+            monitor_launch: self.game.monitor_launch,
+            auto_launch: self.game.auto_launch,
+            quick_check: self.game.quick_check,
+            priority: self.game.priority,
+            cpu_affinity: self.game.cpu_affinity.clone(),
+            crash_reports: self.diagnostics.crash_reports,
+            telemetry: self.diagnostics.telemetry,
+            torrent: self.network.torrent,
+            torrent_client_found: false,
+        }
+    }
+
+    /// Takes over the settings from the settings screen
+    pub fn apply_settings(&mut self, settings: Settings) {
+        if let Some(name) = self.patch.profile.clone() {
+            if let Some(profile) = self.profiles.iter_mut().find(|p| p.name == name) {
+                profile.game_dir = settings.game_dir.clone();
+                profile.channel = settings.channel.clone();
+            }
+        } else {
+            self.patch.game_dir = settings.game_dir;
+        }
+        self.patch.channel = settings.channel;
+        self.network.max_download_kbps = settings.max_download_kbps;
+        self.patch.hash_threads = settings.hash_threads;
+        self.network.max_connections_per_host = settings.max_downloads.max(1);
+        self.patch.low_impact = settings.low_impact;
+        self.ui.language = settings.language;
+        self.ui.theme = settings.theme;
+        self.ui.accent_color = settings.accent_color;
+        self.ui.notifications = settings.notifications;
+        self.ui.screen_reader = settings.screen_reader;
+        self.ui.discord_presence = settings.discord_presence;
+        self.game.launch_args = settings.launch_args;
+        self.game.environment = settings.environment;
+        self.game.monitor_launch = settings.monitor_launch;
+        self.game.auto_launch = settings.auto_launch;
+        self.game.quick_check = settings.quick_check;
+        self.game.priority = settings.priority;
+        self.game.cpu_affinity = settings.cpu_affinity;
+        self.diagnostics.crash_reports = settings.crash_reports;
+        self.diagnostics.telemetry = settings.telemetry;
+        self.network.torrent = settings.torrent;
+    }
+
+    /// How long hooks may run for before they are stopped
+    pub fn hook_timeout(&self) -> Duration {
+        match self.game.hook_timeout_secs {
+            0 => DEFAULT_HOOK_TIMEOUT,
+            secs => Duration::from_secs(secs),
+        }
+    }
+
+    /// How many threads hash files while checking the game
+    pub fn hash_threads(&self) -> usize {
+        let cores = cpu_cores();
+        if self.patch.low_impact {
+            // Leave most of the CPU to everything else
+            return (cores / 4).max(1);
+        }
+        match self.patch.hash_threads {
+            0 => cores,
+            threads => threads,
+        }
+    }
+
+    /// How many downloads may run at once
+    pub fn simultaneous_downloads(&self) -> usize {
+        let downloads = self.network.max_connections_per_host.max(1);
+        if self.patch.low_impact {
+            downloads.min(LOW_IMPACT_DOWNLOADS)
+        } else {
+            downloads
+        }
+    }
+
+    fn active_profile(&self) -> Option<&Profile> {
+        let name = self.patch.profile.as_ref()?;
+        self.profiles.iter().find(|p| &p.name == name)
+    }
+
+    /// Loads the configuration from a file, returning the default
+    /// configuration if the file does not exist
+    pub fn load<P>(path: P) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&text)?)
+    }
+
+    pub fn save<P>(&self, path: P) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Remembers where the window is in the config file. The GUI does this
+    /// itself as it closes, since the worker may still be busy.
+    pub fn save_window_state<P>(path: P, window: WindowState) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut config = Self::load(path)?;
+        config.ui.window = Some(window);
+        config.save(path)
+    }
+}
@@ -0,0 +1,99 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::error;
+
+use crate::logging::timestamp;
+use crate::version::version_summary;
+
+/// Where crash reports are written, in the log folder
+const CRASH_DIR: &str = "crashes";
+/// Where reports go once they were sent or the player chose not to send
+/// them, so they are only offered once. They are kept for support requests.
+const HANDLED_DIR: &str = "handled";
+
+/// Where the launcher writes a report when it crashes
+pub fn crash_dir(log_dir: &Path) -> PathBuf {
+    log_dir.join(CRASH_DIR)
+}
+
+/// Writes a crash report whenever the launcher panics, before Rust's usual
+/// message is printed
+pub fn install(log_dir: &Path) {
+    let dir = crash_dir(log_dir);
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("(no message)");
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_default();
+        match write(&dir, message, &location) {
+            Ok(path) => error!("The launcher crashed, see {}", path.display()),
+            Err(why) => error!("The launcher crashed, and the report could not be written: {why}"),
+        }
+        default_hook(info);
+    }));
+}
+
+/// Writes a report of a panic, with a backtrace and what it happened on,
+/// and returns where it went
+fn write(dir: &Path, message: &str, location: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let thread = std::thread::current();
+    let mut report = String::new();
+    writeln!(report, "Launcher: {}", version_summary())?;
+    writeln!(
+        report,
+        "System: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(report, "Time: {}", timestamp())?;
+    writeln!(report, "Thread: {}", thread.name().unwrap_or("(unnamed)"))?;
+    writeln!(report, "Message: {message}")?;
+    writeln!(report, "Location: {location}")?;
+    writeln!(report)?;
+    writeln!(report, "{}", std::backtrace::Backtrace::force_capture())?;
+
+    std::fs::create_dir_all(dir)?;
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = dir.join(format!("crash-{seconds}-{}.txt", std::process::id()));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+/// Crash reports which haven't been sent or turned down yet
+pub fn pending(log_dir: &Path) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(crash_dir(log_dir)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut reports = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "txt"))
+        .collect::<Vec<_>>();
+    reports.sort();
+    reports
+}
+
+/// Moves a report out of the way once it was sent or turned down
+pub fn set_aside(report: &Path) -> Result<(), Box<dyn Error>> {
+    let dir = report
+        .parent()
+        .ok_or("Crash report has no directory")?
+        .join(HANDLED_DIR);
+    std::fs::create_dir_all(&dir)?;
+    std::fs::rename(report, dir.join(report.file_name().unwrap_or_default()))?;
+    Ok(())
+}
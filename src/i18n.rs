@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::RwLock;
+
+use tracing::warn;
+
+/// The languages the launcher can be shown in, as their language code and
+/// their own name for themselves
+pub const LANGUAGES: [(&str, &str); 2] = [("en", "English"), ("ja", "日本語")];
+
+/// Translations of the launcher's text. The launcher is written in English,
+/// so each bundle maps the English text to the text in its language. Text
+/// which is missing from a bundle stays in English.
+const BUNDLES: [(&str, &str); 2] = [
+    ("en", include_str!("../assets/i18n/en.toml")),
+    ("ja", include_str!("../assets/i18n/ja.toml")),
+];
+
+/// The bundle in use. It is shared by the worker and the GUI, so switching
+/// languages changes both.
+static BUNDLE: RwLock<Option<HashMap<String, String>>> = RwLock::new(None);
+
+/// Switches to a language, given its language code. An empty code means the
+/// system's language, and languages without a bundle fall back to English.
+pub fn set_language(code: &str) {
+    let code = resolve(code);
+    let bundle = BUNDLES
+        .iter()
+        .find(|(bundle_code, _)| *bundle_code == code)
+        .and_then(|(_, text)| match toml::from_str(text) {
+            Ok(bundle) => Some(bundle),
+            Err(why) => {
+                warn!("Could not load the '{code}' language bundle: {why}");
+                None
+            }
+        });
+    *BUNDLE.write().unwrap() = bundle;
+}
+
+/// Picks the language to use for a configured language code
+pub fn resolve(code: &str) -> &'static str {
+    let locale = match code.trim() {
+        "" => sys_locale::get_locale().unwrap_or_default(),
+        code => code.to_string(),
+    };
+    // Regional variants such as "ja-JP" or "en_US" use the main language
+    let language = locale
+        .split(['-', '_', '.'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .map(|(code, _)| *code)
+        .find(|code| *code == language)
+        .unwrap_or("en")
+}
+
+/// Looks up the translation of some English text. Use `tr!` instead.
+pub fn translate(text: &str) -> String {
+    BUNDLE
+        .read()
+        .unwrap()
+        .as_ref()
+        .and_then(|bundle| bundle.get(text))
+        .cloned()
+        .unwrap_or_else(|| text.to_string())
+}
+
+/// Fills in the `{name}` placeholders of a translated text. Use `tr!`
+/// instead.
+pub fn fill(mut text: String, values: &[(&str, &dyn Display)]) -> String {
+    for (name, value) in values {
+        text = text.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    text
+}
+
+/// Translates English text into the chosen language. Like `format!`, values
+/// can be given for `{name}` placeholders, which are filled in after the
+/// text is translated:
+///
+/// `tr!("Moved {count} files", count = moved)`
+macro_rules! tr {
+    ($text:literal) => {
+        $crate::i18n::translate($text)
+    };
+    ($text:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::fill(
+            $crate::i18n::translate($text),
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_uses_the_main_language_of_a_locale() {
+        assert_eq!(resolve("ja"), "ja");
+        assert_eq!(resolve("ja-JP"), "ja");
+        assert_eq!(resolve("JA_jp.UTF-8"), "ja");
+        assert_eq!(resolve(" en_US "), "en");
+    }
+
+    #[test]
+    fn resolve_falls_back_to_english() {
+        assert_eq!(resolve("fr-FR"), "en");
+        assert_eq!(resolve("-"), "en");
+    }
+
+    #[test]
+    fn fill_replaces_every_placeholder() {
+        let text = "{count} of {total} files, {count} done".to_string();
+        let filled = fill(text, &[("count", &3), ("total", &"10")]);
+        assert_eq!(filled, "3 of 10 files, 3 done");
+    }
+
+    #[test]
+    fn fill_leaves_unknown_placeholders() {
+        let filled = fill("{name} is {missing}".to_string(), &[("name", &"Launcher")]);
+        assert_eq!(filled, "Launcher is {missing}");
+    }
+}
@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::prelude::*;
+
+/// Where logs go in portable mode, next to the launcher
+const LOG_DIR: &str = "logs/";
+/// Each day's log is written to launcher.<date>.log
+const LOG_FILE_PREFIX: &str = "launcher";
+const LOG_FILE_SUFFIX: &str = "log";
+/// How many days of logs are kept
+const MAX_LOG_FILES: usize = 5;
+/// Setting this to "debug" or "trace" logs more
+const LOG_LEVEL_ENV: &str = "AECO_LOG";
+
+/// Where the launcher keeps its logs: next to it in portable mode, and in
+/// the platform's usual place for logs otherwise
+pub fn log_dir(launcher_dir: &Path, portable: bool) -> PathBuf {
+    if portable {
+        return launcher_dir.join(LOG_DIR);
+    }
+    platform_log_dir().unwrap_or_else(|| launcher_dir.join(LOG_DIR))
+}
+
+#[cfg(windows)]
+fn platform_log_dir() -> Option<PathBuf> {
+    let local = std::env::var_os("LOCALAPPDATA").filter(|dir| !dir.is_empty())?;
+    Some(PathBuf::from(local).join("AECO Launcher").join("logs"))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_log_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").filter(|dir| !dir.is_empty())?;
+    Some(PathBuf::from(home).join("Library/Logs/AECO Launcher"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn platform_log_dir() -> Option<PathBuf> {
+    let state = match std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME").filter(|dir| !dir.is_empty())?)
+            .join(".local/state"),
+    };
+    Some(state.join("aeco-launcher/logs"))
+}
+
+/// Sends everything logged with `tracing` to the log files in `dir`, as
+/// well as to the error output like before
+pub fn init(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let max_level = match std::env::var(LOG_LEVEL_ENV).as_deref() {
+        Ok("trace") => LevelFilter::TRACE,
+        Ok("debug") => LevelFilter::DEBUG,
+        _ => LevelFilter::INFO,
+    };
+    let file = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)?;
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(file)
+                .with_ansi(false),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .without_time()
+                .with_target(false),
+        )
+        .with(max_level)
+        .try_init()?;
+    Ok(())
+}
+
+/// The current time in UTC, written the same way as in the logs
+pub fn timestamp() -> String {
+    let mut time = String::new();
+    let _ = SystemTime.format_time(&mut Writer::new(&mut time));
+    time
+}
@@ -1,24 +1,82 @@
 // Don't open a command prompt on Windows
 #![windows_subsystem = "windows"]
 
+#[macro_use]
+mod i18n;
+mod cli;
+mod config;
+mod crash_report;
+mod logging;
 mod message;
 mod patcher;
 mod ui;
 mod version;
 use message::{GUIMessage, PatchMessage};
+use std::process::ExitCode;
 use std::sync::mpsc::channel;
+use std::thread::JoinHandle;
+use tracing::{error, warn};
 
-fn main() {
-    let (gui_tx, gui_rx) = channel::<GUIMessage>();
-    let (patch_tx, patch_rx) = channel::<PatchMessage>();
+fn main() -> ExitCode {
+    let args = match cli::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(why) => return cli::usage_error(&why),
+    };
 
-    let patchworker = match patcher::PatchWorker::new(patch_tx, gui_rx) {
-        Ok(patchworker) => patchworker,
+    // A second copy of the launcher would patch the same files as the first,
+    // so nothing else is done before taking the lock
+    let headless = args.command.is_some() || args.json_progress;
+    let launcher_dir = match patcher::launcher_dir() {
+        Ok(launcher_dir) => launcher_dir,
         Err(why) => {
-            eprintln!("Could not initialize patch worker: {why}");
-            return;
+            eprintln!("Could not find the launcher directory: {why}");
+            return ExitCode::FAILURE;
         }
     };
+    let (instance_lock, lock_error) = match patcher::InstanceLock::acquire(&launcher_dir) {
+        Ok(Some(lock)) => (Some(lock), None),
+        Ok(None) if headless => {
+            eprintln!(
+                "The launcher in {} is already running. Close it and try again.",
+                launcher_dir.display()
+            );
+            return ExitCode::FAILURE;
+        }
+        Ok(None) => {
+            ui::raise_running();
+            return ExitCode::SUCCESS;
+        }
+        // Not being able to lock shouldn't stop the game from being played
+        Err(why) => (None, Some(why)),
+    };
+
+    let log_dir = logging::log_dir(
+        &launcher_dir,
+        patcher::is_portable(&launcher_dir, args.portable),
+    );
+    if let Err(why) = logging::init(&log_dir) {
+        eprintln!("Could not start logging: {why}");
+    }
+    crash_report::install(&log_dir);
+    if let Some(why) = lock_error {
+        warn!("Could not lock the launcher directory: {why}");
+    }
+
+    let (gui_tx, gui_rx) = channel::<GUIMessage>();
+    let (patch_tx, patch_rx) = channel::<PatchMessage>();
+
+    let mut patchworker =
+        match patcher::PatchWorker::new(patch_tx, gui_rx, args.server, args.portable) {
+            Ok(patchworker) => patchworker,
+            Err(why) => {
+                error!("Could not initialize patch worker: {why}");
+                return ExitCode::FAILURE;
+            }
+        };
+    patchworker.instance_lock = instance_lock;
+    patchworker.big_picture = args.big_picture;
+    patchworker.may_elevate = !args.no_elevate;
+    i18n::set_language(&patchworker.config.ui.language);
 
     // Check for whether the patcher is a temporary updated one before creating
     // a GUI.
@@ -26,11 +84,65 @@ fn main() {
     // operation again, and if it fails again, it will be able to display an
     // error message to the user.
     match patchworker.check_patcher_aecoupdate() {
-        Ok(patcher::RunState::Close) => return,
+        Ok(patcher::RunState::Close) => return ExitCode::SUCCESS,
         Ok(patcher::RunState::Continue) => {}
-        Err(why) => eprintln!("{:?}", why.internal_error),
+        Err(why) => error!("{:?}", why.internal_error),
     }
 
-    std::thread::spawn(move || patchworker.run());
-    ui::PatcherUI::run(gui_tx, patch_rx, false);
+    // Without a window, a launcher which was just updated counts as working
+    // once it gets this far
+    if headless {
+        patcher::mark_healthy();
+    }
+
+    // Without a command, the GUI opens
+    if let Some(command) = args.command {
+        return command.run(patchworker, patch_rx);
+    }
+
+    // Only show what patching would change
+    let first_message = if args.check_only {
+        GUIMessage::Preview
+    } else {
+        GUIMessage::Retry
+    };
+
+    if args.json_progress {
+        return cli::run_json_progress(patchworker, first_message, gui_tx, patch_rx);
+    }
+
+    let channel = patchworker.config.patch.channel.clone();
+    let window = ui::SavedWindow::new(
+        patchworker.launcher_dir.join(config::CONFIG_FILE),
+        patchworker.config.ui.window,
+    );
+
+    let worker = std::thread::spawn(move || patchworker.run(first_message));
+    ui::PatcherUI::run(
+        gui_tx,
+        patch_rx,
+        false,
+        channel,
+        window,
+        args.big_picture,
+        ui::Hooks {
+            // A launcher which was just updated counts as working once its
+            // window opens
+            started: Box::new(patcher::mark_healthy),
+            worker_closed: Box::new(move || exit_with_game(worker)),
+        },
+    );
+    ExitCode::SUCCESS
+}
+
+/// Waits for the worker to finish closing, which sends its telemetry and
+/// unlocks the game folder, then exits with the game's exit code if the
+/// worker waited for the game. The GUI's event loop never returns, so the
+/// exit code can't be passed back through main().
+fn exit_with_game(worker: JoinHandle<Option<i32>>) {
+    match worker.join() {
+        Ok(Some(exit_code)) => std::process::exit(exit_code),
+        Ok(None) => {}
+        Err(_) => error!("The patch worker panicked"),
+    }
 }
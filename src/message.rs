@@ -1,19 +1,702 @@
+use crate::config::Settings;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Serialized as `{"event": "name", "data": ...}` for `--json-progress`
+#[derive(Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
 pub enum PatchMessage {
-    Error(String),
+    /// What the worker is doing, which decides what it will do next
+    State(WorkerState),
+    /// Something went wrong, and more about why for support requests
+    Error(String, ErrorDetails),
     Downloading(String, f32),
+    /// Like `Downloading`, but for downloads where the speed is known
+    Transfer(String, f32, TransferRate),
     Info(String),
     PatchStatus(PatchStatus),
+    /// The version of the game which is installed, and the newest version
+    /// available. Either is unknown if the server doesn't publish versions.
+    Versions {
+        installed: Option<String>,
+        available: Option<String>,
+    },
+    /// The optional components the patch list offers, and which of them are
+    /// installed
+    Components {
+        available: Vec<String>,
+        enabled: Vec<String>,
+    },
+    /// The game's language packs the patch list offers, and which of them
+    /// are installed
+    GameLanguages {
+        available: Vec<String>,
+        enabled: Vec<String>,
+    },
+    /// Files in the game directory which don't belong to the game or the
+    /// launcher, relative to the game directory
+    ForeignFiles(Vec<String>),
+    /// Nothing has been set up yet. The worker waits for
+    /// `GUIMessage::FinishSetup` before installing the game.
+    FirstRun,
+    /// The game exited with an error soon after starting. The text says how
+    /// it exited, and the output is the end of its error output.
+    GameCrashed {
+        text: String,
+        output: String,
+    },
+    /// The game account needs logging in to before the game can start, and
+    /// why the last attempt failed if there was one
+    AccountLogin(Option<String>),
+    /// Hook commands which are about to run for the first time, so the
+    /// player has to agree to them before the game starts
+    ConfirmHooks(Vec<String>),
+    /// Game files which were damaged when the game was about to start, so it
+    /// wasn't started
+    RepairNeeded(Vec<String>),
+    /// Seconds until the game starts by itself, or `None` once the countdown
+    /// has stopped
+    AutoLaunch(Option<u64>),
+    /// Where the launcher writes its logs
+    LogDir(PathBuf),
+    /// The launcher crashed since it last ran. The worker waits for
+    /// `GUIMessage::CrashReports` before sending the reports anywhere.
+    CrashReports,
+    /// The other programs which come with the game, as their IDs and their
+    /// untranslated names
+    LaunchTargets(Vec<(String, String)>),
+    /// What a finished patch did, sent just before it reports being finished
+    Summary(PatchReport),
+    /// A new patch came out while the launcher was left open
+    UpdateAvailable,
+    /// Roughly how much the patch which is starting will download, in bytes
+    UpdateSize(u64),
+    /// The install profiles to choose from, which one is in use, and the
+    /// release channel that puts the launcher on
+    Profiles {
+        names: Vec<String>,
+        active: Option<String>,
+        channel: String,
+    },
+    /// The realms to choose from and the one chosen. Their pings are sent
+    /// again once `measuring` is done.
+    Realms {
+        realms: Vec<RealmInfo>,
+        selected: Option<String>,
+        measuring: bool,
+    },
+    /// The current settings, for the settings screen
+    Settings(Settings),
+    /// The patch server in use, if it isn't the usual one
+    Server(Option<String>),
+    /// Recent announcements from the patch server, newest first
+    News(Vec<NewsItem>),
+    /// What the patch server says about the game server, sent along with
+    /// every status check
+    ServerDetails(ServerDetails),
+    /// The patch notes of the version being updated to, in Markdown
+    Changelog {
+        version: String,
+        text: String,
+    },
+    /// How each part of reaching the patch server went
+    ConnectionReport(ConnectionReport),
+    /// What happened to a file while patching, relative to the game
+    /// directory, and how many bytes were downloaded for it
+    FileResult {
+        path: String,
+        action: FileAction,
+        bytes: u64,
+    },
+    /// The worker is still getting somewhere, sent every few seconds while
+    /// it is busy with something which says nothing else
+    Heartbeat,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PatchStatus {
     Finished,
+    /// The patch server couldn't be reached, but the game was patched before
+    Offline,
+    /// The patch server needs a username and password
+    Unauthorized,
+    /// The patch server needs a newer version of the launcher
+    LauncherOutdated,
+    /// The game server is down for maintenance
+    Maintenance,
+    /// The game needs to be closed before it can be patched
+    GameRunning,
+    /// The game was removed
+    Uninstalled,
+    /// The installation was verified and repaired
+    Repaired(RepairSummary),
+    /// What patching would change, without anything having been changed
+    Preview(PatchPreview),
     Working,
     Error,
     Close,
 }
 
+/// What the worker is busy with. The GUI should only send messages which
+/// the current state accepts, and the worker ignores any others.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerState {
+    /// Waiting for the player, but the game can't be played as it is
+    Idle,
+    /// Patching, verifying or otherwise changing the game
+    Patching,
+    /// Waiting for the player, and the game can be played
+    ReadyToPlay,
+    /// Starting the game
+    Launching,
+}
+
+impl WorkerState {
+    /// What the worker is doing after reporting a status, if that changes it
+    pub fn after(status: &PatchStatus) -> Option<Self> {
+        match status {
+            PatchStatus::Working => Some(Self::Patching),
+            PatchStatus::Finished | PatchStatus::Offline | PatchStatus::LauncherOutdated => {
+                Some(Self::ReadyToPlay)
+            }
+            PatchStatus::Repaired(summary) if summary.failed == 0 => Some(Self::ReadyToPlay),
+            PatchStatus::Preview(preview) if preview.is_up_to_date() => Some(Self::ReadyToPlay),
+            PatchStatus::Repaired(_)
+            | PatchStatus::Preview(_)
+            | PatchStatus::Unauthorized
+            | PatchStatus::Maintenance
+            | PatchStatus::GameRunning
+            | PatchStatus::Uninstalled
+            | PatchStatus::Error => Some(Self::Idle),
+            PatchStatus::Close => None,
+        }
+    }
+
+    /// Whether the worker is in the middle of something
+    pub fn is_busy(self) -> bool {
+        matches!(self, Self::Patching | Self::Launching)
+    }
+
+    /// Whether the worker can act on a message while in this state
+    pub fn accepts(self, message: &GUIMessage) -> bool {
+        match message {
+            GUIMessage::Close => true,
+            GUIMessage::Play | GUIMessage::PlayTarget(_) => self == Self::ReadyToPlay,
+            _ => !self.is_busy(),
+        }
+    }
+}
+
+/// Read as `{"command": "name", "args": ...}` for `--json-progress`
+#[derive(Deserialize)]
+#[serde(tag = "command", content = "args", rename_all = "snake_case")]
 pub enum GUIMessage {
     Retry,
+    /// Retry with credentials for the patch server
+    Login {
+        username: String,
+        password: String,
+    },
+    /// Check every file, and repair any which are damaged
+    Verify,
+    /// Find out what patching would change without changing anything
+    Preview,
+    /// Switch to another release channel and patch to it
+    SetChannel(String),
+    /// Go back to the game files from before the most recent patch
+    Rollback,
+    /// Choose which optional components are installed, and patch
+    SetComponents(Vec<String>),
+    /// Choose which of the game's language packs are installed, and patch
+    SetGameLanguages(Vec<String>),
+    /// Look for files which don't belong in the game directory
+    ScanForeignFiles,
+    /// Move files found by a scan into the quarantine directory
+    QuarantineFiles(Vec<String>),
+    /// Remove the game and the launcher's state. With `everything`, the
+    /// player's settings and saved login go too.
+    Uninstall {
+        everything: bool,
+    },
+    /// Move the game to another directory, then verify it
+    MoveInstall(PathBuf),
+    /// Switch to another install profile and patch it
+    SelectProfile(String),
+    /// Agree to run these hook commands, then start the game
+    ApproveHooks(Vec<String>),
+    /// Choose which realm the game connects to
+    SelectRealm(String),
+    /// Measure the ping to each realm again
+    MeasureRealms,
+    /// Check each step of reaching the patch server, to tell whether a
+    /// problem is with the player's connection or the server
+    TestConnection,
+    /// Give back the space left in archives by files which were replaced
+    /// since they were last optimized
+    OptimizeArchives,
+    /// Whether to send the reports of the launcher crashing, and whether to
+    /// do the same for future crashes without asking
+    CrashReports {
+        send: bool,
+        remember: bool,
+    },
+    /// Save changes made on the settings screen
+    UpdateSettings(Settings),
+    /// Don't start the game by itself after all
+    #[serde(alias = "cancel")]
+    CancelAutoLaunch,
+    /// Log in to the game account, then start the game
+    AccountLogin {
+        username: String,
+        password: String,
+    },
+    /// What the player chose when setting up the launcher for the first
+    /// time. The game's language pack follows the launcher's language.
+    FinishSetup {
+        settings: Settings,
+        create_shortcut: bool,
+    },
     Play,
+    /// Start one of the other programs which come with the game, by its ID
+    PlayTarget(String),
     Close,
 }
+
+/// Details which newer patch servers publish along with the server status
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServerDetails {
+    /// The message of the day, or what the maintenance is for
+    pub message: Option<String>,
+    /// When maintenance is expected to be over, in seconds since the Unix
+    /// epoch
+    pub maintenance_end: Option<u64>,
+    /// How busy the game server is
+    pub population: Option<Population>,
+}
+
+impl ServerDetails {
+    /// How long until maintenance is expected to be over. This is zero once
+    /// the time has passed, and `None` if the server didn't say.
+    pub fn maintenance_remaining(&self) -> Option<Duration> {
+        let end = self.maintenance_end?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Some(Duration::from_secs(end.saturating_sub(now)))
+    }
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Population {
+    Low,
+    Medium,
+    High,
+    Full,
+}
+
+impl Display for Population {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            Population::Low => tr!("Low"),
+            Population::Medium => tr!("Medium"),
+            Population::High => tr!("High"),
+            Population::Full => tr!("Full"),
+        };
+        f.write_str(&text)
+    }
+}
+
+/// A realm as the realm picker shows it
+#[derive(Clone, Serialize)]
+pub struct RealmInfo {
+    pub name: String,
+    /// How long connecting to the realm took, or `None` if it hasn't been
+    /// measured or didn't answer
+    pub ping: Option<Duration>,
+}
+
+/// An entry of the news published on the patch server
+#[derive(Deserialize, Serialize)]
+pub struct NewsItem {
+    pub title: String,
+    #[serde(default)]
+    pub kind: NewsKind,
+    /// When it was posted, as the server wrote it
+    #[serde(default)]
+    pub date: Option<String>,
+    #[serde(default)]
+    pub body: String,
+    /// Where to read more
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NewsKind {
+    Maintenance,
+    PatchNotes,
+    /// Kinds of news this launcher doesn't know about are shown as
+    /// announcements too
+    #[default]
+    #[serde(other)]
+    Announcement,
+}
+
+/// Why an error happened, in more detail than its message
+#[derive(Clone, Default, Serialize)]
+pub struct ErrorDetails {
+    /// The error and what caused it, starting with the error itself
+    pub causes: Vec<String>,
+    /// The address which was being requested
+    pub url: Option<String>,
+    /// What the server answered with
+    pub http_status: Option<u16>,
+    /// What kind of problem it was
+    pub category: ErrorCategory,
+}
+
+impl Display for ErrorDetails {
+    /// Example:
+    ///
+    /// ```text
+    /// Error: error sending request
+    /// Caused by: connection refused
+    /// URL: https://example.com/patch/patch.json
+    /// HTTP status: 503
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, cause) in self.causes.iter().enumerate() {
+            match index {
+                0 => writeln!(f, "Error: {cause}")?,
+                _ => writeln!(f, "Caused by: {cause}")?,
+            }
+        }
+        if let Some(url) = &self.url {
+            writeln!(f, "URL: {url}")?;
+        }
+        if let Some(status) = self.http_status {
+            writeln!(f, "HTTP status: {status}")?;
+        }
+        writeln!(f, "Category: {}", self.category.name())?;
+        Ok(())
+    }
+}
+
+/// What kind of problem an error was, which decides what the player is told
+/// they can do about it
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorCategory {
+    /// The patch server couldn't be reached
+    Network,
+    /// The patch server answered with an error
+    Server,
+    /// Files couldn't be read or written
+    Disk,
+    /// Something the patch server sent couldn't be understood
+    Parse,
+    /// A download didn't match its digest or signature
+    Verification,
+    #[default]
+    Other,
+}
+
+impl ErrorCategory {
+    /// As it is serialized, for support requests
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Network => "network",
+            Self::Server => "server",
+            Self::Disk => "disk",
+            Self::Parse => "parse",
+            Self::Verification => "verification",
+            Self::Other => "other",
+        }
+    }
+
+    /// What the player can try, for every kind of error in one place
+    pub fn advice(self) -> Option<String> {
+        let advice = match self {
+            Self::Network => tr!(
+                "Check your internet connection, and that no firewall or proxy is blocking the launcher."
+            ),
+            Self::Server => tr!("The patch server is having trouble. Please try again later."),
+            Self::Disk => tr!(
+                "Check that the game folder can be written to and that the disk isn't full."
+            ),
+            Self::Parse => tr!(
+                "The patch server sent something the launcher couldn't read. Please try again later, or download the newest launcher."
+            ),
+            Self::Verification => tr!(
+                "A download was damaged on the way. Please try again, and tell support if it keeps happening."
+            ),
+            Self::Other => return None,
+        };
+        Some(advice)
+    }
+}
+
+/// What patching did with a file
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAction {
+    /// The file was missing or outdated
+    Downloaded,
+    /// Verifying found the file was damaged
+    Repaired,
+    /// The player keeps their own version of the file
+    Skipped,
+    /// Verifying found the file was damaged, but it couldn't be repaired
+    Failed,
+}
+
+impl Display for FileAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            FileAction::Downloaded => tr!("Downloaded"),
+            FileAction::Repaired => tr!("Repaired"),
+            FileAction::Skipped => tr!("Skipped"),
+            FileAction::Failed => tr!("Failed"),
+        };
+        f.write_str(&text)
+    }
+}
+
+/// What was found when verifying the installation
+#[derive(Default, Serialize)]
+pub struct RepairSummary {
+    pub checked: usize,
+    pub repaired: usize,
+    pub failed: usize,
+}
+
+impl Display for RepairSummary {
+    /// Example: `1234 files checked, 2 repaired, 1 could not be repaired`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&tr!(
+            "{checked} files checked, {repaired} repaired",
+            checked = self.checked,
+            repaired = self.repaired
+        ))?;
+        if self.failed > 0 {
+            f.write_str(&tr!(
+                ", {failed} could not be repaired",
+                failed = self.failed
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// What patching would change
+#[derive(Default, Serialize)]
+pub struct PatchPreview {
+    /// The base game needs to be installed first, so nothing else could be
+    /// checked
+    pub base_game: bool,
+    /// How many files would be downloaded
+    pub files: usize,
+    /// How much would be downloaded, not counting files whose size the
+    /// server didn't report
+    pub download_size: u64,
+    /// Archives which would have files replaced
+    pub archives: Vec<String>,
+}
+
+impl PatchPreview {
+    pub fn is_up_to_date(&self) -> bool {
+        !self.base_game && self.files == 0
+    }
+}
+
+impl Display for PatchPreview {
+    /// Example: `12 files to update (34.5 MiB) in 2 archives`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = byte_unit::Byte::from_bytes(self.download_size.into())
+            .get_appropriate_unit(true)
+            .to_string();
+        if self.base_game {
+            return f.write_str(&tr!("The game needs to be installed ({size})", size = size));
+        }
+        if self.is_up_to_date() {
+            return f.write_str(&tr!("The game is up to date"));
+        }
+        f.write_str(&tr!(
+            "{files} files to update ({size})",
+            files = self.files,
+            size = size
+        ))?;
+        if !self.archives.is_empty() {
+            f.write_str(&tr!(
+                " in {archives} archives",
+                archives = self.archives.len()
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// The results of a connection test, in the order they were done
+#[derive(Clone, Default, Serialize)]
+pub struct ConnectionReport {
+    /// The patch server which was tested
+    pub server: String,
+    pub checks: Vec<ConnectionCheck>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ConnectionCheck {
+    /// What was checked, translated
+    pub name: String,
+    pub ok: bool,
+    /// What was found, or why it failed
+    pub detail: String,
+    /// How long it took, in milliseconds
+    pub millis: Option<u64>,
+}
+
+impl ConnectionReport {
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|check| check.ok)
+    }
+}
+
+impl Display for ConnectionReport {
+    /// Example:
+    ///
+    /// ```text
+    /// Connection test for https://example.com/patch/
+    /// [OK]   DNS lookup (12 ms): 203.0.113.7
+    /// [FAIL] Patch list: HTTP status 503
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Connection test for {}", self.server)?;
+        for check in &self.checks {
+            let result = if check.ok { "[OK]  " } else { "[FAIL]" };
+            write!(f, "{result} {}", check.name)?;
+            if let Some(millis) = check.millis {
+                write!(f, " ({millis} ms)")?;
+            }
+            writeln!(f, ": {}", check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// What happened during a patch
+#[derive(Default, Serialize)]
+pub struct PatchReport {
+    /// When patching finished, in seconds since the Unix epoch
+    pub finished_at: u64,
+    pub duration_secs: u64,
+    pub channel: String,
+    pub version: Option<String>,
+    pub files_checked: usize,
+    pub files_downloaded: usize,
+    pub downloaded_bytes: u64,
+    /// Archives which had files replaced
+    pub changed_archives: Vec<String>,
+    /// How saving each changed archive went
+    pub archive_rebuilds: Vec<ArchiveRebuild>,
+    /// Files left alone because of the player's ignore list
+    pub skipped_files: Vec<String>,
+}
+
+impl Display for PatchReport {
+    /// Example: `1234 files checked, 12 updated (34.5 MiB) in 56 s, 2 archives
+    /// optimized in 20 s`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&tr!(
+            "{checked} files checked",
+            checked = self.files_checked
+        ))?;
+        if self.files_downloaded > 0 {
+            let size = byte_unit::Byte::from_bytes(self.downloaded_bytes.into())
+                .get_appropriate_unit(true)
+                .to_string();
+            f.write_str(&tr!(
+                ", {updated} updated ({size})",
+                updated = self.files_downloaded,
+                size = size
+            ))?;
+        }
+        let duration = match self.duration_secs {
+            secs @ 0..=59 => tr!(" in {secs} s", secs = secs),
+            secs => tr!(" in {min} min {secs} s", min = secs / 60, secs = secs % 60),
+        };
+        f.write_str(&duration)?;
+        let optimized = self
+            .archive_rebuilds
+            .iter()
+            .filter(|rebuild| rebuild.optimized)
+            .collect::<Vec<_>>();
+        if !optimized.is_empty() {
+            let millis = optimized
+                .iter()
+                .map(|rebuild| rebuild.duration_ms)
+                .sum::<u64>();
+            f.write_str(&tr!(
+                ", {archives} archives optimized in {secs} s",
+                archives = optimized.len(),
+                secs = millis / 1000
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// How saving an archive which had files replaced went. Compacting an
+/// archive as it is saved can take a while for large ones.
+#[derive(Clone, Serialize)]
+pub struct ArchiveRebuild {
+    pub archive: String,
+    pub files_replaced: usize,
+    /// Whether the archive was compacted, rather than only saved
+    pub optimized: bool,
+    /// The size of the archive pair before and after, in bytes
+    pub size_before: u64,
+    pub size_after: u64,
+    pub duration_ms: u64,
+}
+
+/// How fast a download is going
+#[derive(Serialize)]
+pub struct TransferRate {
+    pub bytes_per_second: u64,
+    /// How long until the download finishes, if the total size is known
+    pub remaining: Option<Duration>,
+}
+
+impl Display for TransferRate {
+    /// Example: `12.3 MiB/s, 2 min left`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let speed = byte_unit::Byte::from_bytes(self.bytes_per_second.into())
+            .get_appropriate_unit(true)
+            .to_string();
+        write!(f, "{speed}/s")?;
+
+        if let Some(remaining) = self.remaining {
+            let secs = remaining.as_secs();
+            let left = match secs {
+                0..=59 => tr!(", {secs} s left", secs = secs),
+                60..=3599 => tr!(", {min} min left", min = secs / 60),
+                _ => tr!(
+                    ", {hours} h {min} min left",
+                    hours = secs / 3600,
+                    min = secs % 3600 / 60
+                ),
+            };
+            f.write_str(&left)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use tracing::info;
+
+use super::constants::{BACKUP_DIR, LAST_PATCH};
+use super::PatchWorker;
+
+/// Lists the files which the last patch added, which don't have an older
+/// version to go back to
+const CREATED_LIST: &str = "created.txt";
+
+/// Keeps the version of each file from before the most recent patch, so the
+/// game can be rolled back if the patch breaks it.
+///
+/// The backup is only replaced once a new patch actually changes something,
+/// so checking an up to date game keeps the previous backup.
+#[derive(Default)]
+pub struct Backup {
+    /// Whether the backup belongs to the current patch yet
+    started: bool,
+}
+
+impl Backup {
+    /// `resumed` is whether an interrupted patch is being continued, in which
+    /// case the backup already belongs to it
+    pub fn new(resumed: bool) -> Self {
+        Self { started: resumed }
+    }
+
+    /// Backs up a file which is about to be replaced or created. Only the
+    /// first version of the file seen during a patch is kept.
+    pub fn save(&mut self, self_dir: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+        let backup_dir = self_dir.join(BACKUP_DIR);
+
+        if !self.started {
+            if backup_dir.exists() {
+                std::fs::remove_dir_all(&backup_dir)?;
+            }
+            std::fs::create_dir_all(&backup_dir)?;
+            self.started = true;
+
+            // Rolling back also goes back to the previous patch result
+            self.save(self_dir, &self_dir.join(LAST_PATCH))?;
+        }
+
+        let relative_path = path.strip_prefix(self_dir)?;
+        let backup_path = backup_dir.join(relative_path);
+        if backup_path.exists() {
+            return Ok(());
+        }
+
+        if path.exists() {
+            if let Some(parent) = backup_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(path, &backup_path)?;
+        } else {
+            let mut created = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(backup_dir.join(CREATED_LIST))?;
+            writeln!(created, "{}", relative_path.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Puts back the files from before the most recent patch, and removes the
+/// files it added. Returns how many files were changed.
+pub fn restore(worker: &PatchWorker) -> Result<usize, Box<dyn Error>> {
+    let _game_lock = worker.lock_game_dir()?;
+    let backup_dir = worker.self_dir.join(BACKUP_DIR);
+    if !backup_dir.is_dir() {
+        return Err("There is no previous version to go back to".into());
+    }
+
+    let mut restored = 0;
+
+    let created_list = backup_dir.join(CREATED_LIST);
+    if created_list.exists() {
+        for relative_path in std::fs::read_to_string(&created_list)?.lines() {
+            match std::fs::remove_file(worker.self_dir.join(relative_path)) {
+                Ok(()) => restored += 1,
+                Err(why) if why.kind() == std::io::ErrorKind::NotFound => {}
+                Err(why) => return Err(why.into()),
+            }
+        }
+        std::fs::remove_file(created_list)?;
+    }
+
+    restored += move_back(&backup_dir, &backup_dir, &worker.self_dir)?;
+    std::fs::remove_dir_all(backup_dir)?;
+
+    Ok(restored)
+}
+
+/// Moves every file in `dir` back to where it was in the game directory
+fn move_back(backup_dir: &Path, dir: &Path, self_dir: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut moved = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            moved += move_back(backup_dir, &path, self_dir)?;
+            continue;
+        }
+
+        let original_path = self_dir.join(path.strip_prefix(backup_dir)?);
+        info!("Restoring {original_path:?}");
+        if let Some(parent) = original_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(&path, &original_path)?;
+        moved += 1;
+    }
+    Ok(moved)
+}
@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+
+use aeco_patch_config::fsobject::File as PatchFile;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use super::cache;
+use super::download;
+use super::PatchWorker;
+
+/// Lists the bundles the patch server offers, next to the patch files
+const BUNDLE_INDEX: &str = "bundles.json";
+/// Files larger than this are left out of bundles, since they are read into
+/// memory whole
+const MAX_BUNDLED_FILE: u64 = 16 * 1024 * 1024;
+
+/// The bundles of small files a patch server offers. Downloading thousands
+/// of small files one at a time mostly waits on the server, so the files
+/// which changed since a version can be fetched together instead.
+#[derive(Deserialize)]
+struct BundleIndex {
+    bundles: Vec<Bundle>,
+}
+
+#[derive(Deserialize)]
+struct Bundle {
+    /// The installed version the bundle updates from
+    from: String,
+    /// A ZIP of the changed files, relative to the patch directory
+    url: String,
+}
+
+/// Downloads the bundle of files which changed since `installed`, if the
+/// server offers one, and puts the files in the download cache. Patching
+/// takes files from there instead of downloading them. Anything missing
+/// from the bundle is downloaded as usual, and nothing in it is used unless
+/// it matches the patch list. Returns how many files were cached.
+pub fn prefetch(worker: &PatchWorker, installed: &str) -> Result<usize, Box<dyn Error>> {
+    let index_url = worker.patch_url.join(BUNDLE_INDEX)?;
+    let index = match download::memory_file(worker, index_url, |_, _| {}) {
+        Ok(index) => index,
+        // Most servers don't offer bundles
+        Err(why) if why.is_not_found() => return Ok(0),
+        Err(why) => return Err(why.into()),
+    };
+    let index = serde_json::from_slice::<BundleIndex>(&index)?;
+    let bundle = match index.bundles.iter().find(|bundle| bundle.from == installed) {
+        Some(bundle) => bundle,
+        None => return Ok(0),
+    };
+
+    let url = worker.patch_url.join(&bundle.url)?;
+    info!("Downloading bundle {url}");
+    let file = download::temp_file(
+        worker,
+        url,
+        download::progress_reporter(worker, tr!("Downloading changed files")),
+    )?;
+    let mut archive = zip::read::ZipArchive::new(file)?;
+
+    let mut cached = 0;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        if entry.is_dir() || entry.size() > MAX_BUNDLED_FILE {
+            continue;
+        }
+        // Digests are made with the file's name, as the patch list has it
+        let name = match Path::new(entry.name()).file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        if let Err(why) = entry.read_to_end(&mut data) {
+            warn!("Could not read {} from the bundle: {why}", entry.name());
+            continue;
+        }
+        // The cache is found by digest, so what the file is called in the
+        // bundle doesn't matter beyond that
+        cache::store(worker, &PatchFile::new(&name, &data), &data);
+        cached += 1;
+    }
+
+    Ok(cached)
+}
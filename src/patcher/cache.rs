@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use aeco_patch_config::fsobject::File;
+use tracing::warn;
+
+use super::constants::CACHE_DIR;
+use super::PatchWorker;
+
+/// Gets where a downloaded file with the given digest is kept
+fn cache_path(worker: &PatchWorker, file: &File) -> PathBuf {
+    worker.self_dir.join(CACHE_DIR).join(digest_key(file))
+}
+
+/// Gets a string which identifies a file's digest and is safe to use as a
+/// file name
+pub fn digest_key(file: &File) -> String {
+    hex::encode(&file.digest)
+}
+
+/// Gets a previously downloaded copy of a file, if there is one and it is
+/// still intact
+pub fn load(worker: &PatchWorker, file: &File) -> Option<Vec<u8>> {
+    let data = std::fs::read(cache_path(worker, file)).ok()?;
+    if File::new(&file.name, &data).digest == file.digest {
+        Some(data)
+    } else {
+        None
+    }
+}
+
+/// Keeps a copy of a downloaded file so it doesn't need to be downloaded
+/// again if patching is interrupted. Failing to do so is not fatal.
+pub fn store(worker: &PatchWorker, file: &File, data: &[u8]) {
+    let path = cache_path(worker, file);
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&path, data));
+
+    if let Err(why) = result {
+        warn!("Could not cache {:?}: {why}", path);
+    }
+}
+
+/// Removes all cached downloads
+pub fn clear(worker: &PatchWorker) -> std::io::Result<()> {
+    let cache_dir = worker.self_dir.join(CACHE_DIR);
+    if cache_dir.exists() {
+        std::fs::remove_dir_all(cache_dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_key_is_the_hex_digest() {
+        let file = File::new("Game.exe", b"game");
+        let key = digest_key(&file);
+        assert_eq!(hex::decode(&key).unwrap(), file.digest);
+        assert!(key
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)));
+    }
+
+    #[test]
+    fn digest_key_differs_with_the_contents() {
+        let first = File::new("Game.exe", b"game");
+        let second = File::new("Game.exe", b"game, patched");
+        assert_ne!(digest_key(&first), digest_key(&second));
+    }
+}
@@ -0,0 +1,190 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{CertificateError, ServerName};
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// Gets the DER encoded SubjectPublicKeyInfo of a DER encoded certificate
+pub fn public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (_, certificate) = X509Certificate::from_der(certificate).ok()?;
+    Some(certificate.tbs_certificate.subject_pki.raw)
+}
+
+/// Whether a DER encoded certificate may sign other certificates, as its
+/// basic constraints extension says
+pub fn is_ca(certificate: &[u8]) -> bool {
+    X509Certificate::from_der(certificate)
+        .map(|(_, certificate)| certificate.is_ca())
+        .unwrap_or(false)
+}
+
+/// Parses a public key pin, which is the SHA-256 hash of a
+/// SubjectPublicKeyInfo in base64. A `sha256/` prefix is allowed, since
+/// that is how pins are often written.
+pub fn parse_pin(pin: &str) -> Result<[u8; 32], Box<dyn Error>> {
+    let encoded = pin.trim();
+    let encoded = encoded.strip_prefix("sha256/").unwrap_or(encoded);
+    base64::decode(encoded)
+        .ok()
+        .and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+        .ok_or_else(|| format!("Public key pin '{pin}' is not a base64 SHA-256 hash").into())
+}
+
+/// Accepts a server only if its certificate has one of the pinned public
+/// keys. That the server holds the key is still checked as usual, by the
+/// handshake signature, so the certificate is only where the key comes from.
+///
+/// The usual checks are skipped on purpose. Who signed the certificate
+/// doesn't matter, since the pin replaces the chain to a trusted root. The
+/// server name doesn't matter either, since the pins come from the same
+/// configuration as the server address, and a key pinned for one server
+/// can't be presented by anyone else. Expiry exists to limit how long a
+/// stolen key can be used without the authority noticing, but a stolen
+/// pinned key stays usable until the pin is removed whatever the
+/// certificate says, so rejecting expired certificates would only lock
+/// players out when the server's operator forgets to renew one.
+struct PinnedKeys {
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedKeys {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let public_key = public_key_info(&end_entity.0).ok_or(
+            rustls::Error::InvalidCertificate(CertificateError::BadEncoding),
+        )?;
+        let hash = <[u8; 32]>::from(Sha256::digest(public_key));
+        if self.pins.contains(&hash) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+}
+
+/// Builds a TLS configuration which only accepts servers with one of the
+/// pinned public keys
+pub fn pinned_keys_config(pins: &[String]) -> Result<rustls::ClientConfig, Box<dyn Error>> {
+    let pins = pins
+        .iter()
+        .map(|pin| parse_pin(pin))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedKeys { pins }))
+        .with_no_client_auth();
+    // reqwest only offers these itself when it sets up TLS
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEQUENCE: u8 = 0x30;
+    const BOOLEAN: u8 = 0x01;
+    const INTEGER: u8 = 0x02;
+    const BIT_STRING: u8 = 0x03;
+    const OCTET_STRING: u8 = 0x04;
+    const OBJECT_IDENTIFIER: u8 = 0x06;
+    const VERSION: u8 = 0xa0;
+    const EXTENSIONS: u8 = 0xa3;
+    /// The object identifier of the basic constraints extension, 2.5.29.19
+    const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+
+    /// DER encodes one element
+    fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![tag];
+        match contents.len() {
+            len @ 0..=0x7f => encoded.push(len as u8),
+            len @ 0x80..=0xff => encoded.extend_from_slice(&[0x81, len as u8]),
+            len => {
+                encoded.push(0x82);
+                encoded.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+        }
+        encoded.extend_from_slice(contents);
+        encoded
+    }
+
+    fn sequence(elements: &[Vec<u8>]) -> Vec<u8> {
+        der(SEQUENCE, &elements.concat())
+    }
+
+    fn public_key() -> Vec<u8> {
+        let algorithm = sequence(&[der(OBJECT_IDENTIFIER, &[0x2b, 0x65, 0x70])]);
+        sequence(&[algorithm, der(BIT_STRING, &[0; 33])])
+    }
+
+    /// Builds a certificate which is only as real as the parsers need,
+    /// optionally with a basic constraints extension saying whether it is
+    /// a CA
+    fn certificate(version: bool, ca: Option<bool>) -> Vec<u8> {
+        let name = sequence(&[der(0x31, &sequence(&[der(0x0c, &[b'x'; 200])]))]);
+        let mut fields = Vec::new();
+        if version {
+            fields.push(der(VERSION, &der(INTEGER, &[2])));
+        }
+        fields.extend([
+            der(INTEGER, &[1, 2, 3, 4]),
+            sequence(&[der(OBJECT_IDENTIFIER, &[0x2b, 0x65, 0x70])]),
+            name.clone(),
+            sequence(&[der(0x17, b"240101000000Z"), der(0x17, b"340101000000Z")]),
+            name,
+            public_key(),
+        ]);
+        if let Some(ca) = ca {
+            let constraints = sequence(&[der(BOOLEAN, &[if ca { 0xff } else { 0 }])]);
+            let extension = sequence(&[
+                der(OBJECT_IDENTIFIER, BASIC_CONSTRAINTS),
+                der(BOOLEAN, &[0xff]),
+                der(OCTET_STRING, &constraints),
+            ]);
+            fields.push(der(EXTENSIONS, &sequence(&[extension])));
+        }
+        let signed = sequence(&fields);
+        let algorithm = sequence(&[der(OBJECT_IDENTIFIER, &[0x2b, 0x65, 0x70])]);
+        sequence(&[signed, algorithm, der(BIT_STRING, &[0; 65])])
+    }
+
+    #[test]
+    fn public_key_info_finds_the_key() {
+        let key = public_key();
+        assert_eq!(public_key_info(&certificate(true, None)), Some(&key[..]));
+        // Version 1 certificates leave the version out
+        assert_eq!(public_key_info(&certificate(false, None)), Some(&key[..]));
+        assert_eq!(public_key_info(&certificate(true, None)[..100]), None);
+        assert_eq!(public_key_info(&sequence(&[sequence(&[])])), None);
+    }
+
+    #[test]
+    fn is_ca_reads_basic_constraints() {
+        assert!(is_ca(&certificate(true, Some(true))));
+        assert!(!is_ca(&certificate(true, Some(false))));
+        assert!(!is_ca(&certificate(true, None)));
+        assert!(!is_ca(b"not a certificate"));
+    }
+
+    #[test]
+    fn parse_pin_takes_base64_hashes() {
+        let hash = [0xab; 32];
+        let encoded = base64::encode(hash);
+        assert_eq!(parse_pin(&encoded).unwrap(), hash);
+        assert_eq!(parse_pin(&format!(" sha256/{encoded}\n")).unwrap(), hash);
+        assert!(parse_pin(&base64::encode([0xab; 20])).is_err());
+        assert!(parse_pin("sha256/not base64!").is_err());
+    }
+}
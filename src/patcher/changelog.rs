@@ -0,0 +1,27 @@
+use std::error::Error;
+
+use super::constants::CHANGELOG_DIR;
+use super::metacache;
+use super::signature;
+use super::PatchWorker;
+
+/// Gets the patch notes for a version of the game, which are Markdown
+/// published in the changelog directory next to the patch list. Versions
+/// without patch notes aren't an error.
+pub fn fetch(worker: &PatchWorker, version: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let url = worker
+        .patchlist_url
+        .join(CHANGELOG_DIR)?
+        .join(&format!("{version}.md"))?;
+
+    let bytes = match metacache::conditional_file(worker, url.clone(), |_, _| {}) {
+        Ok(bytes) => bytes,
+        Err(why) if why.is_not_found() => return Ok(None),
+        Err(why) => return Err(why.into()),
+    };
+
+    // Patch notes can have links in them
+    signature::verify(worker, &url, &bytes)?;
+
+    Ok(Some(String::from_utf8(bytes)?))
+}
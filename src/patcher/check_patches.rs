@@ -1,54 +1,95 @@
-use std::{error::Error, path::Path};
+use std::path::Path;
+use std::time::Instant;
 
-use crate::patcher::utils::set_executable;
+use crate::message::{ArchiveRebuild, FileAction};
+use crate::patcher::utils::{ensure_free_space, set_executable};
 use aeco_patch_config::fsobject::Archive;
 use aeco_patch_config::fsobject::{Directory, FSObject, File};
+use rayon::prelude::*;
+use tracing::{info, warn};
 
+use super::cache;
+use super::constants::HASH_CACHE;
 use super::download;
+use super::error::PatcherError;
+use super::file_mode;
+use super::fragmentation::{self, pair_size};
+use super::self_update;
+use super::signature;
+use super::staging::{self, StagedArchive};
+use super::worker::UpdatedLauncher;
 use super::PatchWorker;
 
+/// How much data is read from an archive before it is hashed
+const HASH_BATCH_SIZE: usize = 64 * 1024 * 1024;
+
 #[derive(Debug)]
 struct ArchivePaths<'a, 'b> {
     pub hed: &'a Path,
     pub dat: &'b Path,
 }
 
-/// Checks files to be patched, patching them if necessary
+/// Checks files to be patched, patching them if necessary. Progress is
+/// shown across every directory being patched, of which `checked_before`
+/// files were already checked, out of `total_files`.
 pub fn check_platform_patches(
     worker: &mut PatchWorker,
     dir: &Directory,
-) -> Result<(), Box<dyn Error>> {
+    checked_before: usize,
+    total_files: usize,
+) -> Result<(), PatcherError> {
     let check_platform = &dir.name;
 
     // The URL to start at needs to be patch/platform because that is where
     // platform specific files are stored
     let platform_net_path = worker.patch_url.join(&format!("{check_platform}/"))?;
 
-    let total_files = get_total_files_in_patch(dir);
+    let platform_files = get_total_files_in_patch(dir);
     let disk_dir = worker.self_dir.clone();
-    let checked_files = check_dir(
+    let result = check_dir(
         worker,
         dir,
         disk_dir,
         platform_net_path,
         check_platform,
-        0,
+        checked_before,
         total_files,
-    )?;
+    );
+
+    // Keep what was learned even if checking failed part way through
+    if let Err(why) = worker.hash_cache.save(&worker.self_dir.join(HASH_CACHE)) {
+        warn!("Could not save hash cache: {why}");
+    }
+
+    let checked_files = result? - checked_before;
+    worker.report.files_checked += checked_files;
 
     // All files should have been checked, but it is not fatal if these
     // values do not match
-    if checked_files != total_files {
-        eprintln!(
-            "Checked files: {checked_files}; total files: {total_files}. These should match."
-        );
+    if checked_files != platform_files {
+        warn!("Checked files: {checked_files}; total files: {platform_files}. These should match.");
     }
 
-    worker.send_download(format!("{total_files} files checked."), 1.);
-
     Ok(())
 }
 
+/// Shows how many files were checked once every directory has been
+pub fn send_checked_summary(worker: &PatchWorker, total_files: usize) {
+    let skipped_files = worker.ignore_list.skipped.len();
+    if skipped_files > 0 {
+        worker.send_download(
+            tr!(
+                "{checked} files checked, {skipped} skipped (user override).",
+                checked = total_files,
+                skipped = skipped_files,
+            ),
+            1.,
+        );
+    } else {
+        worker.send_download(tr!("{checked} files checked.", checked = total_files), 1.);
+    }
+}
+
 /// Iterates through a directory for files to be patched
 fn check_dir<P>(
     worker: &mut PatchWorker,
@@ -58,7 +99,7 @@ fn check_dir<P>(
     platform: &str,
     mut completed_files: usize,
     total_files: usize,
-) -> Result<usize, Box<dyn Error>>
+) -> Result<usize, PatcherError>
 where
     P: AsRef<Path>,
 {
@@ -132,16 +173,18 @@ fn check_file<P>(
     platform: &str,
     mut completed_files: usize,
     total_files: usize,
-) -> Result<usize, Box<dyn Error>>
+) -> Result<usize, PatcherError>
 where
     P: AsRef<Path>,
 {
     let file_to_check = disk_file.as_ref();
-    let mut file_to_write = file_to_check.to_path_buf();
 
     send_checked_files_update(worker, completed_files + 1, total_files, platform);
 
-    let is_self = file_to_check == worker.self_exe;
+    let is_self = worker.is_launcher(file_to_check);
+    if is_self && worker.separate_launcher_updates {
+        return Ok(completed_files + 1);
+    }
 
     // The program can be built to avoid downloading updates to itself using
     // the "dont_update_self" feature.
@@ -152,39 +195,30 @@ where
     #[cfg(not(feature = "dont_update_self"))]
     let skip_file = false;
 
-    if !skip_file {
-        if !file_to_write.exists() {
-            println!("Downloading new file {net_file} -> {:?}", &file_to_write);
-            let file_bytes = download::patch(worker, net_file)?;
-            std::fs::write(file_to_write, file_bytes)?;
-        } else {
-            let file_matches = {
-                let disk_data = std::fs::read(&file_to_check)?;
-                let disk_file_data = File::new(&file.name, &disk_data);
-                file.digest == disk_file_data.digest
-            };
-
-            // If the patched file is this program, don't try to overwrite it
-            // while it is running. Instead, save it as a different file name
-            // and move it later.
-            if is_self {
-                file_to_write = worker.get_self_aecoupdate_path()?;
-            }
-
-            if !file_matches {
-                println!("Updating {net_file} -> {:?}", &file_to_write);
-                let file_bytes = download::patch(worker, net_file)?;
-                std::fs::write(&file_to_write, file_bytes)?;
-                // If we got the file successfully, and it is a replacement for
-                // this program, save the path to the new one for later so we
-                // can switch to it.
-                if is_self {
-                    // Make sure the file is exectuable on unixlike systems
-                    set_executable(&file_to_write)?;
-                    worker.updated_patcher = Some(file_to_write);
-                }
-            }
+    // Players can keep their own version of some files
+    let relative_path = file_to_check.strip_prefix(&worker.self_dir)?;
+    let skip_file = skip_file || worker.ignore_list.skip(relative_path);
+    let mode = worker.file_modes.get(relative_path);
+
+    // The launcher doesn't have to be in the game directory
+    let launcher_path;
+    let file_to_check = if is_self {
+        launcher_path = worker.self_exe.clone();
+        &launcher_path
+    } else {
+        file_to_check
+    };
+
+    let already_checked = already_checked(worker, file_to_check, file) && file_to_check.exists();
+
+    if skip_file {
+        worker.send_file_result(file_to_check, FileAction::Skipped, 0);
+    } else if !already_checked {
+        let result = patch_file(worker, file, file_to_check, net_file, is_self, mode);
+        if is_up_to_date(worker, &result) {
+            worker.journal.record(file_to_check, file);
         }
+        tally(worker, file_to_check, result)?;
     }
 
     completed_files += 1;
@@ -192,6 +226,203 @@ where
     Ok(completed_files)
 }
 
+/// Downloads a file if it is missing or doesn't match the patch info, and
+/// gives it the mode the patch info has for it. Returns whether anything was
+/// downloaded.
+fn patch_file(
+    worker: &mut PatchWorker,
+    file: &File,
+    file_to_check: &Path,
+    net_file: reqwest::Url,
+    is_self: bool,
+    mode: Option<u32>,
+) -> Result<bool, PatcherError> {
+    let mut file_to_write = file_to_check.to_path_buf();
+
+    if !file_to_write.exists() {
+        if preview_download(worker, &net_file, file)? {
+            return Ok(true);
+        }
+        info!("Downloading new file {net_file} -> {:?}", &file_to_write);
+        let file_bytes = download::patch(worker, net_file, file)?;
+        record_download(worker, file_to_check, &file_bytes);
+        // Updates were checked as a whole before patching started, but a
+        // repair or a first patch has no size estimate to check against
+        ensure_free_space(&worker.self_dir, file_bytes.len() as u64)?;
+        worker.backup.save(&worker.self_dir, &file_to_write)?;
+        staging::write(worker, &file_to_write, &file_bytes)?;
+        file_mode::apply(&file_to_write, mode)?;
+        worker.hash_cache.record(file_to_check, file_to_check, file);
+        return Ok(true);
+    }
+
+    let file_matches = known_to_match(worker, file_to_check, file_to_check, file) || {
+        let disk_data = std::fs::read(file_to_check)?;
+        let disk_file_data = File::new(&file.name, &disk_data);
+        file.digest == disk_file_data.digest
+    };
+
+    // If the patched file is this program, don't try to overwrite it
+    // while it is running. Instead, save it as a different file name
+    // and move it later.
+    if is_self {
+        file_to_write = worker.get_self_aecoupdate_path()?;
+    }
+
+    if file_matches {
+        // Files from before the patch info had modes may be missing them
+        if worker.preview.is_none() {
+            file_mode::apply(file_to_check, mode)?;
+        }
+        worker.hash_cache.record(file_to_check, file_to_check, file);
+        return Ok(false);
+    }
+
+    // An update which already failed to start here would be put back again
+    if is_self && self_update::is_rejected(worker, &file.digest) {
+        return Ok(false);
+    }
+    if preview_download(worker, &net_file, file)? {
+        return Ok(true);
+    }
+    info!("Updating {net_file} -> {:?}", &file_to_write);
+    // The launcher usually changes little from one version to the next, so
+    // try building it from the running one first
+    let delta = if is_self {
+        self_update::from_delta(worker, &net_file, file)
+    } else {
+        None
+    };
+    let file_bytes = match delta {
+        Some(file_bytes) => file_bytes,
+        None => download::patch(worker, net_file.clone(), file)?,
+    };
+    // Never write out a launcher which didn't come from us, since it would
+    // be run next
+    if is_self {
+        signature::verify_launcher(worker, &net_file, &file_bytes)
+            .map_err(|why| format!("Refusing to update the launcher: {why}"))?;
+    }
+    record_download(worker, file_to_check, &file_bytes);
+    ensure_free_space(&worker.self_dir, file_bytes.len() as u64)?;
+    // The launcher can't be rolled back along with the game
+    if !is_self {
+        worker.backup.save(&worker.self_dir, &file_to_write)?;
+    }
+    staging::write(worker, &file_to_write, &file_bytes)?;
+    file_mode::apply(&file_to_write, mode)?;
+    // If we got the file successfully, and it is a replacement for
+    // this program, save the path to the new one for later so we
+    // can switch to it.
+    if is_self {
+        // Make sure the file is exectuable on unixlike systems, even if the
+        // patch info doesn't say so
+        if mode.is_none() {
+            set_executable(&file_to_write)?;
+        }
+        worker.updated_patcher = Some(UpdatedLauncher {
+            path: file_to_write,
+            name: file.name.clone(),
+            digest: file.digest.clone(),
+        });
+    } else {
+        worker.hash_cache.record(file_to_check, file_to_check, file);
+    }
+
+    Ok(true)
+}
+
+/// When previewing, counts a file which would be downloaded instead of
+/// downloading it, and returns `true`. Otherwise, returns `false`.
+fn preview_download(
+    worker: &mut PatchWorker,
+    net_file: &reqwest::Url,
+    file: &File,
+) -> Result<bool, PatcherError> {
+    if worker.preview.is_none() {
+        return Ok(false);
+    }
+
+    // Files left over from an interrupted patch don't need downloading
+    let size = match cache::load(worker, file) {
+        Some(_) => 0,
+        None => download::content_length(worker, net_file.clone())?.unwrap_or_default(),
+    };
+
+    if let Some(preview) = &mut worker.preview {
+        preview.files += 1;
+        preview.download_size += size;
+    }
+    Ok(true)
+}
+
+/// Whether a file is known to match the patch info after being checked.
+/// Files which only would have been downloaded while previewing don't.
+fn is_up_to_date(worker: &PatchWorker, result: &Result<bool, PatcherError>) -> bool {
+    match result {
+        Ok(false) => true,
+        Ok(true) => worker.preview.is_none(),
+        Err(_) => false,
+    }
+}
+
+/// Whether the hash cache says a file matches without having to hash it.
+/// `key` identifies the file, and `disk_path` is the file on disk which
+/// contains it.
+fn known_to_match(worker: &PatchWorker, key: &Path, disk_path: &Path, file: &File) -> bool {
+    worker.config.patch.hash_cache
+        && worker.repair_summary.is_none()
+        && worker.hash_cache.is_unchanged(key, disk_path, file)
+}
+
+/// Counts a downloaded file for the report, and shows it in the GUI's file
+/// log. Files inside archives are given as a path inside the archive.
+fn record_download(worker: &mut PatchWorker, path: &Path, data: &[u8]) {
+    worker.report.files_downloaded += 1;
+    worker.report.downloaded_bytes += data.len() as u64;
+    let action = match worker.repair_summary {
+        Some(_) => FileAction::Repaired,
+        None => FileAction::Downloaded,
+    };
+    worker.send_file_result(path, action, data.len() as u64);
+}
+
+/// Whether a file can be assumed to be up to date without looking at it,
+/// because it was checked before the last patch was interrupted, or the last
+/// patch installed this same version of it. Neither is trusted when repairing
+/// the installation.
+fn already_checked(worker: &PatchWorker, path: &Path, file: &File) -> bool {
+    worker.repair_summary.is_none()
+        && (worker.journal.contains(path, file) || worker.manifest_diff.is_unchanged(path, file))
+}
+
+/// Counts the result of checking a file when repairing the installation. A
+/// file which can't be repaired doesn't stop the rest from being repaired.
+/// Outside of repair mode, errors are returned as they are.
+fn tally(
+    worker: &mut PatchWorker,
+    path: &Path,
+    result: Result<bool, PatcherError>,
+) -> Result<(), PatcherError> {
+    let summary = match &mut worker.repair_summary {
+        Some(summary) => summary,
+        None => return result.map(|_| ()),
+    };
+
+    summary.checked += 1;
+    match result {
+        Ok(true) => summary.repaired += 1,
+        Ok(false) => {}
+        Err(why) => {
+            warn!("Could not repair {}: {why}", path.display());
+            summary.failed += 1;
+            worker.send_file_result(path, FileAction::Failed, 0);
+        }
+    }
+
+    Ok(())
+}
+
 /// Iterates through an archive checking for files to be patched, patching them if necessary
 fn check_archive(
     worker: &mut PatchWorker,
@@ -201,79 +432,246 @@ fn check_archive(
     platform: &str,
     mut completed_files: usize,
     total_files: usize,
-) -> Result<usize, Box<dyn Error>> {
+) -> Result<usize, PatcherError> {
     // Open the ECO archive
-    let mut disk_archive = aeco_archive::Archive::open_pair(archive_paths.dat, archive_paths.hed)?;
-
-    // Keep track of if changes were made to this archive.
-    // If changes were made, we will need to finalize (and defrag!) the archive
-    // afterwards.
-    let mut changes_made = false;
-
-    // Go through each of the files in the patch's archive info
-    for file in &archive.files {
-        // Update the GUI to display how many files have been checked so far
-        send_checked_files_update(worker, completed_files + 1, total_files, platform);
-
-        // Figure out if the file in the archive matches the one stored on the
-        // server. If a file is not present in the archive at all, that is
-        // considered to not match.
-        let file_matches = file_matches_in_archive(&disk_archive, file)?;
-
-        // If the file in the archive is outdated, download it and insert it
-        // into the archive on disk.
-        if !file_matches {
-            let new_file_url = net_path.join(&file.name)?;
-            println!("Downloading {new_file_url} -> {archive_paths:?}");
-            let new_file_bytes = download::patch(worker, new_file_url)?;
-            disk_archive.add_file(&file.name, &new_file_bytes)?;
-            changes_made = true;
+    let disk_archive = aeco_archive::Archive::open_pair(archive_paths.dat, archive_paths.hed)?;
+
+    // The archive on disk is only read from. Changes go into a copy of it,
+    // which is made once the first change is needed, and swapped in at the
+    // end. If changes were made, the copy needs to be finalized before
+    // that.
+    let mut staged = None;
+
+    // Files in the archive can only be recorded in the journal once the
+    // archive has been saved
+    let mut checked = Vec::new();
+    let mut replaced = 0;
+
+    // Work out which files still need to be checked, and which of those the
+    // hash cache already vouches for
+    let pending = archive
+        .files
+        .iter()
+        .filter_map(|file| {
+            let entry_path = archive_paths.dat.join(&file.name);
+            if already_checked(worker, &entry_path, file) {
+                return None;
+            }
+            let cached = known_to_match(worker, &entry_path, archive_paths.dat, file);
+            Some((file, entry_path, cached))
+        })
+        .collect::<Vec<_>>();
+    completed_files += archive.files.len() - pending.len();
+
+    // Figure out if the files in the archive match the ones stored on the
+    // server. If a file is not present in the archive at all, that is
+    // considered to not match.
+    let to_hash = pending
+        .iter()
+        .filter(|(_, _, cached)| !cached)
+        .map(|(file, _, _)| *file)
+        .collect::<Vec<_>>();
+    completed_files += pending.len() - to_hash.len();
+    let mut hashed = entries_match(&worker.hash_pool, &disk_archive, &to_hash, |done| {
+        send_checked_files_update(worker, completed_files + done, total_files, platform)
+    })
+    .into_iter();
+    completed_files += to_hash.len();
+
+    // If a file in the archive is outdated, download it and insert it into
+    // the archive. This has to happen one file at a time.
+    for (file, entry_path, cached) in pending {
+        let result = if cached {
+            Ok(false)
+        } else {
+            match hashed.next().expect("every unchecked file was hashed") {
+                Ok(true) => Ok(false),
+                Ok(false) => {
+                    patch_archive_file(worker, &mut staged, file, archive_paths, &net_path).map(
+                        |_| {
+                            replaced += 1;
+                            true
+                        },
+                    )
+                }
+                Err(why) => Err(why.into()),
+            }
+        };
+        let up_to_date = is_up_to_date(worker, &result);
+        tally(worker, &entry_path, result)?;
+        if up_to_date {
+            checked.push((entry_path, file));
         }
+    }
 
-        completed_files += 1;
+    // If the archive has been altered, make sure changes get saved. The
+    // space taken by the replaced files is only given back here if the
+    // config asks for it, since that can take a long time; otherwise it is
+    // noted for the "Optimize archives" action.
+    drop(disk_archive);
+    if let Some(staged) = staged {
+        let optimize = worker.config.patch.optimize_archives_while_patching;
+        let name = archive_paths
+            .dat
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        worker.send_info(if optimize {
+            tr!("Optimizing archive {archive}...", archive = name)
+        } else {
+            tr!("Saving archive {archive}...", archive = name)
+        });
+        let size_before = pair_size(archive_paths.dat, archive_paths.hed);
+        let started = Instant::now();
+        let keep_alive = worker.keep_alive();
+        staged.commit(archive_paths.dat, archive_paths.hed, optimize)?;
+        drop(keep_alive);
+        let rebuild = ArchiveRebuild {
+            archive: archive_paths.dat.display().to_string(),
+            files_replaced: replaced,
+            optimized: optimize,
+            size_before,
+            size_after: pair_size(archive_paths.dat, archive_paths.hed),
+            duration_ms: started.elapsed().as_millis() as u64,
+        };
+        info!(
+            archive = %rebuild.archive,
+            files_replaced = rebuild.files_replaced,
+            optimized = rebuild.optimized,
+            size_before = rebuild.size_before,
+            size_after = rebuild.size_after,
+            duration_ms = rebuild.duration_ms,
+            "Saved archive"
+        );
+        if !optimize {
+            fragmentation::record(worker, &rebuild);
+        }
+        worker.report.changed_archives.push(rebuild.archive.clone());
+        worker.report.archive_rebuilds.push(rebuild);
     }
 
-    // If the archive on disk has been altered, make sure changes get saved,
-    // and make sure that any wasted space gets elimintated.
-    if changes_made {
-        disk_archive.finalize()?;
-        disk_archive.defrag()?;
+    for (entry_path, file) in checked {
+        worker.journal.record(&entry_path, file);
+        worker
+            .hash_cache
+            .record(&entry_path, archive_paths.dat, file);
     }
 
     Ok(completed_files)
 }
 
-/// Reads an ECO archive and checks if a file inside it matches the given File
-/// info.
-fn file_matches_in_archive(
-    disk_archive: &aeco_archive::Archive,
+/// Downloads a file and inserts it into an archive
+fn patch_archive_file(
+    worker: &mut PatchWorker,
+    staged: &mut Option<StagedArchive>,
     file: &File,
-) -> Result<bool, aeco_archive::ArchiveError> {
-    match disk_archive.get_file(&file.name) {
-        Ok(archive_data) => {
-            // File is present in the archive
-            let archive_file = File::new(&file.name, &archive_data);
-            // Is it the same as the one on the server?
-            Ok(file.digest == archive_file.digest)
+    archive_paths: &ArchivePaths,
+    net_path: &reqwest::Url,
+) -> Result<(), PatcherError> {
+    let new_file_url = net_path.join(&file.name)?;
+    if preview_download(worker, &new_file_url, file)? {
+        let archive_name = archive_paths.dat.display().to_string();
+        if let Some(preview) = &mut worker.preview {
+            if !preview.archives.contains(&archive_name) {
+                preview.archives.push(archive_name);
+            }
         }
-        Err(aeco_archive::ArchiveError::FileNotPresentError) => {
-            // The file is not present, so it doesn't match
-            Ok(false)
+        return Ok(());
+    }
+    info!("Downloading {new_file_url} -> {archive_paths:?}");
+    let new_file_bytes = download::patch(worker, new_file_url, file)?;
+    record_download(worker, &archive_paths.dat.join(&file.name), &new_file_bytes);
+    ensure_free_space(archive_paths.dat, new_file_bytes.len() as u64)?;
+    let staged = match staged {
+        Some(staged) => staged,
+        None => {
+            // Copying a large archive takes a while
+            let _keep_alive = worker.keep_alive();
+            worker.backup.save(&worker.self_dir, archive_paths.hed)?;
+            worker.backup.save(&worker.self_dir, archive_paths.dat)?;
+            staged.insert(StagedArchive::new(
+                worker,
+                archive_paths.dat,
+                archive_paths.hed,
+            )?)
         }
-        Err(why) => {
-            // Some other error happened
-            Err(why)
+    };
+    staged.archive.add_file(&file.name, &new_file_bytes)?;
+    Ok(())
+}
+
+/// Checks whether files inside an ECO archive match the given File info.
+///
+/// Reading the archive happens on this thread, while hashing what was read
+/// is spread across the hashing threads in batches. `progress` is called
+/// with how many files have been checked after each batch.
+fn entries_match<F>(
+    pool: &rayon::ThreadPool,
+    disk_archive: &aeco_archive::Archive,
+    files: &[&File],
+    progress: F,
+) -> Vec<Result<bool, aeco_archive::ArchiveError>>
+where
+    F: Fn(usize),
+{
+    let mut results = Vec::with_capacity(files.len());
+    let mut batch = Vec::new();
+    let mut batch_size = 0;
+
+    for (index, file) in files.iter().enumerate() {
+        match disk_archive.get_file(&file.name) {
+            Ok(data) => {
+                batch_size += data.len();
+                batch.push((*file, data));
+            }
+            Err(why) => {
+                // Anything read so far has to be hashed first to keep the
+                // results in order
+                results.extend(hash_batch(pool, &mut batch));
+                batch_size = 0;
+                results.push(match why {
+                    // The file is not present, so it doesn't match
+                    aeco_archive::ArchiveError::FileNotPresentError => Ok(false),
+                    why => Err(why),
+                });
+            }
+        }
+
+        if batch_size >= HASH_BATCH_SIZE || index + 1 == files.len() {
+            results.extend(hash_batch(pool, &mut batch));
+            batch_size = 0;
+            progress(results.len());
         }
     }
+
+    results
+}
+
+/// Hashes the files read from an archive in parallel, emptying the batch
+fn hash_batch(
+    pool: &rayon::ThreadPool,
+    batch: &mut Vec<(&File, Vec<u8>)>,
+) -> Vec<Result<bool, aeco_archive::ArchiveError>> {
+    let batch = std::mem::take(batch);
+    pool.install(|| {
+        batch
+            .into_par_iter()
+            .map(|(file, data)| Ok(file.digest == File::new(&file.name, &data).digest))
+            .collect()
+    })
 }
 
-fn get_total_files_in_patch(dir: &Directory) -> usize {
+pub fn get_total_files_in_patch(dir: &Directory) -> usize {
     let mut total_files = 0;
 
     for child in &dir.children {
         total_files += match child {
             FSObject::File(_) => 1,
             FSObject::Directory(d) => get_total_files_in_patch(d),
+            // Archive entries are always plain files. The patch list format
+            // has no way to describe an archive or directory inside one, and
+            // neither does the ECO archive format.
             FSObject::Archive(a) => a.files.len(),
         };
     }
@@ -289,7 +687,12 @@ fn send_checked_files_update(
 ) {
     let progress = files_checked as f32 / total_files as f32;
     worker.send_download(
-        format!("Checking file {files_checked} / {total_files} for platform '{platform}'",),
+        tr!(
+            "Checking file {checked} / {total} for platform '{platform}'",
+            checked = files_checked,
+            total = total_files,
+            platform = platform,
+        ),
         progress,
     );
 }
@@ -0,0 +1,136 @@
+use super::certificate;
+use crate::config::{AuthConfig, NetworkConfig, ProxyConfig};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use std::error::Error;
+use std::path::Path;
+
+/// Builds the HTTP client used for all requests to the patch server
+pub fn build_client(
+    config: &NetworkConfig,
+    self_dir: &Path,
+) -> Result<reqwest::Client, Box<dyn Error>> {
+    build(config, self_dir, true)
+}
+
+/// Builds an HTTP client for servers other than the patch server, which
+/// aren't sent its credentials and don't need its pinned certificate
+pub fn build_other_client(
+    config: &NetworkConfig,
+    self_dir: &Path,
+) -> Result<reqwest::Client, Box<dyn Error>> {
+    build(config, self_dir, false)
+}
+
+fn build(
+    config: &NetworkConfig,
+    self_dir: &Path,
+    patch_server: bool,
+) -> Result<reqwest::Client, Box<dyn Error>> {
+    let mut builder = reqwest::Client::builder()
+        .tcp_keepalive(config.tcp_keepalive())
+        .http2_adaptive_window(config.http2_adaptive_window)
+        .pool_max_idle_per_host(config.max_connections_per_host)
+        .https_only(config.https_only);
+
+    if patch_server && !config.pinned_public_keys.is_empty() {
+        if config.pinned_certificate.is_some() {
+            return Err("Only one of pinned_certificate and pinned_public_keys can be set".into());
+        }
+        builder = builder
+            .use_preconfigured_tls(certificate::pinned_keys_config(&config.pinned_public_keys)?);
+    } else if let Some(path) = config.pinned_certificate.as_ref().filter(|_| patch_server) {
+        let certificate = load_certificate(&self_dir.join(path))?;
+        builder = builder
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(certificate);
+    }
+
+    // There is no overall timeout, since the base game download can take a
+    // very long time. Stalled downloads are caught by the read timeout.
+    if let Some(connect_timeout) = config.connect_timeout() {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+
+    if let Some(auth) = config.auth.as_ref().filter(|_| patch_server) {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, authorization(auth)?);
+        builder = builder.default_headers(headers);
+    }
+
+    // Without an explicit proxy, reqwest picks up the proxy environment
+    // variables by itself
+    if let Some(proxy_config) = &config.proxy {
+        builder = builder.proxy(build_proxy(proxy_config)?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Builds the Authorization header sent with every request
+fn authorization(auth: &AuthConfig) -> Result<HeaderValue, Box<dyn Error>> {
+    let value = match auth {
+        AuthConfig::Basic { username, password } => {
+            format!("Basic {}", base64::encode(format!("{username}:{password}")))
+        }
+        AuthConfig::Bearer { token } => format!("Bearer {token}"),
+    };
+    let mut value = HeaderValue::from_str(&value)?;
+    // Keep the credentials out of debug output
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// Loads a PEM or DER encoded CA certificate. A server's own certificate
+/// can't be used as one, so it is refused rather than failing every
+/// connection later.
+fn load_certificate(path: &Path) -> Result<reqwest::Certificate, Box<dyn Error>> {
+    let data = std::fs::read(path)
+        .map_err(|why| format!("Could not read pinned certificate {path:?}: {why}"))?;
+    let der = if data.starts_with(b"-----BEGIN") {
+        rustls_pemfile::certs(&mut data.as_slice())?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("No certificate found in {path:?}"))?
+    } else {
+        data
+    };
+    if !certificate::is_ca(&der) {
+        return Err(format!(
+            "Pinned certificate {path:?} is not a CA certificate. Use pinned_public_keys to pin a server's own certificate."
+        )
+        .into());
+    }
+    Ok(reqwest::Certificate::from_der(&der)?)
+}
+
+fn build_proxy(config: &ProxyConfig) -> Result<reqwest::Proxy, Box<dyn Error>> {
+    let proxy_url = reqwest::Url::parse(&config.url)?;
+    let no_proxy = config.no_proxy.clone();
+
+    let mut proxy = reqwest::Proxy::custom(move |url| match url.host_str() {
+        Some(host) if is_bypassed(host, &no_proxy) => None,
+        _ => Some(proxy_url.clone()),
+    });
+
+    if let Some(username) = &config.username {
+        let password = config.password.as_deref().unwrap_or_default();
+        proxy = proxy.basic_auth(username, password);
+    }
+
+    Ok(proxy)
+}
+
+/// Checks whether a host matches any of the entries of a "no proxy" list
+fn is_bypassed(host: &str, no_proxy: &[String]) -> bool {
+    no_proxy.iter().any(|entry| {
+        let entry = entry.trim();
+        if entry == "*" {
+            return true;
+        }
+        let domain = entry.trim_start_matches('*').trim_start_matches('.');
+        host.eq_ignore_ascii_case(domain)
+            || host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+    })
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use super::client::build_client;
+use super::utils::byte_string;
+use super::PatchWorker;
+use crate::message::{ConnectionCheck, ConnectionReport};
+
+/// How long to wait for a connection when the config doesn't say
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Checks each step of reaching the patch server in turn: looking up its
+/// name, connecting to it, setting up a secure connection, reaching each of
+/// the files patching needs, and how fast it downloads. Every check is done
+/// even if an earlier one failed, since a later one can still tell more.
+pub fn run(worker: &PatchWorker) -> ConnectionReport {
+    let mut report = ConnectionReport {
+        server: worker.server_url.to_string(),
+        checks: Vec::new(),
+    };
+    let url = &worker.server_url;
+    let host = url.host_str().unwrap_or_default();
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let started = Instant::now();
+    let lookup = (host, port)
+        .to_socket_addrs()
+        .map(Iterator::collect::<Vec<_>>);
+    report.checks.push(check(
+        tr!("DNS lookup of {host}", host = host),
+        started,
+        lookup
+            .as_ref()
+            .map(|addresses| {
+                addresses
+                    .iter()
+                    .map(|address| address.ip().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .map_err(|why| why.to_string().into()),
+    ));
+
+    let started = Instant::now();
+    let connect = match &lookup {
+        Ok(addresses) => connect(worker, addresses),
+        Err(_) => Err(tr!("Skipped, since the name could not be looked up").into()),
+    };
+    report.checks.push(check(
+        tr!("Connection to port {port}", port = port),
+        started,
+        connect,
+    ));
+    // Direct connections can be blocked when everything has to go through
+    // the proxy, which is fine as long as the rest works
+    if worker.config.network.proxy.is_some() {
+        if let Some(check) = report.checks.last_mut() {
+            check.detail += &tr!(" (a proxy is set up, so this may fail harmlessly)");
+        }
+    }
+
+    // A new client has no connections to reuse, so this includes the
+    // handshake
+    if url.scheme() == "https" {
+        let started = Instant::now();
+        let handshake = build_client(&worker.config.network, &worker.launcher_dir)
+            .and_then(|client| status_of(worker, &client, url.clone()));
+        report.checks.push(check(
+            tr!("Secure connection"),
+            started,
+            // Any answer means the handshake worked
+            handshake.map(|status| format!("HTTP {status}")),
+        ));
+    }
+
+    for (name, url, must_exist) in [
+        (tr!("Server status"), &worker.status_url, true),
+        (tr!("Patch list"), &worker.patchlist_url, true),
+        // Servers often refuse to list directories, which is still an
+        // answer
+        (tr!("Patch files"), &worker.patch_url, false),
+    ] {
+        let started = Instant::now();
+        let result = status_of(worker, &worker.client, url.clone()).and_then(|status| {
+            if must_exist && !status.is_success() {
+                return Err(format!("HTTP {status}").into());
+            }
+            Ok(format!("HTTP {status}"))
+        });
+        report.checks.push(check(name, started, result));
+    }
+
+    let started = Instant::now();
+    let speed = download_speed(worker);
+    report
+        .checks
+        .push(check(tr!("Download speed"), started, speed));
+    report
+}
+
+/// Turns how a step went into a line of the report
+fn check(
+    name: String,
+    started: Instant,
+    result: Result<String, Box<dyn Error>>,
+) -> ConnectionCheck {
+    let millis = Some(started.elapsed().as_millis() as u64);
+    match result {
+        Ok(detail) => ConnectionCheck {
+            name,
+            ok: true,
+            detail,
+            millis,
+        },
+        Err(why) => ConnectionCheck {
+            name,
+            ok: false,
+            detail: why.to_string(),
+            millis,
+        },
+    }
+}
+
+/// Connects to the first address which answers, and says which it was
+fn connect(worker: &PatchWorker, addresses: &[SocketAddr]) -> Result<String, Box<dyn Error>> {
+    let timeout = worker
+        .config
+        .network
+        .connect_timeout()
+        .unwrap_or(DEFAULT_CONNECT_TIMEOUT);
+    let mut last_error = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(address, timeout) {
+            Ok(_) => return Ok(address.to_string()),
+            Err(why) => last_error = Some(why),
+        }
+    }
+    match last_error {
+        Some(why) => Err(why.into()),
+        None => Err(tr!("The name has no addresses").into()),
+    }
+}
+
+fn status_of(
+    worker: &PatchWorker,
+    client: &reqwest::Client,
+    url: reqwest::Url,
+) -> Result<reqwest::StatusCode, Box<dyn Error>> {
+    let response = worker.runtime.block_on(client.head(url).send())?;
+    Ok(response.status())
+}
+
+/// Downloads the patch list, which every server has, and works out how fast
+/// that went
+fn download_speed(worker: &PatchWorker) -> Result<String, Box<dyn Error>> {
+    let started = Instant::now();
+    let data = worker.runtime.block_on(async {
+        worker
+            .client
+            .get(worker.patchlist_url.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await
+    })?;
+    let seconds = started.elapsed().as_secs_f64().max(0.001);
+    let rate = (data.len() as f64 / seconds) as u64;
+    Ok(tr!(
+        "{size} in {seconds} s ({rate}/s)",
+        size = byte_string(data.len() as u64),
+        seconds = format!("{seconds:.1}"),
+        rate = byte_string(rate)
+    ))
+}
@@ -1,9 +1,88 @@
 pub const PATCH_SERVER: &str = "https://ecocp.atomixro.com/ecopatch/saga10/";
+/// An environment variable which overrides the patch server in the config
+pub const PATCH_SERVER_ENV: &str = "AECO_PATCH_SERVER";
 pub const BASE_DIR: &str = "base/";
 pub const BASE_ZIP: &str = "saga10.zip";
+pub const BASE_TORRENT: &str = "saga10.zip.torrent";
 pub const META_DIR: &str = "meta/";
 pub const PATCHLIST: &str = "patchlist.json";
 pub const STATUS: &str = "status.json";
+pub const NEWS: &str = "news.json";
+pub const GAME_VERSION: &str = "version.json";
+/// The newest launcher for each platform, for servers which release it
+/// separately from the game
+pub const LAUNCHER_RELEASE: &str = "launcher.json";
+/// Patch notes for each version, named after the version with ".md" added
+pub const CHANGELOG_DIR: &str = "changelog/";
+pub const SIGNATURE_EXTENSION: &str = ".sig";
 pub const PATCH_DIR: &str = "patch/";
+/// Optional components are top level directories of the patch list, named
+/// with this prefix followed by the name of the component
+pub const COMPONENT_PREFIX: &str = "component-";
+/// Language packs of the game are top level directories of the patch list,
+/// named with this prefix followed by the language code
+pub const LANGUAGE_PREFIX: &str = "lang-";
 pub const GAME_EXE: &str = "eco.exe";
 pub const GAME_INI: &str = "eco.ini";
+
+/// Another program which comes with the game and can be started from the
+/// launcher's tools menu
+pub struct LaunchTarget {
+    /// What the GUI asks for it by
+    pub id: &'static str,
+    /// What it is called in the tools menu, before being translated
+    pub name: &'static str,
+    pub exe: &'static str,
+    pub args: &'static [&'static str],
+}
+
+pub const LAUNCH_TARGETS: &[LaunchTarget] = &[
+    LaunchTarget {
+        id: "viewer",
+        name: "Character viewer",
+        exe: "viewer.exe",
+        args: &[],
+    },
+    LaunchTarget {
+        id: "config",
+        name: "Game configuration",
+        exe: "ecoconfig.exe",
+        args: &[],
+    },
+];
+/// What the game writes to its error output while the launcher watches it
+/// start
+pub const GAME_OUTPUT: &str = "game-output.log";
+pub const PATCH_IGNORE: &str = "patch-ignore.txt";
+/// The game account session, kept next to the launcher. On Windows it is
+/// encrypted for the current user.
+pub const SESSION_FILE: &str = "session.dat";
+/// Credentials for the patch server, kept the same way as the session
+pub const CREDENTIALS_FILE: &str = "credentials.dat";
+/// Held by the running launcher so that only one copy runs from a directory
+pub const LOCK_FILE: &str = "launcher.lock";
+/// The launcher runs in portable mode if this file is next to it
+pub const PORTABLE_MARKER: &str = "portable.txt";
+pub const CACHE_DIR: &str = "cache/";
+pub const META_CACHE_DIR: &str = "meta-cache/";
+pub const LAST_PATCH: &str = "last-patch.json";
+pub const JOURNAL: &str = "patch-journal.txt";
+pub const HASH_CACHE: &str = "hash-cache.json";
+/// How much each archive grew since it was last optimized
+pub const ARCHIVE_FRAGMENTATION: &str = "archive-fragmentation.json";
+pub const PATCH_REPORT: &str = "patch-report.json";
+pub const REMOVED_DIR: &str = "removed/";
+pub const BACKUP_DIR: &str = "backup/";
+pub const STAGING_DIR: &str = ".aeco-staging/";
+pub const QUARANTINE_DIR: &str = "quarantine/";
+/// Files which came from the base game ZIP, one per line
+pub const BASE_FILES: &str = "base-files.txt";
+
+/// Hex encoded Ed25519 public key which the patch list and server status must
+/// be signed with, given at build time. Release builds need one unless the
+/// `unsigned` feature is on, and builds without one don't check signatures.
+pub const MANIFEST_PUBLIC_KEY: Option<&str> = option_env!("AECO_MANIFEST_PUBLIC_KEY");
+/// Hex encoded Ed25519 public key which updates to the launcher itself must be
+/// signed with, given at build time. Builds without one use the manifest key,
+/// and builds with neither never update themselves.
+pub const LAUNCHER_PUBLIC_KEY: Option<&str> = option_env!("AECO_LAUNCHER_PUBLIC_KEY");
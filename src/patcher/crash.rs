@@ -0,0 +1,88 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use subprocess::ExitStatus;
+
+/// How much of the end of the game's output is shown when it crashes
+const OUTPUT_TAIL_BYTES: u64 = 4096;
+
+/// Describes how the game exited, for players
+pub fn describe(status: ExitStatus) -> String {
+    match status {
+        ExitStatus::Exited(code) => tr!("It exited with code {code}.", code = code),
+        ExitStatus::Signaled(signal) => tr!("It was stopped by signal {signal}.", signal = signal),
+        ExitStatus::Other(_) | ExitStatus::Undetermined => tr!("It exited unexpectedly."),
+    }
+}
+
+/// The exit code for the launcher to pass on how the game exited
+pub fn exit_code(status: ExitStatus) -> i32 {
+    match status {
+        ExitStatus::Exited(code) => code as i32,
+        // The way shells report it
+        ExitStatus::Signaled(signal) => 128 + signal as i32,
+        ExitStatus::Other(_) | ExitStatus::Undetermined => 1,
+    }
+}
+
+/// The last lines of what the game wrote to its error output, or an empty
+/// string if it wrote nothing
+pub fn output_tail(path: &Path) -> String {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return String::new(),
+    };
+    let len = file.metadata().map(|m| m.len()).unwrap_or_default();
+    let start = len.saturating_sub(OUTPUT_TAIL_BYTES);
+    let mut tail = Vec::new();
+    if file.seek(SeekFrom::Start(start)).is_err() || file.read_to_end(&mut tail).is_err() {
+        return String::new();
+    }
+    let tail = String::from_utf8_lossy(&tail);
+    // Don't start halfway through a line
+    let tail = match tail.find('\n') {
+        Some(newline) if start > 0 => &tail[newline + 1..],
+        _ => &tail,
+    };
+    tail.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn exit_code_passes_on_the_game_exit_code() {
+        assert_eq!(exit_code(ExitStatus::Exited(0)), 0);
+        assert_eq!(exit_code(ExitStatus::Exited(3)), 3);
+        // Windows reports crashes as NTSTATUS codes, which don't fit an i32
+        assert_eq!(exit_code(ExitStatus::Exited(0xC000_0005)), -1_073_741_819);
+    }
+
+    #[test]
+    fn exit_code_reports_signals_like_shells() {
+        assert_eq!(exit_code(ExitStatus::Signaled(9)), 137);
+        assert_eq!(exit_code(ExitStatus::Undetermined), 1);
+    }
+
+    #[test]
+    fn output_tail_starts_at_a_whole_line() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let line = "x".repeat(99);
+        for _ in 0..OUTPUT_TAIL_BYTES / 100 + 10 {
+            writeln!(file, "{line}").unwrap();
+        }
+        writeln!(file, "last line\n").unwrap();
+
+        let tail = output_tail(file.path());
+        assert!(tail.len() as u64 <= OUTPUT_TAIL_BYTES);
+        assert!(tail.starts_with(&line));
+        assert!(tail.ends_with("last line"));
+    }
+
+    #[test]
+    fn output_tail_is_empty_without_output() {
+        assert_eq!(output_tail(Path::new("does-not-exist.log")), "");
+    }
+}
@@ -0,0 +1,62 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use reqwest::header::CONTENT_TYPE;
+use tracing::{info, warn};
+
+use super::client::build_other_client;
+use super::PatchWorker;
+use crate::config::DiagnosticsConfig;
+use crate::crash_report;
+
+/// Where crash reports go unless the config says otherwise, if this build
+/// of the launcher has somewhere to send them
+const CRASH_REPORT_URL: Option<&str> = option_env!("AECO_CRASH_REPORT_URL");
+
+/// Where to send crash reports, if anywhere
+pub fn report_url(config: &DiagnosticsConfig) -> Option<&str> {
+    config
+        .crash_report_url
+        .as_deref()
+        .or(CRASH_REPORT_URL)
+        .filter(|url| !url.is_empty())
+}
+
+/// Sends crash reports in the background, so a slow server doesn't hold
+/// up patching. Each report which was sent is set aside; any which weren't
+/// are offered again next time.
+pub fn send(worker: &PatchWorker, url: &str, reports: Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let url = reqwest::Url::parse(url)?;
+    if worker.config.network.https_only && url.scheme() != "https" {
+        return Err(format!("Crash report server {url} does not use HTTPS").into());
+    }
+    // The patch server's credentials are none of the crash report server's
+    // business
+    let client = build_other_client(&worker.config.network, &worker.launcher_dir)?;
+    let reports = reports
+        .into_iter()
+        .map(|report| Ok((std::fs::read(&report)?, report)))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    worker.runtime.spawn(async move {
+        for (body, report) in reports {
+            let request = client
+                .post(url.clone())
+                .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(body);
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => info!("Sent crash report {}", report.display()),
+                Err(why) => {
+                    warn!("Could not send crash report {}: {why}", report.display());
+                    return;
+                }
+            }
+            if let Err(why) = crash_report::set_aside(&report) {
+                warn!(
+                    "Could not set aside crash report {}: {why}",
+                    report.display()
+                );
+            }
+        }
+    });
+    Ok(())
+}
@@ -1,22 +1,113 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
-use std::io::Write;
+use std::io::{Read, Seek, Write};
+use std::pin::Pin;
+use std::time::Duration;
 
-use super::error::{PatchError, ToPatchError};
+use super::cache;
+use super::error::{PatchError, PatcherError, ToPatchError};
+use super::fault::Faults;
+use super::file_mode::FileModes;
+use super::metacache;
+use super::retry::with_retries;
+use super::segmented;
+use super::signature;
+use super::speed::SpeedTracker;
+use super::spool::Spool;
+use super::throttle::Throttle;
+use super::torrent;
 use super::utils::byte_string;
 use super::PatchWorker;
-use aeco_patch_config::fsobject::Directory;
+use crate::message::ServerDetails;
+use aeco_patch_config::fsobject::{Directory, File as PatchFile};
 use aeco_patch_config::status::ServerStatus;
-use futures_util::StreamExt;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH};
+use serde::Deserialize;
+use tokio::sync::OwnedSemaphorePermit;
+use tracing::{info, warn};
 
-pub fn server_status(worker: &PatchWorker) -> Result<ServerStatus, PatchError> {
-    let json_bytes = memory_file(worker, worker.status_url.clone(), |_, _| {})
+/// Content encodings which downloads into memory can be decompressed from,
+/// in order of preference
+pub const SUPPORTED_ENCODINGS: &str = "zstd, gzip";
+
+/// The server status as newer patch servers publish it, with details for
+/// the GUI next to the status itself. Older servers publish only the status,
+/// and launchers from before this can't read the details.
+#[derive(Deserialize)]
+struct DetailedStatus {
+    status: ServerStatus,
+    #[serde(flatten)]
+    details: ServerDetails,
+}
+
+pub fn server_status(worker: &PatchWorker) -> Result<(ServerStatus, ServerDetails), PatchError> {
+    let json_bytes = metacache::conditional_file(worker, worker.status_url.clone(), |_, _| {})
         .map_err(|why| why.to_patch_error("Failed to get server status"))?;
 
-    let server_status = serde_json::from_slice::<ServerStatus>(&json_bytes)
+    signature::verify(worker, &worker.status_url, &json_bytes)
+        .map_err(|why| why.to_patch_error("Server status could not be verified"))?;
+
+    let value = serde_json::from_slice::<serde_json::Value>(&json_bytes)
         .map_err(|why| why.to_patch_error("Failed to parse server status"))?;
 
-    Ok(server_status)
+    let status = if value.get("status").is_some() {
+        serde_json::from_value::<DetailedStatus>(value)
+            .map(|status| (status.status, status.details))
+    } else {
+        serde_json::from_value::<ServerStatus>(value)
+            .map(|status| (status, ServerDetails::default()))
+    };
+    status.map_err(|why| why.to_patch_error("Failed to parse server status"))
+}
+
+/// Asks the server for the size of a file without downloading it
+pub fn content_length(
+    worker: &PatchWorker,
+    url: reqwest::Url,
+) -> Result<Option<u64>, PatcherError> {
+    worker.runtime.block_on(async {
+        let (response, _connection) = send(worker, worker.client.head(url)).await?;
+        Ok(head_content_length(&response))
+    })
+}
+
+/// Asks the server for the sizes of several files at once, and adds them up.
+/// Files whose size the server doesn't report aren't counted.
+pub fn total_content_length(
+    worker: &PatchWorker,
+    urls: Vec<reqwest::Url>,
+) -> Result<u64, PatcherError> {
+    let requests = futures_util::stream::iter(urls)
+        .map(|url| async move {
+            let (response, _connection) = send(worker, worker.client.head(url)).await?;
+            Ok::<_, PatcherError>(response)
+        })
+        .buffer_unordered(worker.config.simultaneous_downloads())
+        .collect::<Vec<_>>();
+
+    let mut total = 0;
+    for response in worker.runtime.block_on(requests) {
+        total += head_content_length(&response?).unwrap_or_default();
+    }
+    Ok(total)
+}
+
+fn head_content_length(response: &reqwest::Response) -> Option<u64> {
+    if !response.status().is_success() {
+        return None;
+    }
+
+    // reqwest reports a length of 0 for HEAD responses, so the header needs
+    // to be read directly
+    response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
 }
 
 /// Downloads a file and returns it in a temporary file
@@ -24,44 +115,197 @@ pub fn temp_file<F>(
     worker: &PatchWorker,
     url: reqwest::Url,
     callback: F,
-) -> Result<std::fs::File, Box<dyn Error>>
+) -> Result<std::fs::File, PatcherError>
 where
     F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
 {
-    // Request URL
-    let response = worker
-        .runtime
-        .block_on(worker.client.get(url).send())
-        .map_err(|why| why.to_string())?;
+    let (url, callback) = (&url, &callback);
+    let attempt = || async move {
+        // Create a new temporary file for the data to go into
+        let mut file = tempfile::tempfile_in(&worker.self_dir).map_err(PatcherError::Disk)?;
 
-    // Check response status
-    let status = response.status();
-    if !status.is_success() {
-        Err(format!("URL request failed: {status}"))?;
-    }
+        // Large files are downloaded in several pieces at once if the server
+        // allows it
+        let segments = worker.config.network.download_segments;
+        if segments > 1 {
+            if let Some(total_size) = segmented::probe(worker, url).await? {
+                segmented::download(worker, url, &mut file, total_size, segments, callback).await?;
+                file.rewind().map_err(PatcherError::Disk)?;
+                return Ok(file);
+            }
+        }
+
+        fetch_to_file(worker, url.clone(), &mut file, callback).await?;
+        Ok(file)
+    };
+    worker.runtime.block_on(with_retries(worker, attempt))
+}
 
-    // Create a new temporary file for the data to go into
-    let mut file = tempfile::tempfile_in(&worker.self_dir).map_err(|why| why.to_string())?;
+/// Sends a request once another one may be sent to the patch server. The
+/// returned permit has to be kept until the body has been read.
+pub async fn send(
+    worker: &PatchWorker,
+    request: reqwest::RequestBuilder,
+) -> Result<(reqwest::Response, OwnedSemaphorePermit), PatcherError> {
+    let connection = worker.connections.clone().acquire_owned().await?;
+    let response = request.send().await?;
+    Ok((response, connection))
+}
+
+/// Makes one attempt at downloading a file into `file`.
+///
+/// This is run with `block_on` from the worker's thread rather than on the
+/// runtime's threads, so writing to the file doesn't hold up other
+/// downloads.
+async fn fetch_to_file<F>(
+    worker: &PatchWorker,
+    url: reqwest::Url,
+    file: &mut File,
+    callback: F,
+) -> Result<(), PatcherError>
+where
+    F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
+{
+    let (response, _connection) = send(worker, worker.client.get(url)).await?;
+    let response = log_response(worker, response)?;
 
     // Keep track of the total size and the number of bytes downloaded so far.
     // The server doesn't need to tell us how long the content is.
     let total_size = response.content_length();
     let mut downloaded_size = 0u64;
 
+    let read_timeout = worker.config.network.read_timeout();
     let mut stream = response.bytes_stream();
-    while let Some(stream_result) = worker.runtime.block_on(stream.next()) {
-        // Get next chunk of bytes from stream
-        let bytes = stream_result?;
-
-        // Write the bytes to the file
-        file.write_all(&bytes).map_err(|why| why.to_string())?;
+    while let Some(bytes) =
+        next_chunk(&mut stream, read_timeout, &worker.throttle, &worker.faults).await?
+    {
+        worker.heartbeat();
+        file.write_all(&bytes).map_err(PatcherError::Disk)?;
 
         downloaded_size += bytes.len() as u64;
 
         callback(downloaded_size, total_size);
     }
+    Ok(())
+}
 
-    Ok(file)
+/// Waits for the next chunk of a download. If a read timeout is given and the
+/// server sends nothing for that long, this fails with `TimedOut`.
+pub async fn next_chunk<S>(
+    stream: &mut S,
+    read_timeout: Option<Duration>,
+    throttle: &Throttle,
+    faults: &Faults,
+) -> Result<Option<Bytes>, PatcherError>
+where
+    S: Stream<Item = reqwest::Result<Bytes>> + Unpin,
+{
+    let next = match read_timeout {
+        Some(read_timeout) => tokio::time::timeout(read_timeout, stream.next())
+            .await
+            .map_err(|_| PatcherError::TimedOut)?,
+        None => stream.next().await,
+    };
+
+    let next = next
+        .transpose()?
+        .map(|bytes| faults.chunk(bytes))
+        .transpose()?;
+    if let Some(bytes) = &next {
+        throttle.received(bytes.len()).await;
+    }
+    Ok(next)
+}
+
+/// Reads a download as it arrives, without storing all of it anywhere
+pub struct StreamReader<'a> {
+    worker: &'a PatchWorker,
+    stream: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>>>>,
+    /// The part of the last received chunk which hasn't been read yet
+    chunk: Bytes,
+    downloaded_size: u64,
+    total_size: Option<u64>,
+    callback: Box<dyn Fn(u64, Option<u64>) + 'a>,
+    /// Held until the whole download has been read
+    _connection: OwnedSemaphorePermit,
+}
+
+impl Read for StreamReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Wait for more data if everything received so far has been read
+        let read_timeout = self.worker.config.network.read_timeout();
+        while self.chunk.is_empty() {
+            let next = next_chunk(
+                &mut self.stream,
+                read_timeout,
+                &self.worker.throttle,
+                &self.worker.faults,
+            );
+            match self.worker.runtime.block_on(next) {
+                Ok(Some(bytes)) => {
+                    self.worker.heartbeat();
+                    self.downloaded_size += bytes.len() as u64;
+                    (self.callback)(self.downloaded_size, self.total_size);
+                    self.chunk = bytes;
+                }
+                Err(why) => {
+                    return Err(std::io::Error::other(why));
+                }
+                // End of the download
+                Ok(None) => return Ok(0),
+            }
+        }
+
+        let length = buf.len().min(self.chunk.len());
+        buf[..length].copy_from_slice(&self.chunk[..length]);
+        self.chunk = self.chunk.slice(length..);
+        Ok(length)
+    }
+}
+
+/// Starts a download and returns a reader for its contents
+pub fn stream<'a, F>(
+    worker: &'a PatchWorker,
+    url: reqwest::Url,
+    callback: F,
+) -> Result<StreamReader<'a>, PatcherError>
+where
+    F: Fn(u64, Option<u64>) + 'a, /* downloaded bytes, total bytes */
+{
+    // Only the request itself can be retried. Once reading has started, the
+    // data which was already read can't be taken back.
+    let url = &url;
+    let attempt = || async move {
+        let (response, connection) = send(worker, worker.client.get(url.clone())).await?;
+        Ok((log_response(worker, response)?, connection))
+    };
+    let (response, connection) = worker.runtime.block_on(with_retries(worker, attempt))?;
+
+    Ok(StreamReader {
+        worker,
+        total_size: response.content_length(),
+        stream: Box::pin(response.bytes_stream()),
+        chunk: Bytes::new(),
+        downloaded_size: 0,
+        callback: Box::new(callback),
+        _connection: connection,
+    })
+}
+
+/// Logs the URL and status of a response, then fails if the status is an
+/// error
+fn log_response(
+    worker: &PatchWorker,
+    response: reqwest::Response,
+) -> Result<reqwest::Response, PatcherError> {
+    worker.faults.response()?;
+    let status = response.status();
+    if status.is_success() {
+        info!(url = %response.url(), status = status.as_u16(), "Downloading");
+    } else {
+        warn!(url = %response.url(), status = status.as_u16(), "Request failed");
+    }
+    Ok(response.error_for_status()?)
 }
 
 /// Downloads a file and returns it in a Vec
@@ -69,95 +313,456 @@ pub fn memory_file<F>(
     worker: &PatchWorker,
     url: reqwest::Url,
     callback: F,
-) -> Result<Vec<u8>, Box<dyn Error>>
+) -> Result<Vec<u8>, PatcherError>
 where
     F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
 {
-    // Request URL
-    let response = worker.runtime.block_on(worker.client.get(url).send())?;
+    let (url, callback) = (&url, &callback);
+    let attempt = || fetch_to_memory(worker, url.clone(), callback);
+    worker.runtime.block_on(with_retries(worker, attempt))
+}
 
-    // Check response status
-    let status = response.status();
-    if !status.is_success() {
-        return Err(format!("URL request failed: {status}").into());
-    }
+/// Makes one attempt at downloading a file into a Vec
+pub async fn fetch_to_memory<F>(
+    worker: &PatchWorker,
+    url: reqwest::Url,
+    callback: F,
+) -> Result<Vec<u8>, PatcherError>
+where
+    F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
+{
+    // Servers which support it can send the file compressed
+    let request = worker
+        .client
+        .get(url)
+        .header(ACCEPT_ENCODING, SUPPORTED_ENCODINGS);
+    let (response, _connection) = send(worker, request).await?;
+    let response = log_response(worker, response)?;
+    read_body(worker, response, callback).await
+}
+
+/// Reads the body of a response into a Vec, decompressing it if needed
+pub async fn read_body<F>(
+    worker: &PatchWorker,
+    response: reqwest::Response,
+    callback: F,
+) -> Result<Vec<u8>, PatcherError>
+where
+    F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
+{
+    let encoding = response
+        .headers()
+        .get(CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap_or_default().to_ascii_lowercase());
 
     // Keep track of the total size and the number of bytes downloaded so far.
     // The server doesn't need to tell us how long the content is.
     let total_size = response.content_length();
     let mut downloaded_size = 0u64;
 
-    // If we know the total size of the download, we can pre-allocate the Vec
-    // so there will be no more allocations while downloading
-    let mut result = match total_size {
-        Some(size) => {
-            let size = usize::try_from(size)
-                .map_err(|_| "File to download is too large to load into memory".to_string())?;
-            Vec::<u8>::with_capacity(size)
-        }
-        None => Vec::<u8>::new(),
-    };
+    let max_size = worker.config.network.max_download_size();
+    if let Some(size) = total_size {
+        check_size(size, max_size)?;
+    }
 
-    let mut stream = response.bytes_stream();
-    while let Some(stream_result) = worker.runtime.block_on(stream.next()) {
-        // Get next chunk of bytes from stream
-        let bytes = stream_result.map_err(|why| why.to_string())?;
+    let mut spool = Spool::new(
+        &worker.self_dir,
+        worker.config.network.memory_download_limit(),
+        total_size,
+    )
+    .map_err(PatcherError::Disk)?;
 
-        // Write the bytes to the Vec
-        result.extend(&bytes);
+    let read_timeout = worker.config.network.read_timeout();
+    let mut stream = response.bytes_stream();
+    while let Some(bytes) =
+        next_chunk(&mut stream, read_timeout, &worker.throttle, &worker.faults).await?
+    {
+        worker.heartbeat();
+        spool.write(&bytes).map_err(PatcherError::Disk)?;
 
         downloaded_size += bytes.len() as u64;
+        check_size(downloaded_size, max_size)?;
 
         callback(downloaded_size, total_size);
     }
 
-    Ok(result)
+    let body = spool.into_reader().map_err(PatcherError::Disk)?;
+    decode_body(encoding.as_deref(), body, max_size)
+}
+
+/// Makes sure a download isn't unreasonably large
+fn check_size(size: u64, max_size: u64) -> Result<(), PatcherError> {
+    if size > max_size {
+        Err(PatcherError::TooLarge { size, max_size })
+    } else {
+        Ok(())
+    }
 }
 
-/// Downloads a file and returns the resulting bytes
-pub fn patch(worker: &PatchWorker, net_file: reqwest::Url) -> Result<Vec<u8>, Box<dyn Error>> {
-    let data = memory_file(worker, net_file, |_, _| {})?;
+/// Decompresses a downloaded body according to its Content-Encoding. The
+/// decompressed body may not be larger than `max_size`.
+fn decode_body(
+    encoding: Option<&str>,
+    body: Box<dyn Read>,
+    max_size: u64,
+) -> Result<Vec<u8>, PatcherError> {
+    let decoder: Box<dyn Read> = match encoding {
+        None | Some("identity") => body,
+        Some("zstd") => Box::new(
+            zstd::stream::read::Decoder::new(body)
+                .map_err(|why| PatcherError::Parse(why.into()))?,
+        ),
+        Some("gzip") | Some("x-gzip") => Box::new(flate2::read::GzDecoder::new(body)),
+        Some(other) => {
+            return Err(PatcherError::Unexpected(format!(
+                "Unsupported content encoding '{other}'"
+            )))
+        }
+    };
+
+    // Read one byte more than allowed to find out whether there is too much.
+    // The body is already on hand, so failing here means it is broken.
+    let mut decoded = Vec::new();
+    decoder
+        .take(max_size + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|why| PatcherError::Parse(why.into()))?;
+    check_size(decoded.len() as u64, max_size)?;
+    Ok(decoded)
+}
+
+/// Downloads a file and returns the resulting bytes.
+///
+/// The bytes are checked against the digest in the patch info, and the file
+/// is downloaded again if they don't match, in case it was corrupted on the
+/// way. That uses up the same attempts as any other failure. Files which were already downloaded by an earlier, unfinished patch
+/// are taken from the cache instead.
+pub fn patch(
+    worker: &PatchWorker,
+    net_file: reqwest::Url,
+    file: &PatchFile,
+) -> Result<Vec<u8>, PatcherError> {
+    if let Some(data) = cache::load(worker, file) {
+        info!("Using cached copy of {net_file}");
+        return Ok(data);
+    }
+
+    // Show how far along the file is, since some patched files are large
+    let callback = progress_reporter(worker, tr!("Downloading {name}", name = file.name));
+    let (net_file, callback) = (&net_file, &callback);
+    // A damaged download counts against the same attempts as a failed one
+    let attempt = || async move {
+        let data = fetch_to_memory(worker, net_file.clone(), callback).await?;
+        if PatchFile::new(&file.name, &data).digest != file.digest {
+            return Err(PatcherError::Corrupted(net_file.clone()));
+        }
+        Ok(data)
+    };
+    let data = worker.runtime.block_on(with_retries(worker, attempt))?;
+    cache::store(worker, file, &data);
     Ok(data)
 }
 
 pub fn game_base(worker: &PatchWorker) -> Result<File, Box<dyn Error>> {
-    temp_file(worker, worker.game_zip_url.clone(), |downloaded, total| {
-        let pretty_downloaded = byte_string(downloaded);
-        if let Some(total) = total {
-            let downloaded = downloaded.min(total);
-            let progress = downloaded as f32 / total as f32;
-            let pretty_total = byte_string(total);
-            worker.send_download(
-                format!("Downloading base game ({pretty_downloaded} / {pretty_total})"),
-                progress,
-            );
-        } else {
-            worker.send_download(format!("Downloading base game ({pretty_downloaded})"), 1.);
+    if worker.config.network.torrent {
+        match torrent::game_base(worker) {
+            Ok(file) => return Ok(file),
+            Err(why) => warn!("Torrent download failed, falling back to HTTP: {why}"),
         }
-    })
+    }
+
+    let file = temp_file(
+        worker,
+        worker.game_zip_url.clone(),
+        progress_reporter(worker, tr!("Downloading base game")),
+    )?;
+    Ok(file)
 }
 
-/// Downloads the patchlist and returns the parsed result
-pub fn patch_metadata(worker: &PatchWorker) -> Result<Directory, PatchError> {
-    let result = memory_file(worker, worker.patchlist_url.clone(), |downloaded, total| {
+/// Starts downloading the base game ZIP and returns a reader for it
+pub fn game_base_stream(worker: &PatchWorker) -> Result<StreamReader<'_>, PatcherError> {
+    stream(
+        worker,
+        worker.game_zip_url.clone(),
+        progress_reporter(worker, tr!("Installing base game")),
+    )
+}
+
+/// Creates a download callback which shows the progress, speed, and time
+/// remaining in the GUI
+pub fn progress_reporter(worker: &PatchWorker, label: String) -> impl Fn(u64, Option<u64>) + '_ {
+    let tracker = RefCell::new(SpeedTracker::new());
+    move |downloaded, total| {
+        let rate = tracker.borrow_mut().update(downloaded, total);
         let pretty_downloaded = byte_string(downloaded);
         if let Some(total) = total {
             let downloaded = downloaded.min(total);
             let progress = downloaded as f32 / total as f32;
             let pretty_total = byte_string(total);
-            worker.send_download(
-                format!("Downloading patch info ({pretty_downloaded} / {pretty_total})"),
+            worker.send_transfer(
+                format!("{label} ({pretty_downloaded} / {pretty_total})"),
                 progress,
+                rate,
             );
         } else {
-            worker.send_download(format!("Downloading patch info ({pretty_downloaded})"), 1.);
+            worker.send_transfer(format!("{label} ({pretty_downloaded})"), 1., rate);
         }
-    });
+    }
+}
+
+/// Downloads the patchlist and returns the parsed result
+pub fn patch_metadata(worker: &PatchWorker) -> Result<Patchlist, PatchError> {
+    fetch_patch_metadata(
+        worker,
+        progress_reporter(worker, tr!("Downloading patch info")),
+    )
+}
+
+/// Like `patch_metadata`, but without showing progress in the GUI
+pub fn patch_metadata_quietly(worker: &PatchWorker) -> Result<Patchlist, PatchError> {
+    fetch_patch_metadata(worker, |_, _| {})
+}
+
+fn fetch_patch_metadata<F>(worker: &PatchWorker, callback: F) -> Result<Patchlist, PatchError>
+where
+    F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
+{
+    let url = worker.patchlist_url.clone();
+    let result = metacache::conditional_file(worker, url.clone(), callback);
 
     let json_bytes = result.map_err(|why| why.to_patch_error("Failed to get patch info"))?;
 
-    let patch_dir = serde_json::from_slice::<Directory>(&json_bytes)
+    // Never apply a patch list which didn't come from us
+    signature::verify(worker, &url, &json_bytes)
+        .map_err(|why| why.to_patch_error("Patch info could not be verified"))?;
+
+    parse_patch_metadata(&json_bytes)
+}
+
+/// The patch list format this launcher understands. Servers publishing a
+/// newer format need a newer launcher.
+const PATCHLIST_SCHEMA: u32 = 2;
+
+/// A patch list along with the version of its format. Patch lists from
+/// before the format was versioned are a bare directory, and count as
+/// version 1.
+#[derive(Deserialize)]
+struct PatchlistEnvelope {
+    schema: u32,
+    patchlist: serde_json::Value,
+    /// Unix permissions for some files, which older patch lists don't have
+    #[serde(default)]
+    modes: HashMap<String, String>,
+}
+
+/// The files in the current version of the game, and how they are installed
+pub struct Patchlist {
+    pub root: Directory,
+    pub modes: FileModes,
+}
+
+fn parse_patch_metadata(json_bytes: &[u8]) -> Result<Patchlist, PatchError> {
+    let value = serde_json::from_slice::<serde_json::Value>(json_bytes)
+        .map_err(|why| why.to_patch_error("Failed to parse patch info"))?;
+
+    let mut modes = HashMap::new();
+    let patchlist = if value.get("schema").is_some() {
+        let envelope = serde_json::from_value::<PatchlistEnvelope>(value)
+            .map_err(|why| why.to_patch_error("Failed to parse patch info"))?;
+        if envelope.schema > PATCHLIST_SCHEMA {
+            let outdated = PatcherError::LauncherOutdated {
+                schema: envelope.schema,
+            };
+            return Err(PatchError::from_error(
+                outdated,
+                "Failed to parse patch info",
+            ));
+        }
+        modes = envelope.modes;
+        envelope.patchlist
+    } else {
+        value
+    };
+
+    let root = serde_json::from_value::<Directory>(patchlist)
         .map_err(|why| why.to_patch_error("Failed to parse patch info"))?;
+    Ok(Patchlist {
+        root,
+        modes: FileModes::new(modes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::net::{TcpListener, TcpStream};
+
+    use super::*;
+
+    /// Serves one request on a local port. `respond` is given the connection
+    /// once the request has been read.
+    fn serve<F>(respond: F) -> reqwest::Url
+    where
+        F: FnOnce(TcpStream) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/patch.json", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            respond(stream);
+        });
+        reqwest::Url::parse(&url).unwrap()
+    }
+
+    fn respond_with(status: &'static str) -> reqwest::Url {
+        serve(move |mut stream| {
+            write!(stream, "HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n").unwrap();
+        })
+    }
+
+    fn get(runtime: &tokio::runtime::Runtime, url: reqwest::Url) -> reqwest::Response {
+        runtime
+            .block_on(reqwest::Client::new().get(url).send())
+            .unwrap()
+    }
 
-    Ok(patch_dir)
+    #[test]
+    fn missing_files_are_not_retried() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = get(&runtime, respond_with("404 Not Found"));
+        let why = PatcherError::from(response.error_for_status().unwrap_err());
+        assert!(why.is_not_found());
+        assert!(!why.is_transient());
+        assert!(why.category() == crate::message::ErrorCategory::Server);
+    }
+
+    #[test]
+    fn server_errors_are_retried() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = get(&runtime, respond_with("503 Service Unavailable"));
+        let why = PatcherError::from(response.error_for_status().unwrap_err());
+        assert_eq!(why.status(), Some(reqwest::StatusCode::SERVICE_UNAVAILABLE));
+        assert!(why.is_transient());
+    }
+
+    #[test]
+    fn refused_connections_are_retried() {
+        let url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/", listener.local_addr().unwrap())
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let why = runtime
+            .block_on(reqwest::Client::new().get(url).send())
+            .map(drop)
+            .map_err(PatcherError::from)
+            .unwrap_err();
+        assert!(matches!(why, PatcherError::Network(_)));
+        assert!(why.is_transient());
+    }
+
+    #[test]
+    fn next_chunk_times_out_when_the_server_stalls() {
+        let url = serve(|mut stream| {
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello").unwrap();
+            stream.flush().unwrap();
+            // Hold the connection open without sending the rest
+            std::thread::sleep(Duration::from_secs(2));
+        });
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut stream = get(&runtime, url).bytes_stream();
+        let throttle = Throttle::new(0);
+        let faults = Faults::default();
+        let read_timeout = Some(Duration::from_millis(200));
+
+        let first = runtime.block_on(next_chunk(&mut stream, read_timeout, &throttle, &faults));
+        assert_eq!(first.unwrap().as_deref(), Some(&b"hello"[..]));
+        let second = runtime.block_on(next_chunk(&mut stream, read_timeout, &throttle, &faults));
+        let why = second.unwrap_err();
+        assert!(matches!(why, PatcherError::TimedOut));
+        assert!(why.is_transient());
+    }
+
+    #[test]
+    fn next_chunk_fails_when_the_connection_drops() {
+        let url = serve(|mut stream| {
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello").unwrap();
+        });
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut stream = get(&runtime, url).bytes_stream();
+        let throttle = Throttle::new(0);
+        let faults = Faults::default();
+
+        let why = runtime
+            .block_on(async {
+                while next_chunk(&mut stream, None, &throttle, &faults)
+                    .await?
+                    .is_some()
+                {}
+                Ok::<_, PatcherError>(())
+            })
+            .unwrap_err();
+        assert!(why.is_transient());
+    }
+
+    #[test]
+    fn decode_body_decompresses() {
+        let text = b"patch info ".repeat(100);
+        let zstd = zstd::encode_all(text.as_slice(), 3).unwrap();
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&text).unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        let body =
+            |bytes: &[u8]| -> Box<dyn Read> { Box::new(std::io::Cursor::new(bytes.to_vec())) };
+        assert_eq!(decode_body(None, body(&text), 10_000).unwrap(), text);
+        assert_eq!(
+            decode_body(Some("zstd"), body(&zstd), 10_000).unwrap(),
+            text
+        );
+        assert_eq!(
+            decode_body(Some("gzip"), body(&gzip), 10_000).unwrap(),
+            text
+        );
+    }
+
+    #[test]
+    fn decode_body_refuses_what_it_cant_read() {
+        let body =
+            || -> Box<dyn Read> { Box::new(std::io::Cursor::new(b"not compressed".to_vec())) };
+        let why = decode_body(Some("br"), body(), 10_000).unwrap_err();
+        assert!(matches!(why, PatcherError::Unexpected(_)));
+        let why = decode_body(Some("zstd"), body(), 10_000).unwrap_err();
+        assert!(matches!(why, PatcherError::Parse(_)));
+    }
+
+    #[test]
+    fn decode_body_stops_at_the_limit() {
+        // Small when compressed, too large once it isn't
+        let zstd = zstd::encode_all(vec![0; 100_000].as_slice(), 3).unwrap();
+        let why = decode_body(Some("zstd"), Box::new(std::io::Cursor::new(zstd)), 50_000);
+        assert!(matches!(
+            why,
+            Err(PatcherError::TooLarge {
+                size: 50_001,
+                max_size: 50_000
+            })
+        ));
+    }
+
+    #[test]
+    fn patch_info_from_a_newer_launcher_is_refused() {
+        let json = format!(
+            r#"{{"schema": {}, "patchlist": {{}}}}"#,
+            PATCHLIST_SCHEMA + 1
+        );
+        let why = parse_patch_metadata(json.as_bytes()).err().unwrap();
+        assert!(why.is_launcher_outdated());
+        assert!(parse_patch_metadata(b"{").is_err());
+    }
 }
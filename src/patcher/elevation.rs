@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use super::preflight;
+
+/// Whether changing the game directory needs administrator rights, like
+/// anything under Program Files does. Only Windows can ask for them, so this
+/// is never the case elsewhere.
+pub fn needed(game_dir: &Path) -> bool {
+    if !cfg!(windows) {
+        return false;
+    }
+    // The game directory itself may not exist yet
+    match game_dir.ancestors().find(|dir| dir.is_dir()) {
+        Some(dir) => {
+            matches!(preflight::check_dir(dir), Err(why) if why.kind() == ErrorKind::PermissionDenied)
+        }
+        None => false,
+    }
+}
+
+/// Runs the launcher again with administrator rights, which asks the player
+/// through UAC, and waits for it to finish. Only that copy of the launcher
+/// is elevated; this one, which starts the game, never is.
+#[cfg(windows)]
+pub fn run_elevated(launcher: &Path, args: &[String]) -> Result<(), Box<dyn Error>> {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+    // Don't flash a console window
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let arguments = args
+        .iter()
+        .map(|arg| format!("\"{}\"", arg.replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(" ");
+    // Start-Process fails if the player says no
+    let script = "$process = Start-Process -FilePath $env:AECO_LAUNCHER \
+        -ArgumentList $env:AECO_ARGUMENTS -Verb RunAs -Wait -PassThru; \
+        exit $process.ExitCode";
+    let status = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .env("AECO_LAUNCHER", launcher)
+        .env("AECO_ARGUMENTS", arguments)
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()?;
+    if !status.success() {
+        return Err(format!("Patching with administrator rights did not succeed: {status}").into());
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn run_elevated(_launcher: &Path, _args: &[String]) -> Result<(), Box<dyn Error>> {
+    Err("Administrator rights can only be asked for on Windows".into())
+}
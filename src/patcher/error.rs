@@ -1,41 +1,448 @@
+use super::fault::Injected;
+use super::utils::byte_string;
+use crate::message::{ErrorCategory, ErrorDetails};
 use std::error::Error;
+use std::path::PathBuf;
 
 pub struct PatchError {
     /// The internal error
-    pub internal_error: Box<dyn Error>,
+    pub internal_error: PatcherError,
     /// The message which will be displayed in the client
     pub friendly_message: String,
-    /// Controls the color of the error in the GUI
-    pub level: PatchErrorLevel,
-}
-
-pub enum PatchErrorLevel {
-    /// Displayed as yellow in the GUI
-    Low,
-    /// Displayed as red in the GUI
-    High,
 }
 
 pub trait ToPatchError {
-    /// Converts to a PatchError, with level High by default
+    /// Converts to a PatchError
     fn to_patch_error(self, friendly_message: &str) -> PatchError;
-    /// Converts to a PatchError, with the level specified by a parameter
-    fn to_patch_error_level(self, friendly_message: &str, level: PatchErrorLevel) -> PatchError;
 }
 
 impl<T> ToPatchError for T
 where
-    T: Into<Box<dyn Error>>,
+    T: Into<PatcherError>,
 {
     fn to_patch_error(self, friendly_message: &str) -> PatchError {
-        self.to_patch_error_level(friendly_message, PatchErrorLevel::High)
-    }
-
-    fn to_patch_error_level(self, friendly_message: &str, level: PatchErrorLevel) -> PatchError {
         PatchError {
             internal_error: self.into(),
-            friendly_message: friendly_message.to_owned(),
-            level,
+            // Friendly messages are usually fixed text, which can be
+            // translated as a whole
+            friendly_message: crate::i18n::translate(friendly_message),
+        }
+    }
+}
+
+/// Why something the launcher did failed. Retrying, the advice the player
+/// is given and the text shown for problems they can fix all go by the
+/// variant.
+#[derive(Debug, thiserror::Error)]
+pub enum PatcherError {
+    /// The patch server couldn't be reached, or the connection broke
+    #[error(transparent)]
+    Network(reqwest::Error),
+    /// The patch server sent nothing for longer than the read timeout
+    #[error("Timed out waiting for data from the server")]
+    TimedOut,
+    /// The download ended before all of it arrived
+    #[error("Downloaded {received} bytes, expected {expected}")]
+    Incomplete { received: u64, expected: u64 },
+    /// The patch server answered with an error status
+    #[error(transparent)]
+    Server(reqwest::Error),
+    /// The patch server answered, but not the way the request needed
+    #[error("{0}")]
+    Unexpected(String),
+    /// A fault injected for testing, which counts as the real thing
+    #[error(transparent)]
+    Injected(#[from] Injected),
+    /// What the patch server sent couldn't be read
+    #[error(transparent)]
+    Parse(Box<dyn Error + Send + Sync>),
+    /// The download is larger than the launcher accepts
+    #[error("File is too large ({}, the limit is {})", byte_string(*.size), byte_string(*.max_size))]
+    TooLarge { size: u64, max_size: u64 },
+    /// Something which was downloaded doesn't match its digest or signature
+    #[error("{0}")]
+    Verification(String),
+    /// A patched file arrived, but doesn't match its digest, which happens
+    /// when it is damaged on the way. Downloading it again can help.
+    #[error("Downloaded file {0} did not match its digest")]
+    Corrupted(reqwest::Url),
+    /// The patch server uses a newer patch list format than this launcher
+    /// understands
+    #[error("The patch server uses patch list format {schema}, which is too new")]
+    LauncherOutdated { schema: u32 },
+    /// Reading or writing files failed
+    #[error(transparent)]
+    Disk(std::io::Error),
+    /// There isn't enough free disk space for an operation
+    #[error("Not enough disk space: {required} bytes are needed, {available} are free")]
+    InsufficientSpace { required: u64, available: u64 },
+    /// Files or directories which patching needs to change can't be changed,
+    /// along with why not
+    #[error("{} paths can't be changed, such as {:?}", .0.len(), .0[0])]
+    UnwritablePaths(Vec<(PathBuf, String)>),
+    /// The launcher is shutting down, so no more downloads can start
+    #[error("Downloads were stopped")]
+    Stopped(#[from] tokio::sync::AcquireError),
+    /// Anything else, as it was described where it happened
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<reqwest::Error> for PatcherError {
+    fn from(why: reqwest::Error) -> Self {
+        if why.status().is_some() {
+            Self::Server(why)
+        } else if why.is_decode() {
+            Self::Parse(why.into())
+        } else {
+            Self::Network(why)
+        }
+    }
+}
+
+impl From<std::io::Error> for PatcherError {
+    fn from(why: std::io::Error) -> Self {
+        // Reading a download as it arrives wraps its errors in these
+        let why = match why.downcast::<PatcherError>() {
+            Ok(why) => return why,
+            Err(why) => why,
+        };
+        match why.kind() {
+            std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
+                Self::Parse(why.into())
+            }
+            _ => Self::Disk(why),
+        }
+    }
+}
+
+impl From<serde_json::Error> for PatcherError {
+    fn from(why: serde_json::Error) -> Self {
+        Self::Parse(why.into())
+    }
+}
+
+impl From<zip::result::ZipError> for PatcherError {
+    fn from(why: zip::result::ZipError) -> Self {
+        match why {
+            zip::result::ZipError::Io(why) => why.into(),
+            why => Self::Parse(why.into()),
+        }
+    }
+}
+
+/// Errors from the parts of the launcher which don't have their own type
+/// are sorted into variants by what they turn out to be
+impl From<Box<dyn Error>> for PatcherError {
+    fn from(why: Box<dyn Error>) -> Self {
+        let why = match why.downcast::<PatcherError>() {
+            Ok(why) => return *why,
+            Err(why) => why,
+        };
+        let why = match why.downcast::<std::io::Error>() {
+            Ok(why) => return (*why).into(),
+            Err(why) => why,
+        };
+        let why = match why.downcast::<reqwest::Error>() {
+            Ok(why) => return (*why).into(),
+            Err(why) => why,
+        };
+        let why = match why.downcast::<serde_json::Error>() {
+            Ok(why) => return (*why).into(),
+            Err(why) => why,
+        };
+        let why = match why.downcast::<zip::result::ZipError>() {
+            Ok(why) => return (*why).into(),
+            Err(why) => why,
+        };
+        let why = match why.downcast::<Injected>() {
+            Ok(why) => return (*why).into(),
+            Err(why) => why,
+        };
+        // Keep the causes, which would otherwise be lost
+        let mut text = why.to_string();
+        let mut cause = why.source();
+        while let Some(error) = cause {
+            text = format!("{text}: {error}");
+            cause = error.source();
+        }
+        Self::Other(text)
+    }
+}
+
+impl From<url::ParseError> for PatcherError {
+    fn from(why: url::ParseError) -> Self {
+        Self::Parse(Box::new(why))
+    }
+}
+
+impl From<aeco_archive::ArchiveError> for PatcherError {
+    fn from(why: aeco_archive::ArchiveError) -> Self {
+        Self::other(why)
+    }
+}
+
+impl From<std::path::StripPrefixError> for PatcherError {
+    fn from(why: std::path::StripPrefixError) -> Self {
+        Self::other(why)
+    }
+}
+
+impl From<String> for PatcherError {
+    fn from(why: String) -> Self {
+        Self::Other(why)
+    }
+}
+
+impl From<&str> for PatcherError {
+    fn from(why: &str) -> Self {
+        Self::Other(why.to_string())
+    }
+}
+
+impl From<subprocess::PopenError> for PatcherError {
+    fn from(why: subprocess::PopenError) -> Self {
+        match why {
+            subprocess::PopenError::IoError(why) => why.into(),
+            why => Self::Other(why.to_string()),
+        }
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for PatcherError {
+    fn from(why: Box<dyn Error + Send + Sync>) -> Self {
+        (why as Box<dyn Error>).into()
+    }
+}
+
+impl PatcherError {
+    /// Wraps an error which has no variant of its own
+    pub fn other(why: impl std::fmt::Display) -> Self {
+        Self::Other(why.to_string())
+    }
+
+    /// What kind of problem this is, for the GUI and telemetry
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Network(_) | Self::TimedOut | Self::Incomplete { .. } => ErrorCategory::Network,
+            Self::Server(_) | Self::Unexpected(_) | Self::TooLarge { .. } => ErrorCategory::Server,
+            Self::Injected(injected) => injected.category(),
+            Self::Parse(_) => ErrorCategory::Parse,
+            Self::Verification(_) | Self::Corrupted(_) => ErrorCategory::Verification,
+            Self::Disk(_) | Self::InsufficientSpace { .. } | Self::UnwritablePaths(_) => {
+                ErrorCategory::Disk
+            }
+            Self::LauncherOutdated { .. } | Self::Stopped(_) | Self::Other(_) => {
+                ErrorCategory::Other
+            }
+        }
+    }
+
+    /// Whether trying again is likely to go better, such as after a timeout
+    /// or a 5xx response
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Network(why) => why.is_timeout() || why.is_connect() || why.is_body(),
+            Self::TimedOut | Self::Incomplete { .. } | Self::Corrupted(_) | Self::Injected(_) => {
+                true
+            }
+            Self::Server(why) => why.status().is_some_and(|status| status.is_server_error()),
+            Self::Unexpected(_)
+            | Self::Parse(_)
+            | Self::TooLarge { .. }
+            | Self::Verification(_)
+            | Self::LauncherOutdated { .. }
+            | Self::Disk(_)
+            | Self::InsufficientSpace { .. }
+            | Self::UnwritablePaths(_)
+            | Self::Stopped(_)
+            | Self::Other(_) => false,
+        }
+    }
+
+    /// What to tell the player about problems they can do something about
+    /// themselves, in their language. Other problems are described by what
+    /// was being done when they happened instead.
+    pub fn friendly_message(&self) -> Option<String> {
+        let text = match self {
+            Self::InsufficientSpace {
+                required,
+                available,
+            } => tr!(
+                "Not enough disk space: {needed} more is needed",
+                needed = byte_string(required.saturating_sub(*available))
+            ),
+            Self::UnwritablePaths(problems) => {
+                let (first, _) = &problems[0];
+                let first = format!("{first:?}");
+                match problems.len() {
+                    1 => tr!("Can't change {path}. Is the game running?", path = first),
+                    count => tr!(
+                        "Can't change {path} and {count} other paths. Is the game running?",
+                        path = first,
+                        count = count - 1,
+                    ),
+                }
+            }
+            Self::LauncherOutdated { schema } => tr!(
+                "This launcher is too old for the patch server (patch list format {schema}), \
+                 please download the newest one",
+                schema = schema
+            ),
+            _ => return None,
+        };
+        Some(text)
+    }
+
+    /// The request which failed, if the failure came from reqwest
+    pub fn request(&self) -> Option<&reqwest::Error> {
+        match self {
+            Self::Network(why) | Self::Server(why) => Some(why),
+            _ => None,
+        }
+    }
+
+    /// What the patch server answered with, if it was an error status
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        self.request().and_then(reqwest::Error::status)
+    }
+
+    /// Whether the patch server doesn't have the file
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(reqwest::StatusCode::NOT_FOUND)
+    }
+}
+
+impl PatchError {
+    /// Whether the patch server refused our credentials, or wanted some and
+    /// we had none
+    pub fn is_unauthorized(&self) -> bool {
+        self.internal_error.status() == Some(reqwest::StatusCode::UNAUTHORIZED)
+    }
+
+    /// Whether the patch server needs a newer launcher
+    pub fn is_launcher_outdated(&self) -> bool {
+        matches!(self.internal_error, PatcherError::LauncherOutdated { .. })
+    }
+
+    /// Converts an error to a PatchError, using the error's own description
+    /// as the friendly message if it is one the user can act on
+    pub fn from_error(why: impl Into<PatcherError>, friendly_message: &str) -> Self {
+        let why = why.into();
+        match why.friendly_message() {
+            Some(message) => why.to_patch_error(&message),
+            None => why.to_patch_error(friendly_message),
+        }
+    }
+}
+
+/// Collects everything known about why an error happened: its chain of
+/// causes, and the URL and HTTP status of the request which failed, if any
+pub fn details(why: &PatcherError) -> ErrorDetails {
+    let mut causes = Vec::new();
+    let mut cause: Option<&(dyn Error + 'static)> = Some(why);
+    while let Some(error) = cause {
+        causes.push(error.to_string());
+        cause = error.source();
+    }
+    let request = why.request();
+    ErrorDetails {
+        causes,
+        url: request
+            .and_then(reqwest::Error::url)
+            .map(|url| url.to_string()),
+        http_status: why.status().map(|status| status.as_u16()),
+        category: why.category(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patcher_error_retries_only_what_may_go_away() {
+        assert!(PatcherError::TimedOut.is_transient());
+        assert!(PatcherError::Incomplete {
+            received: 1,
+            expected: 2
+        }
+        .is_transient());
+        assert!(PatcherError::from(Injected::ServerError).is_transient());
+        let url = reqwest::Url::parse("http://localhost/patch/all/data.txt").unwrap();
+        assert!(PatcherError::Corrupted(url).is_transient());
+
+        assert!(!PatcherError::Unexpected("Expected a partial response".into()).is_transient());
+        assert!(!PatcherError::Parse("broken".into()).is_transient());
+        assert!(!PatcherError::TooLarge {
+            size: 2,
+            max_size: 1
         }
+        .is_transient());
+        assert!(!PatcherError::Verification("mismatch".into()).is_transient());
+        let disk = std::io::Error::new(std::io::ErrorKind::StorageFull, "full");
+        assert!(!PatcherError::Disk(disk).is_transient());
+    }
+
+    #[test]
+    fn patcher_error_categories() {
+        assert!(PatcherError::TimedOut.category() == ErrorCategory::Network);
+        assert!(PatcherError::from(Injected::ServerError).category() == ErrorCategory::Server);
+        assert!(
+            PatcherError::from(Injected::ConnectionDropped).category() == ErrorCategory::Network
+        );
+        assert!(PatcherError::Parse("broken".into()).category() == ErrorCategory::Parse);
+        assert!(
+            PatcherError::Verification("mismatch".into()).category() == ErrorCategory::Verification
+        );
+        let disk = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        assert!(PatcherError::Disk(disk).category() == ErrorCategory::Disk);
+    }
+
+    #[test]
+    fn io_errors_from_downloads_keep_their_category() {
+        let why = std::io::Error::other(PatcherError::TimedOut);
+        assert!(PatcherError::from(why).category() == ErrorCategory::Network);
+
+        let why = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+        assert!(PatcherError::from(why).category() == ErrorCategory::Disk);
+    }
+
+    #[test]
+    fn boxed_errors_keep_their_variant() {
+        let why: Box<dyn Error> = Box::new(PatcherError::LauncherOutdated { schema: 3 });
+        let why = PatchError::from_error(why, "Failed to parse patch info");
+        assert!(why.is_launcher_outdated());
+    }
+
+    #[test]
+    fn problems_the_player_can_fix_are_explained() {
+        let why = PatchError::from_error(
+            PatcherError::InsufficientSpace {
+                required: 2048,
+                available: 1024,
+            },
+            "Could not update",
+        );
+        assert!(why.friendly_message.contains("Not enough disk space"));
+        assert!(why.internal_error.category() == ErrorCategory::Disk);
+
+        let why = PatchError::from_error(PatcherError::TimedOut, "Could not update");
+        assert_eq!(why.friendly_message, "Could not update");
+    }
+
+    #[test]
+    fn details_lists_the_causes() {
+        let why = PatchError::from_error(
+            PatcherError::Incomplete {
+                received: 1,
+                expected: 2,
+            },
+            "Could not download",
+        );
+        let details = details(&why.internal_error);
+        assert_eq!(details.causes, ["Downloaded 1 bytes, expected 2"]);
+        assert!(details.category == ErrorCategory::Network);
+        assert_eq!(details.url, None);
     }
 }
@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use tracing::warn;
+
+use crate::message::ErrorCategory;
+
+/// Makes the launcher fail on purpose, for testing how it retries, resumes
+/// and rolls back. It is a comma separated list of faults, each one of
+/// `drop`, `corrupt`, `500` or `disk-full`, optionally followed by `@N` to
+/// only fail the Nth time instead of every time. For example,
+/// `AECO_FAULTS=500@2,drop@10` fails the second request with a server error
+/// and drops the connection at the tenth chunk downloaded.
+const FAULTS_ENV: &str = "AECO_FAULTS";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Point {
+    /// The connection drops while a download is arriving
+    Drop,
+    /// A chunk of a download arrives with a byte changed
+    Corrupt,
+    /// The server answers a request with 500 Internal Server Error
+    ServerError,
+    /// The disk is full when a patched file is written
+    DiskFull,
+}
+
+impl Point {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "drop" => Some(Self::Drop),
+            "corrupt" => Some(Self::Corrupt),
+            "500" => Some(Self::ServerError),
+            "disk-full" => Some(Self::DiskFull),
+            _ => None,
+        }
+    }
+
+    /// Where this point's count is kept
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+struct Fault {
+    point: Point,
+    /// Only fail the Nth time the point is reached, counting from 1
+    nth: Option<u64>,
+}
+
+/// The faults to inject, and how often each point was reached so far. This
+/// is empty unless `AECO_FAULTS` is set, so normally nothing fails.
+#[derive(Default)]
+pub struct Faults {
+    faults: Vec<Fault>,
+    reached: [AtomicU64; 4],
+}
+
+impl Faults {
+    /// Reads the faults from `AECO_FAULTS`. This happens before logging is
+    /// set up, so problems with it are only printed.
+    pub fn from_env() -> Self {
+        let spec = match std::env::var(FAULTS_ENV) {
+            Ok(spec) if !spec.trim().is_empty() => spec,
+            _ => return Self::default(),
+        };
+        let mut faults = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, nth) = match entry.split_once('@') {
+                Some((name, nth)) => match nth.parse::<u64>() {
+                    Ok(nth) if nth > 0 => (name, Some(nth)),
+                    _ => {
+                        warn!("Ignoring fault '{entry}': @ needs a number from 1 up");
+                        continue;
+                    }
+                },
+                None => (entry, None),
+            };
+            match Point::parse(name) {
+                Some(point) => faults.push(Fault { point, nth }),
+                None => warn!("Ignoring unknown fault '{name}'"),
+            }
+        }
+        if !faults.is_empty() {
+            warn!("Injecting faults for testing: {spec}");
+        }
+        Self {
+            faults,
+            ..Self::default()
+        }
+    }
+
+    /// Counts reaching `point`, and says whether it should fail this time
+    fn hit(&self, point: Point) -> bool {
+        if self.faults.is_empty() {
+            return false;
+        }
+        let count = self.reached[point.index()].fetch_add(1, Ordering::Relaxed) + 1;
+        let hit = self
+            .faults
+            .iter()
+            .any(|fault| fault.point == point && fault.nth.is_none_or(|nth| nth == count));
+        if hit {
+            warn!("Injecting a fault at {point} #{count}");
+        }
+        hit
+    }
+
+    /// Called for each chunk of a download as it arrives
+    pub fn chunk(&self, bytes: Bytes) -> Result<Bytes, Injected> {
+        if self.hit(Point::Drop) {
+            return Err(Injected::ConnectionDropped);
+        }
+        if self.hit(Point::Corrupt) && !bytes.is_empty() {
+            let mut corrupted = bytes.to_vec();
+            corrupted[0] ^= 0xff;
+            return Ok(corrupted.into());
+        }
+        Ok(bytes)
+    }
+
+    /// Called for each response from the patch server
+    pub fn response(&self) -> Result<(), Injected> {
+        if self.hit(Point::ServerError) {
+            return Err(Injected::ServerError);
+        }
+        Ok(())
+    }
+
+    /// Called before writing a patched file
+    pub fn write(&self) -> std::io::Result<()> {
+        if self.hit(Point::DiskFull) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::StorageFull,
+                "No space left on the disk (injected fault)",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Drop => "drop",
+            Self::Corrupt => "corrupt",
+            Self::ServerError => "500",
+            Self::DiskFull => "disk-full",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A network failure which was injected, and is retried like the real thing
+#[derive(Debug)]
+pub enum Injected {
+    ConnectionDropped,
+    ServerError,
+}
+
+impl Injected {
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::ConnectionDropped => ErrorCategory::Network,
+            Self::ServerError => ErrorCategory::Server,
+        }
+    }
+}
+
+impl fmt::Display for Injected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConnectionDropped => write!(f, "The connection was dropped (injected fault)"),
+            Self::ServerError => write!(
+                f,
+                "HTTP status server error (500 Internal Server Error) (injected fault)"
+            ),
+        }
+    }
+}
+
+impl Error for Injected {}
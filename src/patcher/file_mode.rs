@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use tracing::warn;
+
+/// The Unix permissions the patch list gives some files, such as native
+/// binaries which need to be executable. The patch list has them as octal
+/// strings like "755", by the file's path relative to the game directory.
+/// Files which aren't listed keep the permissions they already had.
+#[derive(Default)]
+pub struct FileModes {
+    modes: HashMap<String, u32>,
+}
+
+impl FileModes {
+    pub fn new(modes: HashMap<String, String>) -> Self {
+        let modes = modes
+            .into_iter()
+            .filter_map(|(path, mode)| match u32::from_str_radix(mode.trim(), 8) {
+                // Only the permission bits; the patch server has no business
+                // making anything setuid
+                Ok(mode) => Some((path.trim_start_matches('/').to_string(), mode & 0o777)),
+                Err(why) => {
+                    warn!("Invalid mode '{mode}' for {path} in patch info: {why}");
+                    None
+                }
+            })
+            .collect();
+        Self { modes }
+    }
+
+    /// The mode for a file, given by its path relative to the game directory
+    pub fn get(&self, relative_path: &Path) -> Option<u32> {
+        let key = relative_path.to_string_lossy().replace('\\', "/");
+        self.modes.get(&key).copied()
+    }
+}
+
+/// Gives a file a mode from the patch list, if it has one. Only Unixlikes
+/// have modes, so this does nothing on Windows.
+pub fn apply(path: &Path, mode: Option<u32>) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
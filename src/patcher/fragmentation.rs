@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::constants::{ARCHIVE_FRAGMENTATION, HASH_CACHE};
+use super::hash_cache::HashCache;
+use super::staging::StagedArchive;
+use super::PatchWorker;
+use crate::message::ArchiveRebuild;
+
+/// How much archives grew from files being replaced in them since they were
+/// last optimized. The old copies of those files still take up space until
+/// the archive is optimized.
+#[derive(Default, Deserialize, Serialize)]
+struct Fragmentation {
+    /// By the path of the archive's .dat, relative to the game directory
+    archives: BTreeMap<String, Growth>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct Growth {
+    files_replaced: usize,
+    bytes: u64,
+}
+
+impl Fragmentation {
+    fn load(worker: &PatchWorker) -> Self {
+        std::fs::read(worker.self_dir.join(ARCHIVE_FRAGMENTATION))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, worker: &PatchWorker) -> Result<(), Box<dyn Error>> {
+        let path = worker.self_dir.join(ARCHIVE_FRAGMENTATION);
+        if self.archives.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(why) if why.kind() != std::io::ErrorKind::NotFound => Err(why.into()),
+                _ => Ok(()),
+            };
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+/// The size of an archive pair on disk, or 0 if it can't be found out
+pub fn pair_size(dat: &Path, hed: &Path) -> u64 {
+    [dat, hed]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Notes that an archive had files replaced without being optimized
+pub fn record(worker: &PatchWorker, rebuild: &ArchiveRebuild) {
+    let path = Path::new(&rebuild.archive);
+    let key = path
+        .strip_prefix(&worker.self_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned();
+    let mut fragmentation = Fragmentation::load(worker);
+    let growth = fragmentation.archives.entry(key).or_default();
+    growth.files_replaced += rebuild.files_replaced;
+    growth.bytes += rebuild.size_after.saturating_sub(rebuild.size_before);
+    if let Err(why) = fragmentation.save(worker) {
+        warn!("Could not save which archives need optimizing: {why}");
+    }
+}
+
+/// Optimizes each archive which had files replaced since it was last
+/// optimized. Returns how many were optimized, and how many bytes that gave
+/// back.
+pub fn optimize(worker: &PatchWorker) -> Result<(usize, u64), Box<dyn Error>> {
+    let _game_lock = worker.lock_game_dir()?;
+    let mut fragmentation = Fragmentation::load(worker);
+    let hash_cache_path = worker.self_dir.join(HASH_CACHE);
+    let mut hash_cache = HashCache::load(&hash_cache_path);
+    let archives = fragmentation.archives.keys().cloned().collect::<Vec<_>>();
+    let mut optimized = 0;
+    let mut freed = 0;
+
+    for (index, key) in archives.iter().enumerate() {
+        let dat = worker.self_dir.join(key);
+        let hed = dat.with_extension("hed");
+        // The archive was removed since, by a patch or the player
+        if !dat.is_file() || !hed.is_file() {
+            fragmentation.archives.remove(key);
+            continue;
+        }
+
+        worker.send_download(
+            tr!(
+                "Optimizing archive {archive} ({current} / {total})...",
+                archive = key,
+                current = index + 1,
+                total = archives.len()
+            ),
+            index as f32 / archives.len() as f32,
+        );
+        let before = std::fs::metadata(&dat)?;
+        let size_before = pair_size(&dat, &hed);
+        let started = Instant::now();
+        let keep_alive = worker.keep_alive();
+        StagedArchive::new(worker, &dat, &hed)?.commit(&dat, &hed, true)?;
+        drop(keep_alive);
+        let size_after = pair_size(&dat, &hed);
+        info!(
+            archive = %dat.display(),
+            size_before,
+            size_after,
+            duration_ms = started.elapsed().as_millis() as u64,
+            "Optimized archive"
+        );
+        optimized += 1;
+        freed += size_before.saturating_sub(size_after);
+
+        // Optimizing doesn't change what is in the archive, so its files
+        // don't need hashing again next time
+        hash_cache.archive_rewritten(&dat, &before);
+        if let Err(why) = hash_cache.save(&hash_cache_path) {
+            warn!("Could not save hash cache: {why}");
+        }
+        // Saved as each one finishes, so an interrupted run doesn't start
+        // over
+        fragmentation.archives.remove(key);
+        fragmentation.save(worker)?;
+    }
+
+    fragmentation.save(worker)?;
+    Ok((optimized, freed))
+}
@@ -0,0 +1,59 @@
+use std::cmp::Ordering;
+
+use serde::Deserialize;
+
+use super::constants::GAME_VERSION;
+use super::error::{PatchError, ToPatchError};
+use super::metacache;
+use super::signature;
+use super::PatchWorker;
+
+/// The contents of the version file published next to the patch list
+#[derive(Deserialize)]
+struct VersionFile {
+    version: String,
+}
+
+/// Gets the version of the game which the patch list is for. Servers which
+/// don't publish a version file don't have versions, which isn't an error.
+pub fn available(worker: &PatchWorker) -> Result<Option<String>, PatchError> {
+    let url = worker
+        .patchlist_url
+        .join(GAME_VERSION)
+        .map_err(|why| why.to_patch_error("Failed to get game version"))?;
+
+    let json_bytes = match metacache::conditional_file(worker, url.clone(), |_, _| {}) {
+        Ok(json_bytes) => json_bytes,
+        Err(why) if why.is_not_found() => return Ok(None),
+        Err(why) => return Err(why.to_patch_error("Failed to get game version")),
+    };
+
+    signature::verify(worker, &url, &json_bytes)
+        .map_err(|why| why.to_patch_error("Game version could not be verified"))?;
+
+    let version_file = serde_json::from_slice::<VersionFile>(&json_bytes)
+        .map_err(|why| why.to_patch_error("Failed to parse game version"))?;
+
+    Ok(Some(version_file.version))
+}
+
+/// Compares versions like "1.4.2" part by part, so that "1.10" comes after
+/// "1.9". Parts which aren't numbers are compared as text.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let mut a_parts = a.split('.');
+    let mut b_parts = b.split('.');
+    loop {
+        let ordering = match (a_parts.next(), b_parts.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(_), None) => return Ordering::Greater,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use aeco_patch_config::fsobject::File as PatchFile;
+use serde::{Deserialize, Serialize};
+
+/// Remembers the digests of files which were checked before, along with the
+/// size and modification time they had. Files which still have the same size
+/// and modification time don't need to be read and hashed again.
+#[derive(Default, Deserialize, Serialize)]
+pub struct HashCache {
+    entries: HashMap<String, CachedHash>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq)]
+struct CachedHash {
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+    /// The digest type is whatever the patch info uses, so it is kept in its
+    /// serialized form
+    digest: serde_json::Value,
+}
+
+impl CachedHash {
+    /// `disk_path` is the file whose size and modification time are used.
+    /// For a file in an archive, this is the archive.
+    fn new(disk_path: &Path, file: &PatchFile) -> Option<Self> {
+        let metadata = std::fs::metadata(disk_path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            digest: serde_json::to_value(&file.digest).ok()?,
+        })
+    }
+}
+
+impl HashCache {
+    pub fn load(path: &Path) -> Self {
+        std::fs::read(path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Whether the file at `key` was last seen matching `file`, and
+    /// `disk_path` hasn't changed since
+    pub fn is_unchanged(&self, key: &Path, disk_path: &Path, file: &PatchFile) -> bool {
+        match (
+            self.entries.get(&key.to_string_lossy().into_owned()),
+            CachedHash::new(disk_path, file),
+        ) {
+            (Some(cached), Some(current)) => *cached == current,
+            _ => false,
+        }
+    }
+
+    /// Notes that the file at `key` matches `file`
+    pub fn record(&mut self, key: &Path, disk_path: &Path, file: &PatchFile) {
+        let key = key.to_string_lossy().into_owned();
+        match CachedHash::new(disk_path, file) {
+            Some(cached) => self.entries.insert(key, cached),
+            None => self.entries.remove(&key),
+        };
+    }
+
+    /// Keeps the files in an archive known to match after the archive was
+    /// rewritten without changing what is in it, like when optimizing it.
+    /// Only those which were up to date with the archive as it was
+    /// (`before`) are kept; the others are hashed again as usual.
+    pub fn archive_rewritten(&mut self, archive: &Path, before: &std::fs::Metadata) {
+        let after = std::fs::metadata(archive)
+            .ok()
+            .and_then(|after| stamp(&after));
+        let (before, after) = match (stamp(before), after) {
+            (Some(before), Some(after)) => (before, after),
+            _ => return,
+        };
+        for (key, cached) in &mut self.entries {
+            let current = (cached.size, cached.modified_secs, cached.modified_nanos);
+            if Path::new(key).starts_with(archive) && current == before {
+                (cached.size, cached.modified_secs, cached.modified_nanos) = after;
+            }
+        }
+    }
+
+    /// Checks the files this cache knows of which `wanted` picks, and gives
+    /// those which no longer have the digest they were last seen with, or
+    /// are gone. Files which haven't changed size or modification time since
+    /// aren't read.
+    pub fn changed_files<F>(&self, wanted: F) -> Vec<PathBuf>
+    where
+        F: Fn(&Path) -> bool,
+    {
+        self.entries
+            .iter()
+            .map(|(key, cached)| (Path::new(key), cached))
+            .filter(|(path, _)| wanted(path))
+            .filter(|(path, cached)| !still_matches(path, cached))
+            .map(|(path, _)| path.to_owned())
+            .collect()
+    }
+}
+
+/// The size and modification time of a file, as the cache keeps them
+fn stamp(metadata: &std::fs::Metadata) -> Option<(u64, u64, u32)> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_secs(), modified.subsec_nanos()))
+}
+
+/// Whether the file at `path` has the digest it was cached with
+fn still_matches(path: &Path, cached: &CachedHash) -> bool {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+    if let Some(modified) = modified {
+        if metadata.len() == cached.size
+            && modified.as_secs() == cached.modified_secs
+            && modified.subsec_nanos() == cached.modified_nanos
+        {
+            return true;
+        }
+    }
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match std::fs::read(path) {
+        Ok(data) => serde_json::to_value(PatchFile::new(&name, &data).digest)
+            .is_ok_and(|digest| digest == cached.digest),
+        Err(_) => false,
+    }
+}
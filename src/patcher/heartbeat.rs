@@ -0,0 +1,43 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::message::PatchMessage;
+
+/// How often the GUI hears from a busy worker which has nothing else to say
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Sends heartbeats from another thread while the worker is stuck in one
+/// long step it can't report progress from, like rewriting an archive.
+/// They stop once this is dropped.
+pub struct KeepAlive {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeepAlive {
+    pub fn start(tx: Sender<PatchMessage>) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT_INTERVAL) {
+                if tx.send(PatchMessage::Heartbeat).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        // Hanging up wakes the thread
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use tracing::info;
+
+/// Runs a hook command from the game directory, and logs what it prints.
+/// It is stopped if it takes longer than `timeout`. Its output goes to a
+/// file rather than a pipe, so a program it leaves running in the
+/// background doesn't hold it up.
+pub fn run(
+    name: &str,
+    command: &[String],
+    dir: &Path,
+    timeout: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let mut output = tempfile::tempfile()?;
+    let config = subprocess::PopenConfig {
+        stdout: subprocess::Redirection::File(output.try_clone()?),
+        stderr: subprocess::Redirection::Merge,
+        cwd: Some(dir.as_os_str().to_owned()),
+        ..subprocess::PopenConfig::default()
+    };
+    let mut process = subprocess::Popen::create(command, config)?;
+    let status = process.wait_timeout(timeout)?;
+    if status.is_none() {
+        process.kill().ok();
+        process.wait().ok();
+    }
+
+    let mut text = String::new();
+    output.seek(SeekFrom::Start(0))?;
+    output.read_to_string(&mut text).ok();
+    if !text.trim().is_empty() {
+        info!("Output of the {name} hook:\n{}", text.trim_end());
+    }
+
+    match status {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => Err(format!("The {name} hook failed: {status:?}").into()),
+        None => Err(format!(
+            "The {name} hook was stopped after {} seconds",
+            timeout.as_secs()
+        )
+        .into()),
+    }
+}
+
+/// How a hook is shown to the player, and remembered once they agree to
+/// run it. The arguments are written as a JSON list, since joining them
+/// with spaces would make `["a b"]` and `["a", "b"]` look the same.
+pub fn command_line(command: &[String]) -> String {
+    serde_json::to_string(command).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|part| part.to_string()).collect()
+    }
+
+    #[test]
+    fn command_line_keeps_arguments_apart() {
+        assert_eq!(
+            command_line(&strings(&["backup.sh", "--to", "C:\\My Saves"])),
+            r#"["backup.sh","--to","C:\\My Saves"]"#
+        );
+        assert_ne!(
+            command_line(&strings(&["rm", "-rf a"])),
+            command_line(&strings(&["rm", "-rf", "a"]))
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+use tracing::{info, warn};
+
+use super::constants::PATCH_IGNORE;
+
+/// Files which the player has chosen to keep their own version of, such as
+/// approved UI mods. These are listed in `patch-ignore.txt` in the game
+/// directory as glob patterns relative to the game directory, one per line.
+/// Blank lines and lines starting with `#` are ignored.
+#[derive(Default)]
+pub struct IgnoreList {
+    patterns: Vec<Pattern>,
+    /// Files which were skipped because of the list during this patch
+    pub skipped: Vec<PathBuf>,
+}
+
+impl IgnoreList {
+    pub fn load(self_dir: &Path) -> Self {
+        let text = match std::fs::read_to_string(self_dir.join(PATCH_IGNORE)) {
+            Ok(text) => text,
+            Err(_) => return Self::default(),
+        };
+
+        let patterns = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| match Pattern::new(line) {
+                Ok(pattern) => Some(pattern),
+                Err(why) => {
+                    warn!("Invalid pattern '{line}' in {PATCH_IGNORE}: {why}");
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            patterns,
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Whether a file, relative to the game directory, is on the list
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        // Patterns are written with forward slashes on every platform
+        let path = relative_path.to_string_lossy().replace('\\', "/");
+        let options = MatchOptions {
+            case_sensitive: !cfg!(windows),
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches_with(&path, options))
+    }
+
+    /// Like `matches`, but also remembers the file as skipped
+    pub fn skip(&mut self, relative_path: &Path) -> bool {
+        let matches = self.matches(relative_path);
+        if matches {
+            info!("Skipped {relative_path:?} (user override)");
+            self.skipped.push(relative_path.to_path_buf());
+        }
+        matches
+    }
+}
@@ -0,0 +1,66 @@
+use fs2::FileExt;
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::time::Duration;
+
+use super::constants::LOCK_FILE;
+
+/// How many times to try for the lock before deciding another copy of the
+/// launcher has it. A launcher which has just updated itself starts the new
+/// copy before it closes, so the lock can be held for a moment longer.
+const LOCK_ATTEMPTS: u32 = 12;
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Keeps a second copy of the launcher from running from the same directory,
+/// where both would patch the same files. The operating system lets go of
+/// the lock when the launcher exits, however it exits.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Takes the lock for the launcher in `dir`, or returns `None` if
+    /// another copy of the launcher is running from there
+    pub fn acquire(dir: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(dir.join(LOCK_FILE))?;
+        for attempt in 1..=LOCK_ATTEMPTS {
+            match file.try_lock_exclusive() {
+                Ok(()) => return Ok(Some(Self { _file: file })),
+                Err(why) if why.kind() == fs2::lock_contended_error().kind() => {
+                    if attempt < LOCK_ATTEMPTS {
+                        std::thread::sleep(LOCK_RETRY_DELAY);
+                    }
+                }
+                Err(why) => return Err(why.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Takes the lock for a game directory while the game is changed, so
+    /// that launchers installed elsewhere with a profile for the same
+    /// directory take turns. Returns `None` for the launcher's own
+    /// directory, whose lock the launcher holds for as long as it runs.
+    pub fn for_game(launcher_dir: &Path, game_dir: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        if same_dir(launcher_dir, game_dir) {
+            return Ok(None);
+        }
+        std::fs::create_dir_all(game_dir)?;
+        match Self::acquire(game_dir)? {
+            Some(lock) => Ok(Some(lock)),
+            None => Err(format!("Another launcher is changing the game in {game_dir:?}").into()),
+        }
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::{Component, Path, PathBuf};
+
+use tracing::info;
+
+use super::constants::*;
+use super::ignore::IgnoreList;
+use super::last_patch;
+use super::prune::game_files;
+use super::self_update;
+use super::utils::is_below;
+use super::PatchWorker;
+use crate::config::CONFIG_FILE;
+
+/// Files and directories in the game directory which belong to the launcher
+/// itself rather than the game
+const LAUNCHER_FILES: &[&str] = &[
+    CONFIG_FILE,
+    CACHE_DIR,
+    META_CACHE_DIR,
+    LAST_PATCH,
+    JOURNAL,
+    HASH_CACHE,
+    ARCHIVE_FRAGMENTATION,
+    PATCH_REPORT,
+    REMOVED_DIR,
+    BACKUP_DIR,
+    STAGING_DIR,
+    QUARANTINE_DIR,
+    PATCH_IGNORE,
+    BASE_FILES,
+    GAME_INI,
+    GAME_OUTPUT,
+    SESSION_FILE,
+    LOCK_FILE,
+];
+
+/// Remembers which files were installed from the base game ZIP, since they
+/// aren't necessarily in the patch list
+pub fn save_base_files(self_dir: &Path, files: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+    let text = files
+        .iter()
+        .map(|path| format!("{}\n", path.to_string_lossy().replace('\\', "/")))
+        .collect::<String>();
+    std::fs::write(self_dir.join(BASE_FILES), text)?;
+    Ok(())
+}
+
+pub fn load_base_files(self_dir: &Path) -> HashSet<PathBuf> {
+    std::fs::read_to_string(self_dir.join(BASE_FILES))
+        .map(|text| {
+            text.lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Lists files in the game directory which are neither part of the game nor
+/// the launcher, nor on the player's ignore list. These are either leftovers
+/// or something has tampered with the installation.
+///
+/// The paths are relative to the game directory.
+pub fn scan(worker: &PatchWorker) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let last_patch =
+        last_patch::load(worker).ok_or("The game needs to be patched before it can be scanned")?;
+
+    let mut known = game_files(
+        &last_patch.manifest,
+        &last_patch.components,
+        &last_patch.languages,
+    );
+    known.extend(load_base_files(&worker.self_dir));
+    let ignore_list = IgnoreList::load(&worker.self_dir);
+    let aecoupdate_path = worker.get_self_aecoupdate_path()?;
+    let old_launcher_path = self_update::old_path(&worker.self_exe);
+    let rejected_update_path = self_update::rejected_marker(&worker.self_exe);
+
+    let mut foreign = Vec::new();
+    for entry in walkdir(&worker.self_dir)? {
+        let relative = entry.strip_prefix(&worker.self_dir)?;
+        if worker.is_launcher(&entry)
+            || entry == aecoupdate_path
+            || entry == old_launcher_path
+            || entry == rejected_update_path
+            || is_launcher_file(relative)
+            || known.contains(relative)
+            || ignore_list.matches(relative)
+        {
+            continue;
+        }
+        foreign.push(relative.to_owned());
+    }
+
+    foreign.sort();
+    Ok(foreign)
+}
+
+/// Moves files, relative to the game directory, into the quarantine
+/// directory. Returns how many files were moved.
+pub fn quarantine(worker: &PatchWorker, files: &[PathBuf]) -> Result<usize, Box<dyn Error>> {
+    let _game_lock = worker.lock_game_dir()?;
+    let mut moved = 0;
+    for relative in files {
+        // The list comes from the GUI, so don't let it point anywhere else
+        if !is_below(relative) {
+            return Err(format!("Refusing to quarantine {relative:?}").into());
+        }
+
+        let disk_path = worker.self_dir.join(relative);
+        if !disk_path.is_file() {
+            continue;
+        }
+
+        let quarantine_path = worker.self_dir.join(QUARANTINE_DIR).join(relative);
+        info!("Quarantining {disk_path:?} -> {quarantine_path:?}");
+        if let Some(parent) = quarantine_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(&disk_path, &quarantine_path)?;
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+fn is_launcher_file(relative: &Path) -> bool {
+    let first = match relative.components().next() {
+        Some(Component::Normal(name)) => name.to_string_lossy(),
+        _ => return false,
+    };
+    LAUNCHER_FILES
+        .iter()
+        .any(|name| name.trim_end_matches('/') == first)
+}
+
+/// Lists every file below a directory
+fn walkdir(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_owned()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    Ok(files)
+}
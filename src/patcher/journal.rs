@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+use aeco_patch_config::fsobject::File as PatchFile;
+use tracing::warn;
+
+use super::cache::digest_key;
+use super::constants::JOURNAL;
+use super::PatchWorker;
+
+/// Records which files have been checked during a patch, so that a patch
+/// which is interrupted can pick up where it left off instead of checking
+/// every file again.
+///
+/// Each line of the journal is a file's digest followed by its path, so a
+/// file is checked again if the patch list changes it in the meantime.
+#[derive(Default)]
+pub struct Journal {
+    done: HashSet<String>,
+    file: Option<File>,
+}
+
+impl Journal {
+    /// Opens the journal left by the previous patch, or starts a new one
+    pub fn open(worker: &PatchWorker) -> Self {
+        let path = worker.self_dir.join(JOURNAL);
+
+        let done = File::open(&path)
+            .map(|file| BufReader::new(file).lines().map_while(Result::ok).collect())
+            .unwrap_or_default();
+
+        // Not being able to keep a journal only means the next patch can't
+        // be resumed
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|why| warn!("Could not open patch journal: {why}"))
+            .ok();
+
+        Self { done, file }
+    }
+
+    /// Whether this continues a patch which was interrupted
+    pub fn is_resumed(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    /// Whether a file was already checked
+    pub fn contains(&self, path: &Path, file: &PatchFile) -> bool {
+        self.done.contains(&entry(path, file))
+    }
+
+    /// Notes that a file is up to date
+    pub fn record(&mut self, path: &Path, file: &PatchFile) {
+        let entry = entry(path, file);
+        if let Some(journal) = &mut self.file {
+            if let Err(why) = writeln!(journal, "{entry}") {
+                warn!("Could not write to patch journal: {why}");
+            }
+        }
+        self.done.insert(entry);
+    }
+}
+
+/// Identifies a version of a file at a path
+pub fn entry(path: &Path, file: &PatchFile) -> String {
+    format!("{} {}", digest_key(file), path.display())
+}
+
+/// Removes the journal once patching has finished
+pub fn clear(worker: &PatchWorker) -> std::io::Result<()> {
+    let path = worker.self_dir.join(JOURNAL);
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aeco_patch_config::fsobject::Directory;
+use serde::{Deserialize, Serialize};
+
+use super::constants::LAST_PATCH;
+use super::PatchWorker;
+
+/// The outcome of the last time patching finished successfully
+#[derive(Deserialize, Serialize)]
+pub struct LastPatch {
+    /// When patching finished, in seconds since the Unix epoch
+    pub finished_at: u64,
+    /// The patch list which the game was patched to
+    pub manifest: Directory,
+    /// The optional components which were installed
+    #[serde(default)]
+    pub components: Vec<String>,
+    /// The language packs which were installed
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// The version of the game, if the server publishes versions
+    #[serde(default)]
+    pub version: Option<String>,
+    /// The release channel the version is from
+    #[serde(default)]
+    pub channel: String,
+}
+
+/// Gets the result of the last successful patch, if there was one
+pub fn load(worker: &PatchWorker) -> Option<LastPatch> {
+    let data = std::fs::read(worker.self_dir.join(LAST_PATCH)).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Remembers that the game has been fully patched to `manifest`, which is
+/// the given version of the game
+pub fn save(
+    worker: &PatchWorker,
+    manifest: Directory,
+    version: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let last_patch = LastPatch {
+        finished_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        manifest,
+        components: worker.config.patch.components.clone(),
+        languages: worker.config.patch.languages.clone(),
+        version,
+        channel: worker.config.patch.channel.clone(),
+    };
+    std::fs::write(
+        worker.self_dir.join(LAST_PATCH),
+        serde_json::to_vec(&last_patch)?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+
+use aeco_patch_config::fsobject::{Directory, FSObject, File as PatchFile};
+
+use super::journal;
+use super::last_patch::LastPatch;
+use super::utils::{installed_dirs, subdir_by_name};
+
+/// The files of the patch list which the game was last patched to. A file
+/// which is the same in the new patch list was already put in place by that
+/// patch, so only the files which differ need to be checked.
+///
+/// Without a previous patch list, every file is checked.
+#[derive(Default)]
+pub struct ManifestDiff {
+    previous: HashSet<String>,
+}
+
+impl ManifestDiff {
+    pub fn new(self_dir: &Path, last_patch: Option<&LastPatch>) -> Self {
+        let previous = last_patch
+            .map(|last_patch| {
+                entries(
+                    self_dir,
+                    &last_patch.manifest,
+                    &last_patch.components,
+                    &last_patch.languages,
+                )
+            })
+            .unwrap_or_default();
+        Self { previous }
+    }
+
+    /// Whether the last patch installed this version of a file
+    pub fn is_unchanged(&self, path: &Path, file: &PatchFile) -> bool {
+        self.previous.contains(&journal::entry(path, file))
+    }
+
+    /// Gets where to download the files of a patch list which differ from
+    /// the last one
+    pub fn changed_urls(
+        &self,
+        self_dir: &Path,
+        manifest: &Directory,
+        components: &[String],
+        languages: &[String],
+        patch_url: &reqwest::Url,
+    ) -> Result<Vec<reqwest::Url>, Box<dyn Error>> {
+        let mut urls = Vec::new();
+        for dir_name in installed_dirs(components, languages) {
+            if let Some(platform_dir) = subdir_by_name(manifest, &dir_name) {
+                let net_path = patch_url.join(&format!("{dir_name}/"))?;
+                self.collect_changed(platform_dir, self_dir, &net_path, &mut urls)?;
+            }
+        }
+        Ok(urls)
+    }
+
+    fn collect_changed(
+        &self,
+        dir: &Directory,
+        disk_dir: &Path,
+        net_path: &reqwest::Url,
+        urls: &mut Vec<reqwest::Url>,
+    ) -> Result<(), Box<dyn Error>> {
+        for child in &dir.children {
+            match child {
+                FSObject::File(file) => {
+                    if !self.is_unchanged(&disk_dir.join(&file.name), file) {
+                        urls.push(net_path.join(&file.name)?);
+                    }
+                }
+                FSObject::Directory(d) => self.collect_changed(
+                    d,
+                    &disk_dir.join(&d.name),
+                    &net_path.join(&format!("{}/", d.name))?,
+                    urls,
+                )?,
+                FSObject::Archive(a) => {
+                    let dat = disk_dir.join(&a.name).with_extension("dat");
+                    let archive_net_path = net_path.join(&format!("{}.archive/", a.name))?;
+                    for file in &a.files {
+                        if !self.is_unchanged(&dat.join(&file.name), file) {
+                            urls.push(archive_net_path.join(&file.name)?);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists every file a patch list installs, in the same form as the journal
+fn entries(
+    self_dir: &Path,
+    manifest: &Directory,
+    components: &[String],
+    languages: &[String],
+) -> HashSet<String> {
+    let mut entries = HashSet::new();
+    for dir_name in installed_dirs(components, languages) {
+        if let Some(platform_dir) = subdir_by_name(manifest, &dir_name) {
+            collect_entries(platform_dir, self_dir, &mut entries);
+        }
+    }
+    entries
+}
+
+fn collect_entries(dir: &Directory, disk_dir: &Path, entries: &mut HashSet<String>) {
+    for child in &dir.children {
+        match child {
+            FSObject::File(file) => {
+                entries.insert(journal::entry(&disk_dir.join(&file.name), file));
+            }
+            FSObject::Directory(d) => collect_entries(d, &disk_dir.join(&d.name), entries),
+            FSObject::Archive(a) => {
+                let dat = disk_dir.join(&a.name).with_extension("dat");
+                for file in &a.files {
+                    entries.insert(journal::entry(&dat.join(&file.name), file));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use reqwest::header::{
+    HeaderMap, ACCEPT_ENCODING, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use super::constants::META_CACHE_DIR;
+use super::download::{read_body, send, SUPPORTED_ENCODINGS};
+use super::error::PatcherError;
+use super::retry::with_retries;
+use super::PatchWorker;
+
+/// What the server told us about the version of a file we have cached, so we
+/// can ask it whether the file has changed since
+#[derive(Default, Deserialize, Serialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// Downloads a small file which rarely changes, such as the patch list.
+///
+/// The last copy of the file is kept on disk. If the server says the file
+/// hasn't changed since then, the copy on disk is returned instead of
+/// downloading it again.
+pub fn conditional_file<F>(
+    worker: &PatchWorker,
+    url: reqwest::Url,
+    callback: F,
+) -> Result<Vec<u8>, PatcherError>
+where
+    F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
+{
+    let paths = CachePaths::new(worker, &url);
+    let cached = paths.as_ref().and_then(CachePaths::load);
+
+    let attempt = || fetch(worker, &url, paths.as_ref(), cached.as_ref(), &callback);
+    worker.runtime.block_on(with_retries(worker, attempt))
+}
+
+/// Makes one attempt at downloading the file, or finding out that the
+/// cached copy is still current
+async fn fetch<F>(
+    worker: &PatchWorker,
+    url: &reqwest::Url,
+    paths: Option<&CachePaths>,
+    cached: Option<&(Validators, Vec<u8>)>,
+    callback: F,
+) -> Result<Vec<u8>, PatcherError>
+where
+    F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
+{
+    let mut request = worker
+        .client
+        .get(url.clone())
+        .header(ACCEPT_ENCODING, SUPPORTED_ENCODINGS);
+
+    if let Some((validators, _)) = cached {
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let (response, _connection) = send(worker, request).await?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some((_, body)) = cached {
+            info!("{url} is unchanged, using cached copy");
+            return Ok(body.clone());
+        }
+    }
+
+    let response = response.error_for_status()?;
+    let validators = Validators::from_headers(response.headers());
+    let body = read_body(worker, response, callback).await?;
+
+    if let Some(paths) = paths {
+        if let Err(why) = paths.store(&validators, &body) {
+            warn!("Could not cache {url}: {why}");
+        }
+    }
+
+    Ok(body)
+}
+
+/// Where the cached copy of a file and its validators are kept
+struct CachePaths {
+    body: PathBuf,
+    validators: PathBuf,
+}
+
+impl CachePaths {
+    fn new(worker: &PatchWorker, url: &reqwest::Url) -> Option<Self> {
+        // Files with the same path can be on different servers, such as
+        // those of different install profiles, so the whole URL is what
+        // identifies one. The file's name is only kept to make the cache
+        // easier to look through.
+        let file_name = url
+            .path_segments()?
+            .next_back()?
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+            .collect::<String>();
+        if file_name.is_empty() {
+            return None;
+        }
+        let name = format!(
+            "{file_name}-{}",
+            hex::encode(Sha256::digest(url.as_str().as_bytes()))
+        );
+        let dir = worker.self_dir.join(META_CACHE_DIR);
+        Some(Self {
+            body: dir.join(&name),
+            validators: dir.join(format!("{name}.validators.json")),
+        })
+    }
+
+    fn load(&self) -> Option<(Validators, Vec<u8>)> {
+        let validators = std::fs::read(&self.validators).ok()?;
+        let validators = serde_json::from_slice::<Validators>(&validators).ok()?;
+        let body = std::fs::read(&self.body).ok()?;
+        Some((validators, body))
+    }
+
+    fn store(&self, validators: &Validators, body: &[u8]) -> Result<(), Box<dyn Error>> {
+        // Without validators there is no way to ask if the file has changed
+        if validators.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(dir) = self.body.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.body, body)?;
+        std::fs::write(&self.validators, serde_json::to_vec(validators)?)?;
+        Ok(())
+    }
+}
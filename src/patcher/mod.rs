@@ -1,9 +1,58 @@
 mod worker;
-pub use worker::PatchWorker;
+pub use instance::InstanceLock;
+pub use self_update::{mark_healthy, restart};
 pub use worker::RunState;
+pub use worker::{is_portable, launcher_dir, PatchWorker};
 
+mod backup;
+mod bundle;
+mod cache;
+mod certificate;
+mod changelog;
 mod check_patches;
+mod client;
+mod connection_test;
 mod constants;
+mod crash;
+mod crash_upload;
 mod download;
+mod elevation;
 mod error;
+mod fault;
+mod file_mode;
+mod fragmentation;
+mod game_version;
+mod hash_cache;
+mod heartbeat;
+mod hook;
+mod ignore;
+mod instance;
+mod integrity;
+mod journal;
+mod last_patch;
+mod manifest_diff;
+mod metacache;
+mod news;
+mod preflight;
+mod priority;
+mod prune;
+mod realm;
+mod relocate;
+mod report;
+mod retry;
+mod running;
+mod segmented;
+mod self_update;
+mod session;
+mod shortcut;
+mod signature;
+mod speed;
+mod spool;
+mod staging;
+mod telemetry;
+mod throttle;
+mod torrent;
+mod uninstall;
+mod unzip;
 mod utils;
+mod zip_size;
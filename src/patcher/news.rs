@@ -0,0 +1,30 @@
+use std::error::Error;
+
+use super::constants::{META_DIR, NEWS};
+use super::metacache;
+use super::signature;
+use super::PatchWorker;
+use crate::message::NewsItem;
+
+/// How many news items to show at most
+const MAX_NEWS_ITEMS: usize = 10;
+
+/// Gets the news published next to the server status, which is a JSON list
+/// of news items, newest first. Servers which don't publish news have none,
+/// which isn't an error.
+pub fn fetch(worker: &PatchWorker) -> Result<Vec<NewsItem>, Box<dyn Error>> {
+    let url = worker.server_url.join(META_DIR)?.join(NEWS)?;
+
+    let json_bytes = match metacache::conditional_file(worker, url.clone(), |_, _| {}) {
+        Ok(json_bytes) => json_bytes,
+        Err(why) if why.is_not_found() => return Ok(Vec::new()),
+        Err(why) => return Err(why.into()),
+    };
+
+    // The news links to websites, so it needs to come from us too
+    signature::verify(worker, &url, &json_bytes)?;
+
+    let mut news = serde_json::from_slice::<Vec<NewsItem>>(&json_bytes)?;
+    news.truncate(MAX_NEWS_ITEMS);
+    Ok(news)
+}
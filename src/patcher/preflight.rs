@@ -0,0 +1,91 @@
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+use aeco_patch_config::fsobject::{Directory, FSObject};
+use tracing::warn;
+
+use super::error::PatcherError;
+use super::utils::{installed_dirs, subdir_by_name};
+use super::PatchWorker;
+
+/// Makes sure everything patching may need to change can be changed before
+/// anything is, so that a patch doesn't fail half way through. Directories
+/// need to allow creating and renaming files, and archives, which are
+/// changed in place, need to be writable and not in use by the game.
+pub fn validate(worker: &PatchWorker, manifest: &Directory) -> Result<(), PatcherError> {
+    let mut dirs = vec![worker.self_dir.clone()];
+    let mut archives = Vec::new();
+    let patch = &worker.config.patch;
+    for dir_name in installed_dirs(&patch.components, &patch.languages) {
+        if let Some(platform_dir) = subdir_by_name(manifest, &dir_name) {
+            collect_targets(platform_dir, &worker.self_dir, &mut dirs, &mut archives);
+        }
+    }
+    dirs.sort();
+    dirs.dedup();
+
+    let mut problems = Vec::new();
+
+    // Directories which don't exist yet will be created inside ones which
+    // are checked here
+    for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+        if let Err(why) = check_dir(dir) {
+            problems.push((dir.clone(), why.to_string()));
+        }
+    }
+
+    for path in archives.iter().filter(|path| path.exists()) {
+        if let Err(why) = check_archive_file(path) {
+            problems.push((path.clone(), why.to_string()));
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    for (path, why) in &problems {
+        warn!("Can't change {path:?}: {why}");
+    }
+    Err(PatcherError::UnwritablePaths(problems))
+}
+
+/// Creates a file in a directory and renames it, like patching does
+pub fn check_dir(dir: &Path) -> std::io::Result<()> {
+    let file = tempfile::NamedTempFile::new_in(dir)?;
+    let renamed = tempfile::Builder::new().tempfile_in(dir)?.into_temp_path();
+    file.persist(&renamed)?;
+    renamed.close()
+}
+
+fn check_archive_file(path: &Path) -> std::io::Result<()> {
+    if std::fs::metadata(path)?.permissions().readonly() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "The file is read-only",
+        ));
+    }
+    // Windows refuses to open a file for writing while the game has it open
+    OpenOptions::new().write(true).open(path)?;
+    Ok(())
+}
+
+fn collect_targets(
+    dir: &Directory,
+    disk_dir: &Path,
+    dirs: &mut Vec<PathBuf>,
+    archives: &mut Vec<PathBuf>,
+) {
+    dirs.push(disk_dir.to_owned());
+    for child in &dir.children {
+        match child {
+            FSObject::File(_) => {}
+            FSObject::Directory(d) => collect_targets(d, &disk_dir.join(&d.name), dirs, archives),
+            FSObject::Archive(a) => {
+                let archive_path = disk_dir.join(&a.name);
+                archives.push(archive_path.with_extension("hed"));
+                archives.push(archive_path.with_extension("dat"));
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::config::Priority;
+use std::error::Error;
+use std::process::Command;
+
+/// Gives a running process the configured priority, and limits it to the
+/// configured CPU cores. An empty list of cores allows all of them.
+pub fn apply(pid: u32, priority: Priority, cpu_affinity: &[usize]) -> Result<(), Box<dyn Error>> {
+    if priority == Priority::Normal && cpu_affinity.is_empty() {
+        return Ok(());
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // Don't flash a console window
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        let priority_class = match priority {
+            Priority::Idle => "Idle",
+            Priority::BelowNormal => "BelowNormal",
+            Priority::Normal => "",
+            Priority::AboveNormal => "AboveNormal",
+            Priority::High => "High",
+        };
+        // Processes can only be limited to the first 64 cores this way
+        let mask = cpu_affinity
+            .iter()
+            .filter(|core| **core < 64)
+            .fold(0u64, |mask, core| mask | 1 << core);
+        let affinity = if mask == 0 {
+            String::new()
+        } else {
+            mask.to_string()
+        };
+        let script = "$process = Get-Process -Id $env:AECO_PID; \
+            if ($env:AECO_PRIORITY) { $process.PriorityClass = $env:AECO_PRIORITY }; \
+            if ($env:AECO_AFFINITY) { $process.ProcessorAffinity = [IntPtr][Int64]$env:AECO_AFFINITY }";
+        let status = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", script])
+            .env("AECO_PID", pid.to_string())
+            .env("AECO_PRIORITY", priority_class)
+            .env("AECO_AFFINITY", affinity)
+            .creation_flags(CREATE_NO_WINDOW)
+            .status()?;
+        if !status.success() {
+            return Err(format!("PowerShell could not change the process: {status}").into());
+        }
+        Ok(())
+    }
+    #[cfg(unix)]
+    {
+        let pid = pid.to_string();
+        // Only the superuser can raise a process's priority
+        let niceness = match priority {
+            Priority::Idle => Some("19"),
+            Priority::BelowNormal => Some("10"),
+            Priority::Normal => None,
+            Priority::AboveNormal => Some("-5"),
+            Priority::High => Some("-10"),
+        };
+        if let Some(niceness) = niceness {
+            run(Command::new("renice").args(["-n", niceness, "-p", &pid]))?;
+        }
+        if !cpu_affinity.is_empty() {
+            set_affinity(&pid, cpu_affinity)?;
+        }
+        Ok(())
+    }
+}
+
+/// Limits every thread of a process to some CPU cores
+#[cfg(target_os = "linux")]
+fn set_affinity(pid: &str, cpu_affinity: &[usize]) -> Result<(), Box<dyn Error>> {
+    let cores = cpu_affinity
+        .iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    run(Command::new("taskset").args(["--all-tasks", "--cpu-list", "--pid", &cores, pid]))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_affinity(_pid: &str, _cpu_affinity: &[usize]) -> Result<(), Box<dyn Error>> {
+    Err("CPU affinity can't be set on this system".into())
+}
+
+#[cfg(unix)]
+fn run(command: &mut Command) -> Result<(), Box<dyn Error>> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "{:?} failed: {}",
+            command.get_program(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use aeco_patch_config::fsobject::{Directory, FSObject};
+use tracing::{info, warn};
+
+use super::constants::REMOVED_DIR;
+use super::last_patch::LastPatch;
+use super::utils::{installed_dirs, is_below, subdir_by_name};
+use super::PatchWorker;
+use crate::config::RemovedFiles;
+
+/// Removes files which were part of the game when it was last patched, but
+/// are no longer in the patch list. Returns how many files were removed.
+///
+/// Only files from the old patch list are considered, so anything the player
+/// added themselves is never touched.
+pub fn removed_files(
+    worker: &mut PatchWorker,
+    last_patch: &LastPatch,
+    new_manifest: &Directory,
+) -> Result<usize, Box<dyn Error>> {
+    let mode = worker.config.patch.removed_files;
+    if mode == RemovedFiles::Keep {
+        return Ok(0);
+    }
+
+    let current = game_files(
+        new_manifest,
+        &worker.config.patch.components,
+        &worker.config.patch.languages,
+    );
+    let previous = game_files(
+        &last_patch.manifest,
+        &last_patch.components,
+        &last_patch.languages,
+    );
+    let mut removed = 0;
+
+    for path in previous.difference(&current) {
+        // The names come from the patch server, so don't let them point
+        // outside the game directory
+        if !is_below(path) {
+            warn!("Not removing {path:?}, which is outside the game directory");
+            continue;
+        }
+
+        let disk_path = worker.self_dir.join(path);
+        if !disk_path.is_file()
+            || worker.is_launcher(&disk_path)
+            || worker.ignore_list.matches(path)
+        {
+            continue;
+        }
+
+        // Rolling back brings the file back as well
+        worker.backup.save(&worker.self_dir, &disk_path)?;
+
+        match mode {
+            RemovedFiles::Quarantine => {
+                let quarantine_path = worker.self_dir.join(REMOVED_DIR).join(path);
+                info!("Moving obsolete file {disk_path:?} -> {quarantine_path:?}");
+                if let Some(parent) = quarantine_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::rename(&disk_path, &quarantine_path)?;
+            }
+            RemovedFiles::Delete => {
+                info!("Deleting obsolete file {disk_path:?}");
+                std::fs::remove_file(&disk_path)?;
+            }
+            RemovedFiles::Keep => unreachable!(),
+        }
+
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Gets the paths of all files a patch list installs for this platform and
+/// the given components and language packs, relative to the game directory
+pub fn game_files(
+    manifest: &Directory,
+    components: &[String],
+    languages: &[String],
+) -> HashSet<PathBuf> {
+    let mut paths = HashSet::new();
+    for dir_name in installed_dirs(components, languages) {
+        if let Some(platform_dir) = subdir_by_name(manifest, &dir_name) {
+            collect_paths(platform_dir, Path::new(""), &mut paths);
+        }
+    }
+    paths
+}
+
+fn collect_paths(dir: &Directory, prefix: &Path, paths: &mut HashSet<PathBuf>) {
+    for child in &dir.children {
+        match child {
+            FSObject::File(file) => {
+                paths.insert(prefix.join(&file.name));
+            }
+            FSObject::Directory(d) => collect_paths(d, &prefix.join(&d.name), paths),
+            // Archives are stored on disk as a pair of files
+            FSObject::Archive(a) => {
+                let archive_path = prefix.join(&a.name);
+                paths.insert(archive_path.with_extension("hed"));
+                paths.insert(archive_path.with_extension("dat"));
+            }
+        }
+    }
+}
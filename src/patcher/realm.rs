@@ -0,0 +1,75 @@
+use crate::config::Realm;
+use crate::message::{PatchMessage, RealmInfo};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+/// How long a realm has to answer before it counts as unreachable
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Measures the ping to every realm at once, on another thread so patching
+/// isn't held up, and sends the realms to the GUI before and after
+pub fn measure(realms: &[Realm], selected: Option<String>, tx: Sender<PatchMessage>) {
+    let names = realms
+        .iter()
+        .map(|realm| realm.name.clone())
+        .collect::<Vec<_>>();
+    let addresses = realms
+        .iter()
+        .map(|realm| realm.address.clone())
+        .collect::<Vec<_>>();
+    tx.send(PatchMessage::Realms {
+        realms: realm_infos(&names, vec![None; names.len()]),
+        selected: selected.clone(),
+        measuring: true,
+    })
+    .ok();
+
+    std::thread::spawn(move || {
+        let pings = std::thread::scope(|scope| {
+            let handles = addresses
+                .iter()
+                .map(|address| scope.spawn(|| ping(address)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_default())
+                .collect()
+        });
+        // The GUI may have closed in the meantime
+        tx.send(PatchMessage::Realms {
+            realms: realm_infos(&names, pings),
+            selected,
+            measuring: false,
+        })
+        .ok();
+    });
+}
+
+fn realm_infos(names: &[String], pings: Vec<Option<Duration>>) -> Vec<RealmInfo> {
+    names
+        .iter()
+        .zip(pings)
+        .map(|(name, ping)| RealmInfo {
+            name: name.clone(),
+            ping,
+        })
+        .collect()
+}
+
+/// How long it takes to open a connection to an address, or `None` if it
+/// can't be reached
+fn ping(address: &str) -> Option<Duration> {
+    let address = match address.to_socket_addrs() {
+        Ok(mut addresses) => addresses.next()?,
+        Err(why) => {
+            warn!("Could not look up realm {address}: {why}");
+            return None;
+        }
+    };
+    let started = Instant::now();
+    TcpStream::connect_timeout(&address, PING_TIMEOUT).ok()?;
+    Some(started.elapsed())
+}
@@ -0,0 +1,96 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use tracing::info;
+
+use super::constants::*;
+use super::instance::InstanceLock;
+use super::integrity::load_base_files;
+use super::last_patch;
+use super::prune::game_files;
+use super::uninstall::LAUNCHER_STATE;
+use super::utils::remove_empty_parents;
+use super::PatchWorker;
+
+/// Files the player may have changed, which go along with the game
+const PLAYER_FILES: &[&str] = &[GAME_INI, PATCH_IGNORE, QUARANTINE_DIR];
+
+/// Moves the game to another directory. Returns the new game directory.
+///
+/// Only what the launcher installed is moved, along with the launcher's
+/// state and the player's settings. The game should be verified afterwards
+/// to fix anything which didn't make it.
+pub fn move_game(worker: &PatchWorker, target: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let target = worker.launcher_dir.join(target);
+    if target.starts_with(&worker.self_dir) || worker.self_dir.starts_with(&target) {
+        return Err(format!(
+            "Can't move the game from {:?} to {target:?}",
+            worker.self_dir
+        )
+        .into());
+    }
+    if target.join(GAME_EXE).exists() {
+        return Err(format!("There is already a game in {target:?}").into());
+    }
+    std::fs::create_dir_all(&target)?;
+    // Another launcher could be using either directory
+    let from_lock = worker.lock_game_dir()?;
+    let _to_lock = InstanceLock::for_game(&worker.launcher_dir, &target)?;
+
+    let mut paths = load_base_files(&worker.self_dir);
+    if let Some(last_patch) = last_patch::load(worker) {
+        paths.extend(game_files(
+            &last_patch.manifest,
+            &last_patch.components,
+            &last_patch.languages,
+        ));
+    }
+    paths.extend(
+        LAUNCHER_STATE
+            .iter()
+            .chain(PLAYER_FILES)
+            .map(|name| PathBuf::from(name.trim_end_matches('/'))),
+    );
+
+    for relative in &paths {
+        let from = worker.self_dir.join(relative);
+        if worker.is_launcher(&from) || !from.exists() {
+            continue;
+        }
+        let to = target.join(relative);
+        info!("Moving {from:?} -> {to:?}");
+        move_path(&from, &to)?;
+        remove_empty_parents(&from, &worker.self_dir);
+    }
+
+    // Nothing is left to lock in the old directory
+    if let Some(from_lock) = from_lock {
+        drop(from_lock);
+        let _ = std::fs::remove_file(worker.self_dir.join(LOCK_FILE));
+    }
+
+    Ok(target)
+}
+
+/// Moves a file or directory, copying it if it can't be renamed, such as
+/// when moving to another drive
+fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            move_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        std::fs::remove_dir(from)
+    } else {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)
+    }
+}
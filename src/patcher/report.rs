@@ -0,0 +1,32 @@
+use std::error::Error;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::constants::PATCH_REPORT;
+use super::PatchWorker;
+use crate::message::PatchReport;
+
+/// Fills in the rest of the report of a patch which started at `started`,
+/// and saves it to `patch-report.json` for support tickets
+pub fn finish(
+    worker: &PatchWorker,
+    report: &mut PatchReport,
+    started: Instant,
+    version: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    report.finished_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    report.duration_secs = started.elapsed().as_secs();
+    report.channel = worker.config.patch.channel.clone();
+    report.version = version;
+    report.skipped_files = worker
+        .ignore_list
+        .skipped
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+
+    std::fs::write(
+        worker.self_dir.join(PATCH_REPORT),
+        serde_json::to_vec_pretty(report)?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+use tracing::warn;
+
+use super::error::PatcherError;
+use super::PatchWorker;
+
+/// Controls how failed network requests are retried
+pub struct RetryPolicy {
+    /// The total number of attempts to make, including the first one
+    pub attempts: u32,
+    /// How long to wait before the first retry. Each further retry waits
+    /// twice as long as the one before it.
+    pub base_delay: Duration,
+    /// The longest a single wait is allowed to be, before jitter
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(15),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Gets how long to wait after the given (1-based) failed attempt.
+    /// Up to 25% random jitter is added so many launchers which failed at the
+    /// same time don't all hit the server again at the same time.
+    pub fn delay(&self, failed_attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failed_attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0.0..=0.25);
+        delay.mul_f64(1. + jitter)
+    }
+}
+
+/// Runs a network operation, running it again if it fails in a way that is
+/// likely to be temporary, such as a timeout or a 5xx response. The waits
+/// between attempts are on the runtime, so nothing else is held up by them.
+pub async fn with_retries<T, F, Fut>(
+    worker: &PatchWorker,
+    mut operation: F,
+) -> Result<T, PatcherError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, PatcherError>>,
+{
+    let policy = &worker.retry_policy;
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(result) => return Ok(result),
+            Err(why) if attempt < policy.attempts && why.is_transient() => {
+                let delay = policy.delay(attempt);
+                warn!("Request failed, retrying in {delay:?}: {why}");
+                attempt += 1;
+                worker.send_info(tr!(
+                    "Retrying ({attempt}/{attempts})...",
+                    attempt = attempt,
+                    attempts = policy.attempts
+                ));
+                tokio::time::sleep(delay).await;
+            }
+            Err(why) => return Err(why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Checks a delay is the expected one plus at most 25% jitter
+    fn assert_around(delay: Duration, expected: Duration) {
+        assert!(delay >= expected, "{delay:?} < {expected:?}");
+        assert!(delay <= expected.mul_f64(1.25), "{delay:?} > {expected:?}");
+    }
+
+    #[test]
+    fn delay_doubles_each_attempt() {
+        let policy = policy();
+        assert_around(policy.delay(1), Duration::from_millis(100));
+        assert_around(policy.delay(2), Duration::from_millis(200));
+        assert_around(policy.delay(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn delay_stops_at_the_maximum() {
+        let policy = policy();
+        assert_around(policy.delay(5), Duration::from_secs(1));
+        assert_around(policy.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn delay_treats_attempt_zero_as_the_first() {
+        assert_around(policy().delay(0), Duration::from_millis(100));
+    }
+}
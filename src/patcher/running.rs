@@ -0,0 +1,130 @@
+use std::path::Path;
+#[cfg(not(target_os = "linux"))]
+use std::process::Command;
+
+use tracing::warn;
+
+/// Whether the game is running from this path. Processes whose path can't
+/// be found out count if they have the game's file name.
+pub fn is_running(game_exe: &Path) -> bool {
+    let file_name = match game_exe.file_name() {
+        Some(file_name) => file_name.to_string_lossy(),
+        None => return false,
+    };
+    processes_named(&file_name).iter().any(|path| match path {
+        Some(path) => same_file(Path::new(path), game_exe),
+        None => true,
+    })
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// The paths of the processes with a file name, or `None` for those whose
+/// path isn't known
+#[cfg(windows)]
+fn processes_named(file_name: &str) -> Vec<Option<String>> {
+    use std::os::windows::process::CommandExt;
+    // Don't flash a console window
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let name = Path::new(file_name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // Processes of other users have no path, which comes out as an empty
+    // line
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            r#"Get-Process -Name $env:AECO_PROCESS_NAME -ErrorAction SilentlyContinue | ForEach-Object { "$($_.Path)" }"#,
+        ])
+        .env("AECO_PROCESS_NAME", name)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output();
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| Some(line.trim().to_string()).filter(|path| !path.is_empty()))
+            .collect(),
+        Err(why) => {
+            warn!("Could not look for running processes: {why}");
+            Vec::new()
+        }
+    }
+}
+
+/// The paths of the processes with a file name, or `None` for those whose
+/// path isn't known. Wine runs the game as an argument of its own process,
+/// so every argument is looked at.
+#[cfg(target_os = "linux")]
+fn processes_named(file_name: &str) -> Vec<Option<String>> {
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(why) => {
+            warn!("Could not look for running processes: {why}");
+            return Vec::new();
+        }
+    };
+    let mut found = Vec::new();
+    for entry in entries.flatten() {
+        // Only processes have a command line
+        let cmdline = match std::fs::read(entry.path().join("cmdline")) {
+            Ok(cmdline) => cmdline,
+            Err(_) => continue,
+        };
+        let arg = cmdline
+            .split(|byte| *byte == 0)
+            .map(String::from_utf8_lossy)
+            .find(|arg| file_name_of(arg).eq_ignore_ascii_case(file_name));
+        if let Some(arg) = arg {
+            found.push(unix_path(&arg));
+        }
+    }
+    found
+}
+
+/// The paths of the processes with a file name. `ps` can't tell where one
+/// argument ends and the next begins, so no paths are known.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn processes_named(file_name: &str) -> Vec<Option<String>> {
+    let file_name = file_name.to_lowercase();
+    match Command::new("ps")
+        .args(["-axww", "-o", "command="])
+        .output()
+    {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.to_lowercase().contains(&file_name))
+            .map(|_| None)
+            .collect(),
+        Err(why) => {
+            warn!("Could not look for running processes: {why}");
+            Vec::new()
+        }
+    }
+}
+
+/// The last part of a Unix or Windows path
+#[cfg(target_os = "linux")]
+fn file_name_of(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// Turns a path from a command line into a Unix path, if it can be. Wine
+/// maps the Z: drive to the root directory.
+#[cfg(target_os = "linux")]
+fn unix_path(path: &str) -> Option<String> {
+    if path.starts_with('/') {
+        return Some(path.to_string());
+    }
+    let rest = path
+        .strip_prefix("Z:\\")
+        .or_else(|| path.strip_prefix("z:\\"))?;
+    Some(format!("/{}", rest.replace('\\', "/")))
+}
@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, RANGE};
+use reqwest::StatusCode;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Semaphore;
+
+use super::download::{next_chunk, send};
+use super::error::PatcherError;
+use super::fault::Faults;
+use super::throttle::Throttle;
+use super::PatchWorker;
+
+/// Downloads smaller than this are not worth splitting up
+const MIN_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+
+type SegmentResult = Result<(u64, Bytes), PatcherError>;
+
+/// What the segments share with the worker, since they can't borrow it
+#[derive(Clone)]
+struct Shared {
+    client: reqwest::Client,
+    connections: Arc<Semaphore>,
+    throttle: Arc<Throttle>,
+    faults: Arc<Faults>,
+    read_timeout: Option<Duration>,
+}
+
+/// Asks the server how large a file is and whether it can be downloaded in
+/// pieces. Returns the size of the file if it can be.
+pub async fn probe(worker: &PatchWorker, url: &reqwest::Url) -> Result<Option<u64>, PatcherError> {
+    let (response, _connection) = send(worker, worker.client.head(url.clone())).await?;
+
+    // Not every server answers HEAD requests, but a normal download will
+    // still work
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+
+    let accepts_ranges = response
+        .headers()
+        .get(ACCEPT_RANGES)
+        .is_some_and(|value| value.as_bytes() == b"bytes");
+
+    // reqwest reports a length of 0 for HEAD responses, so the header needs
+    // to be read directly
+    let size = response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    match size {
+        Some(size) if accepts_ranges && size >= MIN_SEGMENT_SIZE * 2 => Ok(Some(size)),
+        _ => Ok(None),
+    }
+}
+
+/// Downloads a file into `file` using several simultaneous range requests.
+///
+/// The requests run on the worker's runtime and send what they receive back
+/// to this download, which does all of the writing.
+pub async fn download<F>(
+    worker: &PatchWorker,
+    url: &reqwest::Url,
+    file: &mut File,
+    total_size: u64,
+    segments: u64,
+    callback: F,
+) -> Result<(), PatcherError>
+where
+    F: Fn(u64, Option<u64>), /* downloaded bytes, total bytes */
+{
+    file.set_len(total_size).map_err(PatcherError::Disk)?;
+
+    let segments = segments.clamp(1, total_size / MIN_SEGMENT_SIZE);
+    let segment_size = total_size.div_ceil(segments);
+
+    let shared = Shared {
+        client: worker.client.clone(),
+        connections: worker.connections.clone(),
+        throttle: worker.throttle.clone(),
+        faults: worker.faults.clone(),
+        read_timeout: worker.config.network.read_timeout(),
+    };
+    let (tx, mut rx) = unbounded_channel::<SegmentResult>();
+    let tasks = (0..segments)
+        .map(|segment| {
+            let start = segment * segment_size;
+            let end = (start + segment_size).min(total_size) - 1;
+            worker.runtime.spawn(fetch_segment(
+                shared.clone(),
+                url.clone(),
+                start..=end,
+                tx.clone(),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    // The channel closes once every segment has finished, which can only
+    // happen if this download isn't holding a sender too
+    drop(tx);
+
+    let mut downloaded_size = 0u64;
+    let mut result = Ok(());
+    while let Some(received) = rx.recv().await {
+        result = received.and_then(|(offset, bytes)| {
+            file.seek(SeekFrom::Start(offset))
+                .and_then(|_| file.write_all(&bytes))
+                .map_err(PatcherError::Disk)?;
+
+            downloaded_size += bytes.len() as u64;
+            callback(downloaded_size, Some(total_size));
+            Ok(())
+        });
+        if result.is_err() {
+            break;
+        }
+    }
+
+    if result.is_err() {
+        // Don't leave the other segments downloading in the background
+        for task in &tasks {
+            task.abort();
+        }
+    } else if downloaded_size != total_size {
+        return Err(PatcherError::Incomplete {
+            received: downloaded_size,
+            expected: total_size,
+        });
+    }
+
+    result
+}
+
+/// Downloads the bytes in `range` of a file
+async fn fetch_segment(
+    shared: Shared,
+    url: reqwest::Url,
+    range: RangeInclusive<u64>,
+    tx: UnboundedSender<SegmentResult>,
+) {
+    let Shared {
+        client,
+        connections,
+        throttle,
+        faults,
+        read_timeout,
+    } = shared;
+    let (start, end) = range.into_inner();
+    let result: Result<(), PatcherError> = async {
+        let _permit = connections.acquire_owned().await?;
+        let response = client
+            .get(url)
+            .header(RANGE, format!("bytes={start}-{end}"))
+            .send()
+            .await?;
+        faults.response()?;
+        let response = response.error_for_status()?;
+
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(PatcherError::Unexpected(format!(
+                "Expected a partial response, got {}",
+                response.status()
+            )));
+        }
+
+        let mut offset = start;
+        let mut stream = response.bytes_stream();
+        while let Some(bytes) = next_chunk(&mut stream, read_timeout, &throttle, &faults).await? {
+            let length = bytes.len() as u64;
+            if tx.send(Ok((offset, bytes))).is_err() {
+                // Nobody is listening anymore
+                return Ok(());
+            }
+            offset += length;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(why) = result {
+        tx.send(Err(why)).ok();
+    }
+}
@@ -0,0 +1,519 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use aeco_patch_config::fsobject::File as PatchFile;
+use serde::{Deserialize, Serialize};
+use subprocess::{Popen, PopenConfig};
+use tracing::{info, warn};
+
+use super::constants::{LAUNCHER_RELEASE, META_DIR};
+use super::download;
+use super::error::PatcherError;
+use super::game_version;
+use super::signature;
+use super::staging;
+use super::utils::{get_platform, set_executable};
+use super::worker::{start_detached_process, UpdatedLauncher};
+use super::PatchWorker;
+use crate::version::pkg_version;
+
+/// How long an updated launcher has to start before the old one is put back
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Tells an updated launcher where to mark that it started properly
+const HEALTHY_MARKER_ENV: &str = "AECO_UPDATE_MARKER";
+/// Deltas can refer back this far into the running launcher, as a power of
+/// two. This is zstd's largest window.
+const DELTA_WINDOW_LOG: u32 = 31;
+
+/// Where the launcher which was replaced by an update is kept
+pub fn old_path(launcher: &Path) -> PathBuf {
+    let mut file_name = launcher.file_name().unwrap_or_default().to_owned();
+    file_name.push(".old");
+    launcher.with_file_name(file_name)
+}
+
+/// How many times to try renaming or removing a launcher, which may belong
+/// to a process that hasn't quite closed yet
+const FILE_ATTEMPTS: u32 = 5;
+const FILE_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Puts an update in place of the launcher, keeping the launcher it replaces
+/// next to it. Files are renamed rather than copied over, since Windows lets
+/// a running program be renamed but not overwritten.
+pub fn swap_in(update: &Path, launcher: &Path) -> Result<(), Box<dyn Error>> {
+    let old = old_path(launcher);
+    remove_leftover(&old)?;
+    if launcher.exists() {
+        with_file_retries(|| std::fs::rename(launcher, &old))?;
+    }
+    if let Err(why) = with_file_retries(|| std::fs::rename(update, launcher)) {
+        // There needs to be a launcher to start next time
+        if let Err(why) = std::fs::rename(&old, launcher) {
+            warn!("Could not put the old launcher back: {why}");
+        }
+        return Err(why.into());
+    }
+    Ok(())
+}
+
+/// Removes a launcher which is no longer needed. On Windows, one which is
+/// still running is moved out of the way instead, and removed by this the
+/// next time the launcher starts.
+pub fn remove_leftover(path: &Path) -> Result<(), Box<dyn Error>> {
+    for moved in moved_leftovers(path) {
+        if let Err(why) = std::fs::remove_file(&moved) {
+            info!("Could not remove {moved:?} yet: {why}");
+        }
+    }
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let why = match with_file_retries(|| std::fs::remove_file(path)) {
+        Ok(()) => return Ok(()),
+        Err(why) => why,
+    };
+    if !cfg!(windows) {
+        return Err(why.into());
+    }
+
+    // Its name may be needed again before then
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(format!(".{}.delete", std::process::id()));
+    std::fs::rename(path, path.with_file_name(file_name))?;
+    Ok(())
+}
+
+/// Copies of a launcher which were moved out of the way by `remove_leftover`
+fn moved_leftovers(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Vec::new();
+    };
+    let name = name.to_string_lossy();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|moved| {
+            let moved_name = moved.file_name().unwrap_or_default().to_string_lossy();
+            moved_name
+                .strip_prefix(name.as_ref())
+                .and_then(|rest| rest.strip_prefix('.'))
+                .and_then(|rest| rest.strip_suffix(".delete"))
+                .is_some_and(|pid| pid.parse::<u32>().is_ok())
+        })
+        .collect()
+}
+
+fn with_file_retries<F>(mut operation: F) -> std::io::Result<()>
+where
+    F: FnMut() -> std::io::Result<()>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Err(_) if attempt < FILE_ATTEMPTS => {
+                attempt += 1;
+                std::thread::sleep(FILE_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The command line to start another launcher with in place of this one.
+/// It gets the same arguments, so things like `--portable` and `--server`
+/// still apply after an update.
+pub fn restart_command(launcher: &Path) -> Vec<OsString> {
+    std::iter::once(launcher.as_os_str().to_owned())
+        .chain(std::env::args_os().skip(1))
+        .collect()
+}
+
+/// Where an updated launcher marks that it started properly
+fn healthy_marker(launcher: &Path) -> PathBuf {
+    let mut file_name = launcher.file_name().unwrap_or_default().to_owned();
+    file_name.push(".healthy");
+    launcher.with_file_name(file_name)
+}
+
+/// Where an update which did not start is remembered, next to the launcher
+pub fn rejected_marker(launcher: &Path) -> PathBuf {
+    let mut file_name = launcher.file_name().unwrap_or_default().to_owned();
+    file_name.push(".rejected");
+    launcher.with_file_name(file_name)
+}
+
+/// An update which was put back because it did not start. It is not
+/// installed again, since it would only be put back again.
+#[derive(Deserialize, Serialize)]
+struct RejectedUpdate {
+    /// Hex encoded, made the same way as the patch list's digests
+    digest: String,
+    /// Whether the player was told about it yet
+    reported: bool,
+}
+
+/// Remembers an update which did not start
+fn reject(launcher: &Path) -> Result<(), Box<dyn Error>> {
+    let name = launcher.file_name().unwrap_or_default().to_string_lossy();
+    let data = std::fs::read(launcher)?;
+    let rejected = RejectedUpdate {
+        digest: hex::encode(PatchFile::new(&name, &data).digest),
+        reported: false,
+    };
+    std::fs::write(rejected_marker(launcher), serde_json::to_vec(&rejected)?)?;
+    Ok(())
+}
+
+/// Whether an update with this digest already failed to start here. The
+/// player is told the first time it is skipped.
+pub fn is_rejected(worker: &PatchWorker, digest: &[u8]) -> bool {
+    let marker = rejected_marker(&worker.self_exe);
+    let mut rejected = match std::fs::read(&marker)
+        .ok()
+        .and_then(|json| serde_json::from_slice::<RejectedUpdate>(&json).ok())
+    {
+        Some(rejected) if rejected.digest.eq_ignore_ascii_case(&hex::encode(digest)) => rejected,
+        _ => return false,
+    };
+    warn!(
+        "Skipping launcher update {}, which did not start",
+        rejected.digest
+    );
+    if !rejected.reported {
+        let why = PatcherError::Other(format!(
+            "Launcher update {} did not start and was undone",
+            rejected.digest
+        ));
+        worker.send_error(
+            tr!("The launcher update did not start, so this launcher is kept until the next one"),
+            &why,
+        );
+        rejected.reported = true;
+        let saved = serde_json::to_vec(&rejected)
+            .map_err(|why| -> Box<dyn Error> { why.into() })
+            .and_then(|json| Ok(std::fs::write(&marker, json)?));
+        if let Err(why) = saved {
+            warn!("Could not remember that the skipped update was reported: {why}");
+        }
+    }
+    true
+}
+
+/// Starts a launcher which was just updated, and waits for it to mark that
+/// it started properly. If it closes, crashes or hangs first, the old launcher is
+/// put back and started instead, so a broken update can't leave the player
+/// without a launcher, and the update is moved back to `update`. Either
+/// launcher is given the arguments this one was.
+pub fn start_and_watch(launcher: &Path, update: &Path) -> Result<(), Box<dyn Error>> {
+    let command_line = restart_command(launcher);
+    let marker = healthy_marker(launcher);
+    // Left over from an earlier update
+    let _ = std::fs::remove_file(&marker);
+
+    let mut env = PopenConfig::current_env();
+    env.retain(|(name, _)| name != HEALTHY_MARKER_ENV);
+    env.push((OsString::from(HEALTHY_MARKER_ENV), marker.clone().into()));
+    let config = PopenConfig {
+        env: Some(env),
+        ..Default::default()
+    };
+    let mut child = Popen::create(&command_line, config)?;
+
+    let started = Instant::now();
+    let healthy = loop {
+        if marker.exists() {
+            break true;
+        }
+        match child.poll() {
+            // Only the marker counts, since an update which closes right
+            // away, even without an error, would leave the player with no
+            // launcher. It may have been written just before closing.
+            Some(_) => break marker.exists(),
+            None if started.elapsed() > STARTUP_TIMEOUT => break false,
+            None => std::thread::sleep(POLL_INTERVAL),
+        }
+    };
+    let _ = std::fs::remove_file(&marker);
+
+    if healthy {
+        child.detach();
+        // A later update can't be the one which failed
+        let _ = std::fs::remove_file(rejected_marker(launcher));
+        return Ok(());
+    }
+
+    warn!("The updated launcher did not start properly, putting the old one back");
+    if child.poll().is_none() {
+        child.kill()?;
+        child.wait()?;
+    }
+    let old = old_path(launcher);
+    if !old.is_file() {
+        return Err("The updated launcher did not start, and the old one was not kept".into());
+    }
+    // Otherwise the next patch would install it again
+    if let Err(why) = reject(launcher) {
+        warn!("Could not remember the update which did not start: {why}");
+    }
+    // This program is the update, so it can only be renamed out of the way
+    std::fs::rename(launcher, update)?;
+    std::fs::rename(&old, launcher)?;
+    start_detached_process(&command_line)?;
+    Ok(())
+}
+
+/// Starts another copy of this launcher with the same arguments, for when
+/// this one has to be given up on. It waits for this one to close before
+/// doing anything, since only one may run at a time.
+pub fn restart() -> Result<(), Box<dyn Error>> {
+    let launcher = std::env::current_exe()?;
+    start_detached_process(&restart_command(&launcher))?;
+    Ok(())
+}
+
+/// Lets the launcher which updated this one know that it started properly.
+/// Launchers which weren't just updated have nobody to tell.
+pub fn mark_healthy() {
+    if let Some(marker) = std::env::var_os(HEALTHY_MARKER_ENV) {
+        if let Err(why) = std::fs::write(&marker, b"") {
+            warn!("Could not mark the launcher update as working: {why}");
+        }
+    }
+}
+
+/// Builds the new launcher from a delta against the running one, which is
+/// much smaller than the whole launcher when little has changed. Returns
+/// `None` if there is no delta for the running launcher or it doesn't give
+/// the launcher in the patch info, in which case the whole thing needs to be
+/// downloaded.
+///
+/// The server publishes deltas next to the launcher, in a directory named
+/// after it with ".delta" appended, as files named after the hex digest of
+/// the launcher they apply to. They are made with `zstd --patch-from`.
+pub fn from_delta(
+    worker: &PatchWorker,
+    net_file: &reqwest::Url,
+    file: &PatchFile,
+) -> Option<Vec<u8>> {
+    match try_delta(worker, net_file, file) {
+        Ok(data) => data,
+        Err(why) => {
+            warn!("Could not update the launcher from a delta: {why}");
+            None
+        }
+    }
+}
+
+fn try_delta(
+    worker: &PatchWorker,
+    net_file: &reqwest::Url,
+    file: &PatchFile,
+) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let current = std::fs::read(&worker.self_exe)?;
+    let current_digest = hex::encode(PatchFile::new(&file.name, &current).digest);
+    let delta_url = reqwest::Url::parse(&format!("{net_file}.delta/{current_digest}"))?;
+    let delta = match download::memory_file(worker, delta_url, |_, _| {}) {
+        Ok(delta) => delta,
+        Err(why) if why.is_not_found() => return Ok(None),
+        Err(why) => return Err(why.into()),
+    };
+
+    let data = apply_delta(&current, &delta)?;
+    if PatchFile::new(&file.name, &data).digest != file.digest {
+        return Err(PatcherError::Verification(
+            "The launcher built from the delta did not match its digest".into(),
+        )
+        .into());
+    }
+    Ok(Some(data))
+}
+
+/// Builds a file from a delta made with `zstd --patch-from` against `base`
+fn apply_delta(base: &[u8], delta: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoder = zstd::stream::read::Decoder::with_dictionary(delta, base)?;
+    decoder.window_log_max(DELTA_WINDOW_LOG)?;
+    let mut data = Vec::new();
+    decoder.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// The newest launcher, which servers can release separately from the game
+/// so that it can update itself even when the game isn't being patched
+#[derive(Deserialize)]
+struct LauncherRelease {
+    version: String,
+    /// By platform, such as "windows-x86_64"
+    files: HashMap<String, ReleaseFile>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseFile {
+    /// Where to download it from, relative to the release
+    url: String,
+    /// Hex encoded, made the same way as the patch list's digests
+    digest: String,
+    /// Hex encoded Ed25519 signature of the launcher
+    signature: String,
+}
+
+/// Downloads the newest launcher if the server releases it separately from
+/// the game and it is newer than this one, setting `updated_patcher`.
+/// Returns whether the server releases it separately; if not, the launcher
+/// is updated along with the game.
+pub fn check_release(worker: &mut PatchWorker) -> Result<bool, Box<dyn Error>> {
+    let url = worker.server_url.join(META_DIR)?.join(LAUNCHER_RELEASE)?;
+    let json_bytes = match download::memory_file(worker, url.clone(), |_, _| {}) {
+        Ok(json_bytes) => json_bytes,
+        Err(why) if why.is_not_found() => return Ok(false),
+        Err(why) => return Err(why.into()),
+    };
+    // Otherwise an older launcher could be passed off as the newest one
+    signature::verify_required(worker, &url, &json_bytes)?;
+    let release = serde_json::from_slice::<LauncherRelease>(&json_bytes)?;
+
+    let current = pkg_version().unwrap_or("0");
+    if game_version::compare(current, &release.version) != Ordering::Less {
+        return Ok(true);
+    }
+    let platform = get_platform();
+    let file = match release.files.get(&platform) {
+        Some(file) => file,
+        None => {
+            info!(
+                "Launcher {} was not released for {platform}",
+                release.version
+            );
+            return Ok(true);
+        }
+    };
+    let digest = hex::decode(&file.digest)?;
+    // Nothing is downloaded while previewing
+    if worker.preview.is_some()
+        || cfg!(feature = "dont_update_self")
+        || is_rejected(worker, &digest)
+    {
+        return Ok(true);
+    }
+
+    let name = worker
+        .self_exe
+        .file_name()
+        .ok_or("Failed to get launcher file name")?
+        .to_string_lossy()
+        .into_owned();
+    let label = tr!(
+        "Updating the launcher to {version}",
+        version = release.version
+    );
+    let data = download::memory_file(
+        worker,
+        url.join(&file.url)?,
+        download::progress_reporter(worker, label),
+    )?;
+    if PatchFile::new(&name, &data).digest != digest {
+        return Err(PatcherError::Verification(format!(
+            "Launcher {} did not match its digest",
+            release.version
+        ))
+        .into());
+    }
+    signature::verify_launcher_signature(&data, &hex::decode(&file.signature)?)?;
+
+    let path = worker.get_self_aecoupdate_path()?;
+    staging::write(worker, &path, &data)?;
+    set_executable(&path)?;
+    worker.updated_patcher = Some(UpdatedLauncher { path, name, digest });
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// Makes a delta the way `zstd --patch-from` does
+    fn make_delta(base: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            zstd::stream::write::Encoder::with_dictionary(Vec::new(), 3, base).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn launcher(version: u8) -> Vec<u8> {
+        (0..64 * 1024u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8 ^ version)
+            .collect()
+    }
+
+    #[test]
+    fn apply_delta_builds_the_new_launcher() {
+        let old = launcher(1);
+        let mut new = old.clone();
+        new[1000..1100].fill(0xff);
+        new.extend_from_slice(b"new feature");
+
+        let delta = make_delta(&old, &new);
+        assert!(delta.len() < new.len() / 10);
+        assert_eq!(apply_delta(&old, &delta).unwrap(), new);
+    }
+
+    #[test]
+    fn apply_delta_against_another_launcher_goes_wrong() {
+        let old = launcher(1);
+        let mut new = old.clone();
+        new[1000..1100].fill(0xff);
+
+        let delta = make_delta(&old, &new);
+        // zstd can't tell, which is why the result's digest is checked
+        let built = apply_delta(&launcher(2), &delta);
+        assert!(built.map_or(true, |built| built != new));
+    }
+
+    #[test]
+    fn apply_delta_refuses_garbage() {
+        assert!(apply_delta(&launcher(1), b"not a zstd frame").is_err());
+    }
+
+    #[test]
+    fn remove_leftover_takes_earlier_moved_copies_along() {
+        let dir = tempfile::tempdir().unwrap();
+        let leftover = dir.path().join("launcher.old");
+        let moved = dir.path().join("launcher.old.1234.delete");
+        let unrelated = dir.path().join("launcher.old.notes.delete");
+        for path in [&leftover, &moved, &unrelated] {
+            std::fs::write(path, b"launcher").unwrap();
+        }
+
+        remove_leftover(&leftover).unwrap();
+        assert!(!leftover.exists());
+        assert!(!moved.exists());
+        assert!(unrelated.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_update_which_closes_without_the_marker_is_put_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let launcher = dir.path().join("launcher");
+        let update = dir.path().join("update");
+        // Closes right away without an error, and without marking itself
+        std::fs::copy("/bin/true", &launcher).unwrap();
+        std::fs::copy("/bin/true", old_path(&launcher)).unwrap();
+
+        start_and_watch(&launcher, &update).unwrap();
+        assert!(update.is_file());
+        assert!(!old_path(&launcher).exists());
+        assert!(rejected_marker(&launcher).is_file());
+    }
+}
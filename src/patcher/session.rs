@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use super::client::build_other_client;
+use super::constants::{CREDENTIALS_FILE, SESSION_FILE};
+use super::PatchWorker;
+use crate::config::AuthConfig;
+
+/// A game account session, which the game is started with
+#[derive(Deserialize, Serialize)]
+pub struct Session {
+    pub token: String,
+    /// When the session runs out, in seconds since the Unix epoch
+    pub expires: Option<u64>,
+}
+
+impl Session {
+    /// Whether the session can still be used. Sessions which are about to
+    /// run out can't, as the game needs some time to start.
+    pub fn is_valid(&self) -> bool {
+        match self.expires {
+            Some(expires) => now() + EXPIRY_MARGIN_SECS < expires,
+            None => true,
+        }
+    }
+}
+
+/// How long a session needs to last at least for the game to be started
+/// with it
+const EXPIRY_MARGIN_SECS: u64 = 60;
+
+#[derive(Serialize)]
+struct LoginRequest<'a> {
+    username: &'a str,
+    password: &'a str,
+}
+
+#[derive(Deserialize)]
+struct LoginResponse {
+    token: String,
+    expires_in: Option<u64>,
+}
+
+/// Logs in to a game account, and returns the new session
+pub fn login(
+    worker: &PatchWorker,
+    url: &str,
+    username: &str,
+    password: &str,
+) -> Result<Session, Box<dyn Error>> {
+    let url = reqwest::Url::parse(url)?;
+    if worker.config.network.https_only && url.scheme() != "https" {
+        return Err(format!("Login server {url} does not use HTTPS").into());
+    }
+    // The patch server's credentials are none of the login server's
+    // business
+    let client = build_other_client(&worker.config.network, &worker.launcher_dir)?;
+    let body = serde_json::to_vec(&LoginRequest { username, password })?;
+    let request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    let response = worker.runtime.block_on(request.send())?;
+    if matches!(
+        response.status(),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    ) {
+        return Err(tr!("Wrong username or password").into());
+    }
+    let data = worker
+        .runtime
+        .block_on(response.error_for_status()?.bytes())?;
+    let response = serde_json::from_slice::<LoginResponse>(&data)?;
+    Ok(Session {
+        token: response.token,
+        expires: response.expires_in.map(|secs| now() + secs),
+    })
+}
+
+/// Loads the saved session, if there is one
+pub fn load(worker: &PatchWorker) -> Option<Session> {
+    let data = std::fs::read(worker.launcher_dir.join(SESSION_FILE)).ok()?;
+    let data = match unprotect(&data) {
+        Ok(data) => data,
+        Err(why) => {
+            warn!("Could not read the saved session: {why}");
+            return None;
+        }
+    };
+    serde_json::from_slice(&data).ok()
+}
+
+/// Saves a session so the player doesn't have to log in every time
+pub fn save(worker: &PatchWorker, session: &Session) -> Result<(), Box<dyn Error>> {
+    let data = protect(&serde_json::to_vec(session)?)?;
+    write_private(&worker.launcher_dir.join(SESSION_FILE), &data)
+}
+
+/// Loads the saved patch server credentials, if there are any
+pub fn load_credentials(launcher_dir: &Path) -> Option<AuthConfig> {
+    let data = std::fs::read(launcher_dir.join(CREDENTIALS_FILE)).ok()?;
+    let data = match unprotect(&data) {
+        Ok(data) => data,
+        Err(why) => {
+            warn!("Could not read the saved credentials: {why}");
+            return None;
+        }
+    };
+    serde_json::from_slice(&data).ok()
+}
+
+/// Saves the patch server credentials so they don't have to be entered
+/// every time
+pub fn save_credentials(launcher_dir: &Path, auth: &AuthConfig) -> Result<(), Box<dyn Error>> {
+    let data = protect(&serde_json::to_vec(auth)?)?;
+    write_private(&launcher_dir.join(CREDENTIALS_FILE), &data)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Encrypts data so only the current Windows user can read it. The file
+/// holds it in base64.
+#[cfg(windows)]
+fn protect(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    use windows_sys::Win32::Security::Cryptography::{CryptProtectData, CRYPTPROTECT_UI_FORBIDDEN};
+
+    let input = dpapi::blob(data)?;
+    let mut output = dpapi::empty_blob();
+    // SAFETY: `input` points into `data`, which outlives the call, and the
+    // call fills in `output` for `dpapi::take` to free
+    let succeeded = unsafe {
+        CryptProtectData(
+            &input,
+            std::ptr::null(),
+            std::ptr::null(),
+            std::ptr::null(),
+            std::ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    Ok(base64::encode(dpapi::take(succeeded, output)?).into_bytes())
+}
+
+#[cfg(windows)]
+fn unprotect(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    use windows_sys::Win32::Security::Cryptography::{
+        CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN,
+    };
+
+    let data = base64::decode(data)?;
+    let input = dpapi::blob(&data)?;
+    let mut output = dpapi::empty_blob();
+    // SAFETY: as in `protect`
+    let succeeded = unsafe {
+        CryptUnprotectData(
+            &input,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+            std::ptr::null(),
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+    };
+    dpapi::take(succeeded, output)
+}
+
+/// Passing data to and from the Windows Data Protection API
+#[cfg(windows)]
+mod dpapi {
+    use std::error::Error;
+
+    use windows_sys::Win32::Foundation::BOOL;
+    use windows_sys::Win32::Security::Cryptography::CRYPT_INTEGER_BLOB;
+    use windows_sys::Win32::System::Memory::LocalFree;
+
+    /// Describes data for the API to read. It is never written through.
+    pub fn blob(data: &[u8]) -> Result<CRYPT_INTEGER_BLOB, Box<dyn Error>> {
+        Ok(CRYPT_INTEGER_BLOB {
+            cbData: data.len().try_into()?,
+            pbData: data.as_ptr().cast_mut(),
+        })
+    }
+
+    /// Somewhere for the API to put what it returns
+    pub fn empty_blob() -> CRYPT_INTEGER_BLOB {
+        CRYPT_INTEGER_BLOB {
+            cbData: 0,
+            pbData: std::ptr::null_mut(),
+        }
+    }
+
+    /// Copies out what a call returned and frees it, or gets why it failed
+    pub fn take(succeeded: BOOL, output: CRYPT_INTEGER_BLOB) -> Result<Vec<u8>, Box<dyn Error>> {
+        if succeeded == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // SAFETY: a successful call leaves `cbData` bytes at `pbData`, which
+        // were allocated with LocalAlloc and are ours to free
+        let data = unsafe {
+            let data = std::slice::from_raw_parts(output.pbData, output.cbData as usize).to_vec();
+            LocalFree(output.pbData as isize);
+            data
+        };
+        Ok(data)
+    }
+}
+
+/// Elsewhere, the file can only be read by its owner instead
+#[cfg(not(windows))]
+fn protect(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(data.to_vec())
+}
+
+#[cfg(not(windows))]
+fn unprotect(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(data.to_vec())
+}
+
+fn write_private(path: &Path, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // The mode above only applies to new files, and one saved by something
+    // else may be readable by others
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(data)?;
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::error::Error;
+use std::path::Path;
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(not(target_os = "macos"))]
+use std::process::Command;
+
+/// What shortcuts to the launcher are called
+const SHORTCUT_NAME: &str = "AECO Launcher";
+
+/// Puts a shortcut to the launcher on the player's desktop. On Linux, the
+/// launcher is added to the applications menu as well.
+pub fn create(launcher: &Path) -> Result<(), Box<dyn Error>> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // Don't flash a console window
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        // The paths are passed through the environment so they don't need
+        // to be quoted for PowerShell
+        let script = "$shell = New-Object -ComObject WScript.Shell; \
+            $desktop = [Environment]::GetFolderPath('Desktop'); \
+            $link = $shell.CreateShortcut((Join-Path $desktop $env:AECO_SHORTCUT_NAME) + '.lnk'); \
+            $link.TargetPath = $env:AECO_SHORTCUT_TARGET; \
+            $link.WorkingDirectory = $env:AECO_SHORTCUT_DIR; \
+            $link.Save()";
+        let status = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", script])
+            .env("AECO_SHORTCUT_NAME", SHORTCUT_NAME)
+            .env("AECO_SHORTCUT_TARGET", launcher)
+            .env("AECO_SHORTCUT_DIR", launcher_dir(launcher)?)
+            .creation_flags(CREATE_NO_WINDOW)
+            .status()?;
+        if !status.success() {
+            return Err(format!("PowerShell could not create the shortcut: {status}").into());
+        }
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::os::unix::fs::symlink(launcher, desktop_dir()?.join(SHORTCUT_NAME))?;
+        Ok(())
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let entry = desktop_entry(launcher)?;
+        let file_name = "aeco-launcher.desktop";
+
+        let applications = data_dir()?.join("applications");
+        std::fs::create_dir_all(&applications)?;
+        std::fs::write(applications.join(file_name), &entry)?;
+
+        // Desktops only run entries which are executable
+        let shortcut = desktop_dir()?.join(file_name);
+        std::fs::write(&shortcut, &entry)?;
+        std::fs::set_permissions(&shortcut, std::fs::Permissions::from_mode(0o755))?;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn launcher_dir(launcher: &Path) -> Result<&Path, Box<dyn Error>> {
+    launcher
+        .parent()
+        .ok_or_else(|| "No parent directory for the launcher was found.".into())
+}
+
+#[cfg(unix)]
+fn home_dir() -> Result<PathBuf, Box<dyn Error>> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| "HOME is not set".into())
+}
+
+/// The player's desktop directory. Linux desktops can name it something
+/// other than "Desktop", such as in the player's language.
+#[cfg(unix)]
+fn desktop_dir() -> Result<PathBuf, Box<dyn Error>> {
+    #[cfg(not(target_os = "macos"))]
+    if let Ok(output) = Command::new("xdg-user-dir").arg("DESKTOP").output() {
+        let dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !dir.is_empty() {
+            std::fs::create_dir_all(&dir)?;
+            return Ok(dir.into());
+        }
+    }
+    let dir = home_dir()?.join("Desktop");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Where applications menu entries go
+#[cfg(all(unix, not(target_os = "macos")))]
+fn data_dir() -> Result<PathBuf, Box<dyn Error>> {
+    match std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Ok(dir.into()),
+        None => Ok(home_dir()?.join(".local/share")),
+    }
+}
+
+/// A freedesktop.org desktop entry which starts the launcher
+#[cfg(all(unix, not(target_os = "macos")))]
+fn desktop_entry(launcher: &Path) -> Result<String, Box<dyn Error>> {
+    let launcher_path = launcher
+        .to_str()
+        .ok_or("The launcher's path is not valid UTF-8")?;
+    // Quoted arguments need these escaped, and the whole value needs its
+    // backslashes escaped again
+    let mut exec = String::new();
+    for c in launcher_path.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            exec.push('\\');
+        }
+        exec.push(c);
+    }
+    let exec = exec.replace('\\', "\\\\");
+    let dir = launcher_dir(launcher)?.to_string_lossy();
+    Ok(format!(
+        "[Desktop Entry]\n\
+        Type=Application\n\
+        Name={SHORTCUT_NAME}\n\
+        Exec=\"{exec}\"\n\
+        Path={dir}\n\
+        Terminal=false\n\
+        Categories=Game;\n"
+    ))
+}
@@ -0,0 +1,140 @@
+use std::error::Error;
+
+use ed25519_dalek::{PublicKey, Signature};
+use tracing::warn;
+
+use super::constants::{LAUNCHER_PUBLIC_KEY, MANIFEST_PUBLIC_KEY, SIGNATURE_EXTENSION};
+use super::download;
+use super::error::PatcherError;
+use super::PatchWorker;
+
+/// Checks that the contents of a file downloaded from `url` were signed with
+/// the key this launcher was built with.
+///
+/// The server publishes the raw 64 byte Ed25519 signature of each signed
+/// file next to it, with ".sig" appended to its name.
+pub fn verify(worker: &PatchWorker, url: &reqwest::Url, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    match MANIFEST_PUBLIC_KEY {
+        Some(public_key) => verify_with(worker, public_key, url, data),
+        None => {
+            warn!("No manifest key was built in, not verifying {url}");
+            Ok(())
+        }
+    }
+}
+
+/// Checks metadata about launcher updates the same way as `verify`, but
+/// refuses it when no manifest key was built in. Otherwise anyone who can
+/// answer for the server could offer any launcher as the newest one.
+pub fn verify_required(
+    worker: &PatchWorker,
+    url: &reqwest::Url,
+    data: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let public_key = MANIFEST_PUBLIC_KEY.ok_or_else(|| {
+        PatcherError::Verification(format!(
+            "No manifest key was built in, so {url} can't be trusted"
+        ))
+    })?;
+    verify_with(worker, public_key, url, data)
+}
+
+/// Checks that an update to the launcher downloaded from `url` was signed,
+/// the same way as `verify`. Unlike other files, the launcher runs whatever
+/// it is replaced with, so updates are refused when there is no key to check
+/// them with.
+pub fn verify_launcher(
+    worker: &PatchWorker,
+    url: &reqwest::Url,
+    data: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    verify_with(worker, launcher_key()?, url, data)
+}
+
+/// Checks a launcher against a signature which came with it, rather than
+/// from next to it on the server
+pub fn verify_launcher_signature(data: &[u8], signature: &[u8]) -> Result<(), Box<dyn Error>> {
+    let public_key = launcher_key()?;
+    check(public_key, data, signature).map_err(|_| {
+        PatcherError::Verification("The launcher's signature does not match".into())
+    })?;
+    Ok(())
+}
+
+fn launcher_key() -> Result<&'static str, Box<dyn Error>> {
+    Ok(LAUNCHER_PUBLIC_KEY
+        .or(MANIFEST_PUBLIC_KEY)
+        .ok_or("No key to verify launcher updates with was built in")?)
+}
+
+fn verify_with(
+    worker: &PatchWorker,
+    public_key: &str,
+    url: &reqwest::Url,
+    data: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let signature_url = reqwest::Url::parse(&format!("{url}{SIGNATURE_EXTENSION}"))?;
+    let signature_bytes = download::memory_file(worker, signature_url, |_, _| {})?;
+
+    check(public_key, data, &signature_bytes).map_err(|_| {
+        PatcherError::Verification(format!("The signature of {url} does not match"))
+    })?;
+
+    Ok(())
+}
+
+/// Checks a raw Ed25519 signature against a hex encoded public key
+fn check(public_key: &str, data: &[u8], signature: &[u8]) -> Result<(), Box<dyn Error>> {
+    let public_key = PublicKey::from_bytes(&hex::decode(public_key.trim())?)?;
+    let signature = Signature::try_from(signature)?;
+    public_key.verify_strict(data, &signature)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{ExpandedSecretKey, SecretKey};
+
+    use super::*;
+
+    /// A hex public key and a signature of `data` made with it
+    fn signed(data: &[u8]) -> (String, Vec<u8>) {
+        let secret = SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        let signature = ExpandedSecretKey::from(&secret).sign(data, &public);
+        (
+            hex::encode(public.as_bytes()),
+            signature.to_bytes().to_vec(),
+        )
+    }
+
+    #[test]
+    fn check_accepts_a_matching_signature() {
+        let (public_key, signature) = signed(b"patchlist");
+        check(&public_key, b"patchlist", &signature).unwrap();
+        // Keys are often pasted with a trailing newline
+        check(&format!("{public_key}\n"), b"patchlist", &signature).unwrap();
+    }
+
+    #[test]
+    fn check_refuses_changed_data() {
+        let (public_key, signature) = signed(b"patchlist");
+        assert!(check(&public_key, b"patchlisT", &signature).is_err());
+    }
+
+    #[test]
+    fn check_refuses_another_key() {
+        let (_, signature) = signed(b"patchlist");
+        let other = PublicKey::from(&SecretKey::from_bytes(&[8; 32]).unwrap());
+        assert!(check(&hex::encode(other.as_bytes()), b"patchlist", &signature).is_err());
+    }
+
+    #[test]
+    fn check_refuses_malformed_signatures_and_keys() {
+        let (public_key, signature) = signed(b"patchlist");
+        assert!(check(&public_key, b"patchlist", &signature[..63]).is_err());
+        assert!(check(&public_key, b"patchlist", &[]).is_err());
+        assert!(check("not hex", b"patchlist", &signature).is_err());
+        assert!(check(&public_key[..62], b"patchlist", &signature).is_err());
+    }
+}
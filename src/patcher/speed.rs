@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::message::TransferRate;
+
+/// How far back to look when averaging the download speed
+const WINDOW: Duration = Duration::from_secs(5);
+
+/// Keeps track of how fast a download is going
+pub struct SpeedTracker {
+    /// When each progress update was received and how many bytes had been
+    /// downloaded at that time, oldest first
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl SpeedTracker {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Records how many bytes have been downloaded so far and returns the
+    /// average speed over the last few seconds
+    pub fn update(&mut self, downloaded: u64, total: Option<u64>) -> TransferRate {
+        let now = Instant::now();
+        self.samples.push_back((now, downloaded));
+
+        // Forget samples which are too old, but always keep one to measure from
+        while self.samples.len() > 2 && now.duration_since(self.samples[0].0) > WINDOW {
+            self.samples.pop_front();
+        }
+
+        let (start_time, start_bytes) = self.samples[0];
+        let elapsed = now.duration_since(start_time).as_secs_f64();
+        let bytes_per_second = if elapsed > 0. {
+            (downloaded.saturating_sub(start_bytes) as f64 / elapsed) as u64
+        } else {
+            0
+        };
+
+        let remaining = match total {
+            Some(total) if bytes_per_second > 0 => Some(Duration::from_secs(
+                total.saturating_sub(downloaded) / bytes_per_second,
+            )),
+            _ => None,
+        };
+
+        TransferRate {
+            bytes_per_second,
+            remaining,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Holds a response body while it downloads. Small bodies are kept in memory,
+/// but once a body grows past a threshold it is moved to a temporary file so
+/// a large download doesn't need to fit in memory twice while it is
+/// decompressed.
+enum SpooledBody {
+    Memory(Vec<u8>),
+    File(File),
+}
+
+pub struct Spool<'a> {
+    body: SpooledBody,
+    /// Where to create the temporary file
+    temp_dir: &'a Path,
+    memory_limit: u64,
+    size: u64,
+}
+
+impl<'a> Spool<'a> {
+    /// Creates a spool for a body of `expected_size` bytes, if known. The
+    /// body starts out on disk if it is known to be too large for memory.
+    pub fn new(
+        temp_dir: &'a Path,
+        memory_limit: u64,
+        expected_size: Option<u64>,
+    ) -> std::io::Result<Self> {
+        let body = match expected_size {
+            Some(size) if size > memory_limit => {
+                SpooledBody::File(tempfile::tempfile_in(temp_dir)?)
+            }
+            // If we know the total size of the download, we can pre-allocate
+            // the Vec so there will be no more allocations while downloading
+            Some(size) => SpooledBody::Memory(Vec::with_capacity(size as usize)),
+            None => SpooledBody::Memory(Vec::new()),
+        };
+
+        Ok(Self {
+            body,
+            temp_dir,
+            memory_limit,
+            size: 0,
+        })
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.size += bytes.len() as u64;
+
+        if let SpooledBody::Memory(data) = &self.body {
+            if self.size > self.memory_limit {
+                let mut file = tempfile::tempfile_in(self.temp_dir)?;
+                file.write_all(data)?;
+                self.body = SpooledBody::File(file);
+            }
+        }
+
+        match &mut self.body {
+            SpooledBody::Memory(data) => data.extend_from_slice(bytes),
+            SpooledBody::File(file) => file.write_all(bytes)?,
+        }
+
+        Ok(())
+    }
+
+    /// Gets a reader for everything which was written
+    pub fn into_reader(self) -> std::io::Result<Box<dyn Read>> {
+        match self.body {
+            SpooledBody::Memory(data) => Ok(Box::new(Cursor::new(data))),
+            SpooledBody::File(mut file) => {
+                file.seek(SeekFrom::Start(0))?;
+                Ok(Box::new(file))
+            }
+        }
+    }
+}
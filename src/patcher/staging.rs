@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+use tempfile::TempPath;
+
+use super::constants::STAGING_DIR;
+use super::utils::ensure_free_space;
+use super::PatchWorker;
+
+/// Writes a patched file without ever leaving it half written. The data is
+/// written to the staging directory first, then renamed over the file being
+/// replaced, which either happens completely or not at all.
+pub fn write<P>(worker: &PatchWorker, path: P, data: &[u8]) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    // The staging directory is inside the game directory, so renaming out of
+    // it never has to cross file systems
+    let staging_dir = worker.self_dir.join(STAGING_DIR);
+    std::fs::create_dir_all(&staging_dir)?;
+
+    let mut staged = tempfile::NamedTempFile::new_in(&staging_dir)?;
+    worker.faults.write()?;
+    staged.write_all(data)?;
+    staged.as_file().sync_all()?;
+
+    // Temporary files are only readable by their owner, so give the file the
+    // permissions of the one it replaces
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path)
+            .map(|metadata| metadata.permissions().mode())
+            .unwrap_or(0o644);
+        staged
+            .as_file()
+            .set_permissions(std::fs::Permissions::from_mode(mode))?;
+    }
+
+    staged.persist(path)?;
+
+    Ok(())
+}
+
+/// A copy of an archive pair in the staging directory. Changes are made to
+/// the copy, and the original pair is only replaced once the copy has been
+/// saved successfully, so a failure part way leaves the original untouched.
+pub struct StagedArchive {
+    pub archive: aeco_archive::Archive,
+    dat: TempPath,
+    hed: TempPath,
+}
+
+impl StagedArchive {
+    pub fn new(worker: &PatchWorker, dat: &Path, hed: &Path) -> Result<Self, Box<dyn Error>> {
+        let staging_dir = worker.self_dir.join(STAGING_DIR);
+        std::fs::create_dir_all(&staging_dir)?;
+
+        // Both files are copied in full, so they need to fit twice for a
+        // moment
+        let size = std::fs::metadata(dat)?.len() + std::fs::metadata(hed)?.len();
+        ensure_free_space(&staging_dir, size)?;
+
+        let staged_dat = tempfile::NamedTempFile::new_in(&staging_dir)?.into_temp_path();
+        let staged_hed = tempfile::NamedTempFile::new_in(&staging_dir)?.into_temp_path();
+        std::fs::copy(dat, &staged_dat)?;
+        std::fs::copy(hed, &staged_hed)?;
+
+        let archive = aeco_archive::Archive::open_pair(&staged_dat, &staged_hed)?;
+        Ok(Self {
+            archive,
+            dat: staged_dat,
+            hed: staged_hed,
+        })
+    }
+
+    /// Saves the changes made to the copy and puts it in place of the
+    /// original pair. The original must not be open anymore. With
+    /// `optimize`, the space left by replaced files is given back first.
+    pub fn commit(mut self, dat: &Path, hed: &Path, optimize: bool) -> Result<(), Box<dyn Error>> {
+        self.archive.finalize()?;
+        if optimize {
+            self.archive.defrag()?;
+        }
+
+        // Close the copy before moving it, Windows can't rename open files
+        let Self {
+            archive,
+            dat: staged_dat,
+            hed: staged_hed,
+        } = self;
+        drop(archive);
+
+        // Each rename is atomic, leaving only a brief moment between the
+        // two where the pair doesn't match
+        staged_dat.persist(dat)?;
+        staged_hed.persist(hed)?;
+        Ok(())
+    }
+}
+
+/// Removes anything left in the staging directory by an interrupted patch
+pub fn clear(worker: &PatchWorker) -> std::io::Result<()> {
+    let staging_dir = worker.self_dir.join(STAGING_DIR);
+    if staging_dir.exists() {
+        std::fs::remove_dir_all(staging_dir)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use super::client::build_other_client;
+use super::PatchWorker;
+use crate::config::DiagnosticsConfig;
+use crate::message::ErrorCategory;
+use crate::version::version_summary;
+
+/// Where patch statistics go unless the config says otherwise, if this
+/// build of the launcher has somewhere to send them
+const TELEMETRY_URL: Option<&str> = option_env!("AECO_TELEMETRY_URL");
+/// How long closing the launcher may be held up by sending statistics
+const SEND_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Where to send patch statistics, if the player agreed to share them
+pub fn telemetry_url(config: &DiagnosticsConfig) -> Option<&str> {
+    if !config.telemetry {
+        return None;
+    }
+    config
+        .telemetry_url
+        .as_deref()
+        .or(TELEMETRY_URL)
+        .filter(|url| !url.is_empty())
+}
+
+/// How a patch went, for the patch server's operators. Nothing in it says
+/// who the player is or where the game is installed.
+#[derive(Serialize)]
+pub struct PatchEvent {
+    launcher_version: String,
+    os: &'static str,
+    arch: &'static str,
+    channel: String,
+    /// Whether every file was checked, rather than only the changed ones
+    repair: bool,
+    outcome: Outcome,
+    /// What kind of problem stopped the patch, if one did
+    failure: Option<ErrorCategory>,
+    duration_secs: u64,
+    downloaded_bytes: u64,
+    files_downloaded: usize,
+    /// The host name of the patch server which was used
+    server: Option<String>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Finished,
+    /// The patch server couldn't be reached, but the game could be played
+    /// as it was
+    Offline,
+    Failed,
+}
+
+/// The patches of this run of the launcher, which are sent when it closes
+#[derive(Default)]
+pub struct Telemetry {
+    events: RefCell<Vec<PatchEvent>>,
+}
+
+impl Telemetry {
+    /// Notes how the patch which started at `started` went. Nothing is kept
+    /// unless the player agreed to share it, and previews aren't patches.
+    pub fn record(
+        &self,
+        worker: &PatchWorker,
+        started: Instant,
+        outcome: Outcome,
+        failure: Option<ErrorCategory>,
+    ) {
+        if telemetry_url(&worker.config.diagnostics).is_none() || worker.preview.is_some() {
+            return;
+        }
+        self.events.borrow_mut().push(PatchEvent {
+            launcher_version: version_summary(),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            channel: worker.config.patch.channel.clone(),
+            repair: worker.repair_summary.is_some(),
+            outcome,
+            failure,
+            duration_secs: started.elapsed().as_secs(),
+            downloaded_bytes: worker.report.downloaded_bytes,
+            files_downloaded: worker.report.files_downloaded,
+            server: worker.server_url.host_str().map(str::to_string),
+        });
+    }
+
+    /// Sends what was recorded during this run of the launcher, giving up
+    /// rather than keeping the launcher open for long
+    pub fn send(&self, worker: &PatchWorker) {
+        let events = self.events.take();
+        if events.is_empty() {
+            return;
+        }
+        let url = match telemetry_url(&worker.config.diagnostics) {
+            Some(url) => url,
+            None => return,
+        };
+        match try_send(worker, url, &events) {
+            Ok(()) => info!("Sent statistics for {} patches", events.len()),
+            Err(why) => warn!("Could not send patch statistics: {why}"),
+        }
+    }
+}
+
+fn try_send(worker: &PatchWorker, url: &str, events: &[PatchEvent]) -> Result<(), Box<dyn Error>> {
+    let url = reqwest::Url::parse(url)?;
+    if worker.config.network.https_only && url.scheme() != "https" {
+        return Err(format!("Statistics server {url} does not use HTTPS").into());
+    }
+    // The patch server's credentials are none of the statistics server's
+    // business
+    let client = build_other_client(&worker.config.network, &worker.launcher_dir)?;
+    let request = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(events)?);
+    worker
+        .runtime
+        .block_on(tokio::time::timeout(SEND_TIMEOUT, request.send()))
+        .map_err(|_| "Timed out")??
+        .error_for_status()?;
+    Ok(())
+}
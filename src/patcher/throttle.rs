@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Keeps all downloads together under a speed limit, so that patching
+/// doesn't take up the whole connection
+pub struct Throttle {
+    /// 0 means there is no limit
+    bytes_per_second: AtomicU64,
+    /// When the data received so far is "paid off" at the limited speed
+    caught_up_at: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: AtomicU64::new(bytes_per_second),
+            caught_up_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn set_limit(&self, bytes_per_second: u64) {
+        self.bytes_per_second
+            .store(bytes_per_second, Ordering::Relaxed);
+    }
+
+    /// Waits long enough after receiving `bytes` to stay under the limit
+    pub async fn received(&self, bytes: usize) {
+        let bytes_per_second = self.bytes_per_second.load(Ordering::Relaxed);
+        if bytes_per_second == 0 {
+            return;
+        }
+
+        let delay = {
+            let mut caught_up_at = self.caught_up_at.lock().unwrap();
+            let now = Instant::now();
+            let cost = Duration::from_secs_f64(bytes as f64 / bytes_per_second as f64);
+            *caught_up_at = (*caught_up_at).max(now) + cost;
+            caught_up_at.saturating_duration_since(now)
+        };
+
+        tokio::time::sleep(delay).await;
+    }
+}
@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use subprocess::{Exec, ExitStatus, NullFile, Popen, PopenConfig, Redirection};
+
+use super::constants::BASE_TORRENT;
+use super::download;
+use super::PatchWorker;
+
+/// Downloads the base game ZIP over BitTorrent using an external client
+/// (aria2), and returns it in a temporary file.
+///
+/// The torrent metainfo is published next to the base game ZIP on the patch
+/// server.
+pub fn game_base(worker: &PatchWorker) -> Result<File, Box<dyn Error>> {
+    let network = &worker.config.network;
+    if !client_found(&network.torrent_client) {
+        return Err(format!("Torrent client '{}' was not found", network.torrent_client).into());
+    }
+    let torrent_url = worker.game_base_url.join(BASE_TORRENT)?;
+
+    worker.send_info(tr!("Getting base game torrent"));
+    let metainfo = download::memory_file(worker, torrent_url, |_, _| {})?;
+    let mut metainfo_file = tempfile::NamedTempFile::new_in(&worker.self_dir)?;
+    metainfo_file.write_all(&metainfo)?;
+
+    // The client writes into a file we created so that it gets cleaned up
+    // like any other temporary file
+    let output = tempfile::NamedTempFile::new_in(&worker.self_dir)?;
+    let output_name = output
+        .path()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Invalid temporary file name")?;
+
+    let mut args = vec![
+        network.torrent_client.clone(),
+        "--allow-overwrite=true".to_string(),
+        "--file-allocation=none".to_string(),
+        "--show-console-readout=false".to_string(),
+        "--summary-interval=1".to_string(),
+        format!("--seed-time={}", network.torrent_seed_minutes),
+        format!("--dir={}", worker.self_dir.display()),
+        format!("--index-out=1={output_name}"),
+        format!("--torrent-file={}", metainfo_file.path().display()),
+    ];
+    // The client doesn't go through our own speed limit
+    if network.max_download_kbps > 0 {
+        args.push(format!(
+            "--max-overall-download-limit={}K",
+            network.max_download_kbps
+        ));
+    }
+
+    let mut client = Popen::create(
+        &args,
+        PopenConfig {
+            stdout: Redirection::Pipe,
+            ..PopenConfig::default()
+        },
+    )?;
+
+    // Relay the client's progress summaries to the GUI
+    if let Some(stdout) = client.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            if let Some((progress, sizes)) = parse_progress(&line?) {
+                worker.send_download(
+                    tr!("Downloading base game via torrent ({sizes})", sizes = sizes),
+                    progress,
+                );
+            }
+        }
+    }
+
+    match client.wait()? {
+        ExitStatus::Exited(0) => Ok(output.reopen()?),
+        status => Err(format!("Torrent client failed: {status:?}").into()),
+    }
+}
+
+/// Whether the torrent client can be run. It isn't shipped with the
+/// launcher, so the player has to install it themselves.
+pub fn client_found(client: &str) -> bool {
+    matches!(
+        Exec::cmd(client)
+            .arg("--version")
+            .stdout(NullFile)
+            .stderr(NullFile)
+            .join(),
+        Ok(ExitStatus::Exited(0))
+    )
+}
+
+/// Reads a progress line from aria2's summary, which looks like
+/// `[#2089b0 400.0KiB/33.2MiB(1%) CN:1 SD:3 DL:115.7KiB ETA:4m51s]`.
+/// Returns the progress from 0 to 1 and the "downloaded / total" text.
+fn parse_progress(line: &str) -> Option<(f32, String)> {
+    let line = line.trim().strip_prefix("[#")?;
+    let (_gid, rest) = line.split_once(' ')?;
+    let (sizes, rest) = rest.split_once('(')?;
+    let (percentage, _) = rest.split_once("%)")?;
+    let percentage = percentage.parse::<f32>().ok()?;
+    let (downloaded, total) = sizes.split_once('/')?;
+    Some((percentage / 100., format!("{downloaded} / {total}")))
+}
@@ -0,0 +1,117 @@
+use std::error::Error;
+use std::path::Path;
+
+use tracing::{info, warn};
+
+use super::constants::*;
+use super::integrity::load_base_files;
+use super::last_patch;
+use super::prune::game_files;
+use super::utils::{is_below, remove_empty_parents};
+use super::PatchWorker;
+use crate::config::CONFIG_FILE;
+
+/// What the launcher keeps for itself, which is of no use once the game is
+/// gone. Settings and the player's own files are not on this list.
+pub const LAUNCHER_STATE: &[&str] = &[
+    CACHE_DIR,
+    META_CACHE_DIR,
+    LAST_PATCH,
+    JOURNAL,
+    HASH_CACHE,
+    ARCHIVE_FRAGMENTATION,
+    PATCH_REPORT,
+    REMOVED_DIR,
+    BACKUP_DIR,
+    STAGING_DIR,
+    BASE_FILES,
+];
+
+/// The player's settings and what the launcher kept aside for them, which
+/// are only removed when asked to
+const SETTINGS: &[&str] = &[GAME_INI, GAME_OUTPUT, PATCH_IGNORE, QUARANTINE_DIR];
+
+/// Removes the game and the launcher's state from the game directory.
+/// Returns how many files and directories were removed.
+///
+/// Only what the launcher put there is removed, so screenshots and anything
+/// else the player added stay behind. The game directory can be any folder
+/// the player picked, so nothing is removed just for being in it. With
+/// `everything`, the player's settings and saved login go too. The launcher
+/// itself is always left, since it is running.
+pub fn run(worker: &PatchWorker, everything: bool) -> Result<usize, Box<dyn Error>> {
+    let game_lock = worker.lock_game_dir()?;
+    let mut files = load_base_files(&worker.self_dir);
+    if let Some(last_patch) = last_patch::load(worker) {
+        files.extend(game_files(
+            &last_patch.manifest,
+            &last_patch.components,
+            &last_patch.languages,
+        ));
+    }
+
+    let mut removed = 0;
+    for relative in &files {
+        // The patch list comes from the server, so don't let it point
+        // outside the game directory
+        if !is_below(relative) {
+            warn!("Not removing {relative:?}, which is outside the game directory");
+            continue;
+        }
+
+        let path = worker.self_dir.join(relative);
+        if worker.is_launcher(&path) || !path.is_file() {
+            continue;
+        }
+        info!("Removing {path:?}");
+        std::fs::remove_file(&path)?;
+        removed += 1;
+
+        // Take along directories which are empty now
+        remove_empty_parents(&path, &worker.self_dir);
+    }
+
+    for name in LAUNCHER_STATE {
+        removed += remove_path(&worker.self_dir.join(name))?;
+    }
+    removed += remove_path(&worker.get_self_aecoupdate_path()?)?;
+
+    if everything {
+        for name in SETTINGS {
+            removed += remove_path(&worker.self_dir.join(name))?;
+        }
+        for name in [CONFIG_FILE, SESSION_FILE] {
+            removed += remove_path(&worker.launcher_dir.join(name))?;
+        }
+    }
+
+    // The lock goes last, and has to be let go first since a locked file
+    // can't be removed on Windows
+    if let Some(game_lock) = game_lock {
+        drop(game_lock);
+        remove_path(&worker.self_dir.join(LOCK_FILE))?;
+    }
+
+    // Only an empty game directory goes, since anything left in it isn't
+    // the launcher's to remove
+    if worker.self_dir != worker.launcher_dir && std::fs::remove_dir(&worker.self_dir).is_ok() {
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Removes a file or directory if it exists, and returns whether it did
+fn remove_path(path: &Path) -> std::io::Result<usize> {
+    if path.is_dir() {
+        info!("Removing {path:?}");
+        std::fs::remove_dir_all(path)?;
+        Ok(1)
+    } else if path.exists() {
+        info!("Removing {path:?}");
+        std::fs::remove_file(path)?;
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
@@ -0,0 +1,339 @@
+use std::error::Error;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rayon::prelude::*;
+
+/// How often progress is reported while extracting in parallel
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Errors from extracting have to cross threads
+type ExtractError = Box<dyn Error + Send + Sync>;
+
+/// A file several threads can read from at once. Each copy keeps its own
+/// position and reads at it, instead of sharing the file's cursor.
+#[derive(Clone)]
+pub struct SharedFile {
+    file: Arc<std::fs::File>,
+    len: u64,
+    position: u64,
+}
+
+impl SharedFile {
+    pub fn new(file: std::fs::File) -> std::io::Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(Self {
+            file: Arc::new(file),
+            len,
+            position: 0,
+        })
+    }
+}
+
+impl Read for SharedFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = read_at(&self.file, buf, self.position)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for SharedFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &std::fs::File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    use std::os::unix::fs::FileExt;
+    file.read_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &std::fs::File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    // This moves the file's cursor too, but nothing here relies on it
+    use std::os::windows::fs::FileExt;
+    file.seek_read(buf, offset)
+}
+
+/// The permissions of the directories in a ZIP. They are only applied once
+/// everything has been extracted, since a directory which is read-only
+/// can't have files written into it.
+#[derive(Default)]
+pub struct DirModes(Vec<(PathBuf, u32)>);
+
+impl DirModes {
+    /// Applies the permissions, to the innermost directories first
+    pub fn apply(self) -> std::io::Result<()> {
+        for (path, mode) in self.0.into_iter().rev() {
+            set_mode(&path, mode)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    // Only Unix permissions are kept in ZIPs
+    Ok(())
+}
+
+/// Extracts every entry of `archive` into `dir`. The directories are
+/// created first, and then the files are written on the threads of `pool`,
+/// so at most that many at once. The files don't depend on each other, so
+/// they can be written in any order. `progress` is called every so often
+/// on this thread with how many entries and bytes are done. Returns the
+/// paths of the files extracted, relative to `dir`, in archive order.
+pub fn extract_all<F>(
+    pool: &rayon::ThreadPool,
+    archive: &zip::read::ZipArchive<SharedFile>,
+    dir: &Path,
+    mut progress: F,
+) -> Result<Vec<PathBuf>, Box<dyn Error>>
+where
+    F: FnMut(usize, u64),
+{
+    let mut dir_modes = DirModes::default();
+    let mut files = Vec::new();
+    let mut reader = archive.clone();
+    for index in 0..archive.len() {
+        let entry = reader.by_index_raw(index)?;
+        if entry.is_dir() {
+            create_dir(dir, &entry, &mut dir_modes).map_err(|why| why as Box<dyn Error>)?;
+        } else {
+            files.push(index);
+        }
+    }
+
+    let entries_done = AtomicUsize::new(archive.len() - files.len());
+    let bytes_done = AtomicU64::new(0);
+
+    let extracted = std::thread::scope(|scope| {
+        let extraction = scope.spawn(|| {
+            pool.install(|| {
+                files
+                    .into_par_iter()
+                    .map_init(
+                        || archive.clone(),
+                        |archive, index| {
+                            let mut file = archive.by_index(index)?;
+                            let path = extract_file(dir, &mut file)?;
+                            bytes_done.fetch_add(file.size(), Ordering::Relaxed);
+                            entries_done.fetch_add(1, Ordering::Relaxed);
+                            Ok(path)
+                        },
+                    )
+                    .collect::<Result<Vec<_>, ExtractError>>()
+            })
+        });
+
+        while !extraction.is_finished() {
+            progress(
+                entries_done.load(Ordering::Relaxed),
+                bytes_done.load(Ordering::Relaxed),
+            );
+            std::thread::sleep(PROGRESS_INTERVAL);
+        }
+        extraction.join()
+    });
+
+    let extracted = match extracted {
+        Ok(extracted) => extracted.map_err(|why| why as Box<dyn Error>)?,
+        Err(panic) => std::panic::resume_unwind(panic),
+    };
+    dir_modes.apply()?;
+    Ok(extracted)
+}
+
+/// Writes a single file or directory from a ZIP into `dir`. Returns the path
+/// of the file relative to `dir`, or None for directories. The permissions
+/// of directories are added to `dir_modes` for later.
+pub fn extract_entry(
+    dir: &Path,
+    file: &mut zip::read::ZipFile,
+    dir_modes: &mut DirModes,
+) -> Result<Option<PathBuf>, ExtractError> {
+    if file.is_dir() {
+        create_dir(dir, file, dir_modes)?;
+        Ok(None)
+    } else {
+        extract_file(dir, file).map(Some)
+    }
+}
+
+/// Gets where an entry of a ZIP goes, relative to the directory it is
+/// extracted into
+fn entry_path(file: &zip::read::ZipFile) -> Result<PathBuf, ExtractError> {
+    Ok(file.enclosed_name().ok_or("Invalid file path")?.to_owned())
+}
+
+/// Creates a directory from a ZIP in `dir`, adding its permissions to
+/// `dir_modes`
+fn create_dir(
+    dir: &Path,
+    file: &zip::read::ZipFile,
+    dir_modes: &mut DirModes,
+) -> Result<(), ExtractError> {
+    let outpath = dir.join(entry_path(file)?);
+    std::fs::create_dir_all(&outpath)?;
+    if let Some(mode) = file.unix_mode() {
+        dir_modes.0.push((outpath, mode));
+    }
+    Ok(())
+}
+
+/// Writes a single file from a ZIP into `dir`, and returns its path relative
+/// to `dir`
+fn extract_file(dir: &Path, file: &mut zip::read::ZipFile) -> Result<PathBuf, ExtractError> {
+    let filepath = entry_path(file)?;
+    let outpath = dir.join(&filepath);
+
+    // Create parent directories if needed. Other threads may be creating
+    // the same ones, which create_dir_all doesn't mind.
+    if let Some(p) = outpath.parent() {
+        if !p.exists() {
+            std::fs::create_dir_all(p)?;
+        }
+    }
+
+    // Copy extracted file to disk
+    let mut outfile = std::fs::File::create(&outpath)?;
+    std::io::copy(file, &mut outfile)?;
+
+    if let Some(mode) = file.unix_mode() {
+        set_mode(&outpath, mode)?;
+    }
+
+    Ok(filepath)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn shared_file(contents: &[u8]) -> SharedFile {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(contents).unwrap();
+        SharedFile::new(file).unwrap()
+    }
+
+    fn read_byte(file: &mut SharedFile) -> u8 {
+        let mut byte = [0];
+        file.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    #[test]
+    fn seek_moves_from_start_end_and_current() {
+        let mut file = shared_file(b"0123456789");
+        assert_eq!(file.seek(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(read_byte(&mut file), b'2');
+        assert_eq!(file.seek(SeekFrom::Current(4)).unwrap(), 7);
+        assert_eq!(read_byte(&mut file), b'7');
+        assert_eq!(file.seek(SeekFrom::End(-3)).unwrap(), 7);
+        assert_eq!(read_byte(&mut file), b'7');
+        assert_eq!(file.seek(SeekFrom::Current(-8)).unwrap(), 0);
+        assert_eq!(read_byte(&mut file), b'0');
+    }
+
+    #[test]
+    fn seek_refuses_negative_positions() {
+        let mut file = shared_file(b"0123456789");
+        file.seek(SeekFrom::Start(5)).unwrap();
+        let why = file.seek(SeekFrom::End(-11)).unwrap_err();
+        assert_eq!(why.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(file.seek(SeekFrom::Current(-6)).is_err());
+        // A failed seek doesn't move it
+        assert_eq!(read_byte(&mut file), b'5');
+    }
+
+    #[test]
+    fn seek_past_the_end_reads_nothing() {
+        let mut file = shared_file(b"0123456789");
+        assert_eq!(file.seek(SeekFrom::End(5)).unwrap(), 15);
+        assert_eq!(file.read(&mut [0; 4]).unwrap(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_directories_get_their_files_first() {
+        use std::os::unix::fs::PermissionsExt;
+        use zip::write::FileOptions;
+
+        let mut writer = zip::ZipWriter::new(tempfile::tempfile().unwrap());
+        let read_only = FileOptions::default().unix_permissions(0o555);
+        writer.add_directory("data/", read_only).unwrap();
+        writer.add_directory("data/maps/", read_only).unwrap();
+        let names = (0..50)
+            .map(|index| format!("data/maps/{index}.bin"))
+            .collect::<Vec<_>>();
+        for name in &names {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        let archive = writer.finish().unwrap();
+        let archive = zip::ZipArchive::new(SharedFile::new(archive).unwrap()).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        let extracted = extract_all(&pool, &archive, dir.path(), |_, _| {}).unwrap();
+
+        let mode = |path: &str| {
+            let metadata = std::fs::metadata(dir.path().join(path)).unwrap();
+            metadata.permissions().mode() & 0o777
+        };
+        assert_eq!(mode("data"), 0o555);
+        assert_eq!(mode("data/maps"), 0o555);
+        assert_eq!(
+            extracted,
+            names.iter().map(PathBuf::from).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            std::fs::read(dir.path().join("data/maps/49.bin")).unwrap(),
+            b"data/maps/49.bin"
+        );
+
+        // So the directory can be cleaned up
+        for path in ["data/maps", "data"] {
+            let permissions = std::fs::Permissions::from_mode(0o755);
+            std::fs::set_permissions(dir.path().join(path), permissions).unwrap();
+        }
+    }
+
+    #[test]
+    fn copies_keep_their_own_position() {
+        let mut first = shared_file(b"0123456789");
+        first.seek(SeekFrom::Start(3)).unwrap();
+        let mut second = first.clone();
+        second.seek(SeekFrom::Start(8)).unwrap();
+        assert_eq!(read_byte(&mut first), b'3');
+        assert_eq!(read_byte(&mut second), b'8');
+        assert_eq!(read_byte(&mut first), b'4');
+    }
+}
@@ -1,4 +1,7 @@
-use std::path::Path;
+use super::constants::{COMPONENT_PREFIX, LANGUAGE_PREFIX};
+use super::error::PatcherError;
+use aeco_patch_config::fsobject::{Directory, FSObject};
+use std::path::{Component, Path};
 
 /// Format a quantity of bytes into a human readable string
 pub fn byte_string<T>(bytes: T) -> String
@@ -30,3 +33,106 @@ where
     }
     Ok(())
 }
+
+/// Makes sure the volume containing `path` has at least `required` bytes of
+/// free space, returning `InsufficientSpace` if it does not
+pub fn ensure_free_space<P>(path: P, required: u64) -> Result<(), PatcherError>
+where
+    P: AsRef<Path>,
+{
+    let available = fs2::available_space(path)?;
+    if available < required {
+        return Err(PatcherError::InsufficientSpace {
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Gets a Directory child from a Directory by name, if it is present
+pub fn subdir_by_name<'a>(dir: &'a Directory, name: &str) -> Option<&'a Directory> {
+    for child in &dir.children {
+        if let FSObject::Directory(d) = child {
+            if d.name == name {
+                return Some(d);
+            }
+        }
+    }
+    None
+}
+
+/// Gets the names of the top level patch list directories which are
+/// installed: files for all platforms, files for this platform, the chosen
+/// optional components and the chosen language packs
+pub fn installed_dirs(components: &[String], languages: &[String]) -> Vec<String> {
+    let mut dirs = vec!["all".to_string(), get_platform()];
+    dirs.extend(
+        components
+            .iter()
+            .map(|component| format!("{COMPONENT_PREFIX}{component}")),
+    );
+    dirs.extend(
+        languages
+            .iter()
+            .map(|language| format!("{LANGUAGE_PREFIX}{language}")),
+    );
+    dirs
+}
+
+/// Gets the names of the optional components a patch list offers
+pub fn available_components(manifest: &Directory) -> Vec<String> {
+    subdirs_with_prefix(manifest, COMPONENT_PREFIX)
+}
+
+/// Gets the codes of the language packs a patch list offers
+pub fn available_languages(manifest: &Directory) -> Vec<String> {
+    subdirs_with_prefix(manifest, LANGUAGE_PREFIX)
+}
+
+fn subdirs_with_prefix(manifest: &Directory, prefix: &str) -> Vec<String> {
+    manifest
+        .children
+        .iter()
+        .filter_map(|child| match child {
+            FSObject::Directory(d) => d.name.strip_prefix(prefix),
+            _ => None,
+        })
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Whether a relative path only names things below the directory it is
+/// joined to. Paths from the patch list or the GUI are checked with this
+/// before anything is moved or removed, so `..`, a root or a drive letter
+/// can't point elsewhere.
+pub fn is_below(relative: &Path) -> bool {
+    relative.components().next().is_some()
+        && relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Removes the directories containing `path` which are empty, up to but not
+/// including `root`
+pub fn remove_empty_parents(path: &Path, root: &Path) {
+    for dir in path.ancestors().skip(1) {
+        if dir == root || !dir.starts_with(root) || std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_plain_relative_paths_are_below() {
+        assert!(is_below(Path::new("data/game.dat")));
+        assert!(!is_below(Path::new("")));
+        assert!(!is_below(Path::new("../launcher.json")));
+        assert!(!is_below(Path::new("data/../../outside")));
+        assert!(!is_below(Path::new("/etc/passwd")));
+    }
+}
@@ -1,22 +1,99 @@
-use super::check_patches::check_platform_patches;
+use super::backup::{self, Backup};
+use super::bundle;
+use super::cache;
+use super::changelog;
+use super::check_patches::{
+    check_platform_patches, get_total_files_in_patch, send_checked_summary,
+};
+use super::client::build_client;
+use super::connection_test;
 use super::constants::*;
-use super::download;
-use super::error::{PatchError, PatchErrorLevel, ToPatchError};
-use super::utils::set_executable;
-use super::utils::{byte_string, get_platform};
-use crate::message::{GUIMessage, PatchMessage, PatchStatus};
-use aeco_patch_config::fsobject::*;
+use super::crash;
+use super::crash_upload;
+use super::download::{self, Patchlist};
+use super::elevation;
+use super::error::{self, PatchError, PatcherError, ToPatchError};
+use super::fault::Faults;
+use super::file_mode::FileModes;
+use super::fragmentation;
+use super::game_version;
+use super::hash_cache::HashCache;
+use super::heartbeat::{KeepAlive, HEARTBEAT_INTERVAL};
+use super::hook;
+use super::ignore::IgnoreList;
+use super::instance::InstanceLock;
+use super::integrity;
+use super::journal::{self, Journal};
+use super::last_patch::{self, LastPatch};
+use super::manifest_diff::ManifestDiff;
+use super::news;
+use super::preflight;
+use super::priority;
+use super::prune;
+use super::realm;
+use super::relocate;
+use super::report;
+use super::retry::RetryPolicy;
+use super::running;
+use super::self_update;
+use super::session::{self, Session};
+use super::shortcut;
+use super::staging;
+use super::telemetry::{Outcome, Telemetry};
+use super::throttle::Throttle;
+use super::torrent;
+use super::uninstall;
+use super::unzip::{self, DirModes, SharedFile};
+use super::utils::{available_components, available_languages, byte_string};
+use super::utils::{ensure_free_space, set_executable};
+use super::utils::{installed_dirs, subdir_by_name};
+use super::zip_size;
+use crate::config::{
+    AuthConfig, Config, CrashReports, Profile, Realm, Settings, CONFIG_FILE, STABLE_CHANNEL,
+};
+use crate::crash_report;
+use crate::message::{
+    ErrorCategory, FileAction, GUIMessage, PatchMessage, PatchPreview, PatchReport, PatchStatus,
+    RepairSummary, TransferRate, WorkerState,
+};
+use aeco_patch_config::fsobject::{Directory, File as PatchFile};
 use aeco_patch_config::status::ServerStatus;
+use std::cell::Cell;
+use std::cmp::Ordering;
 use std::error::Error;
 use std::ffi::OsStr;
+use std::io::Read;
+use std::time::Instant;
 use std::{
-    path::PathBuf,
-    sync::mpsc::{Receiver, Sender},
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvError, RecvTimeoutError, Sender},
+    sync::Arc,
 };
 use subprocess::PopenError;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{error, info, warn};
 
 const UPDATE_FILE_EXTENSION: &str = "aecoupdate";
 
+/// An update to the launcher, saved next to it with the update extension
+pub struct UpdatedLauncher {
+    pub path: PathBuf,
+    /// The launcher's name in the patch list, which its digest is made with
+    pub name: String,
+    /// The digest of the update as it was when its signature was checked
+    pub digest: Vec<u8>,
+}
+
+impl UpdatedLauncher {
+    /// Whether the saved update is still the one which was verified
+    fn is_intact(&self) -> bool {
+        match std::fs::read(&self.path) {
+            Ok(data) => PatchFile::new(&self.name, &data).digest == self.digest,
+            Err(_) => false,
+        }
+    }
+}
+
 /// This is used for functions which need to communicate whether the program
 /// should continue running or shut down after executing.
 pub enum RunState {
@@ -27,50 +104,190 @@ pub enum RunState {
 pub struct PatchWorker {
     tx: Sender<PatchMessage>,
     rx: Receiver<GUIMessage>,
+    /// What the worker is doing, as the GUI was last told
+    state: Cell<WorkerState>,
+    /// When the GUI last heard from the worker, so it can be sent a
+    /// heartbeat when there is nothing else to say
+    last_sent: Cell<Instant>,
+    /// The GUI closed while the worker was busy
+    close_requested: Cell<bool>,
+    /// The last patch finished without errors
+    patched: Cell<bool>,
+    /// Start the game by itself after the next patch which finishes without
+    /// errors. This only happens once, and never without a GUI.
+    auto_launch: bool,
+    /// Where the game is installed, which is next to the launcher unless the
+    /// config says otherwise
     pub self_dir: PathBuf,
+    /// Where the launcher and its config are
+    pub launcher_dir: PathBuf,
+    /// The launcher is run from somewhere which may move, like a USB stick,
+    /// so everything it stores is kept next to it and paths are stored
+    /// relative to it
+    pub portable: bool,
+    /// Running from Steam's Big Picture mode, which tracks the launcher as
+    /// the game. The game starts once it is up to date, and the launcher
+    /// waits for it and exits with its exit code.
+    pub big_picture: bool,
+    /// Patch with administrator rights when the game directory can't be
+    /// changed without them. The copy of the launcher which gets them is
+    /// told not to, so it doesn't ask again.
+    pub may_elevate: bool,
+    /// Keeps other copies of the launcher from running from `launcher_dir`
+    /// while this one is
+    pub instance_lock: Option<InstanceLock>,
     pub self_exe: PathBuf,
+    /// Where the launcher's logs go
+    pub log_dir: PathBuf,
     pub client: reqwest::Client,
+    /// Limits how many requests are sent to the patch server at once
+    pub connections: Arc<Semaphore>,
+    /// The threads which hash files while checking the game
+    pub hash_pool: rayon::ThreadPool,
+    /// Keeps downloads under the configured speed limit
+    pub throttle: Arc<Throttle>,
+    /// Failures to cause on purpose, for testing
+    pub faults: Arc<Faults>,
     pub server_url: reqwest::Url,
+    /// The patch server chosen on the command line or in the environment,
+    /// which is used no matter what the config says
+    server_override: Option<String>,
     pub game_base_url: reqwest::Url,
     pub game_zip_url: reqwest::Url,
     pub patchlist_url: reqwest::Url,
     pub status_url: reqwest::Url,
     pub patch_url: reqwest::Url,
     pub runtime: tokio::runtime::Runtime,
-    pub updated_patcher: Option<PathBuf>,
+    /// A newer launcher which was downloaded and verified during this patch,
+    /// and is started once it finishes
+    pub updated_patcher: Option<UpdatedLauncher>,
+    /// The patch server releases the launcher on its own, so the launcher in
+    /// the patch list is left alone
+    pub separate_launcher_updates: bool,
+    /// Tallies what was checked while repairing the installation, and is
+    /// only set while doing so
+    pub repair_summary: Option<RepairSummary>,
+    /// Collects what patching would change, and is only set while
+    /// previewing. Nothing is downloaded or written while previewing.
+    pub preview: Option<PatchPreview>,
+    /// Files which have already been checked during this patch
+    pub journal: Journal,
+    /// Files which haven't changed since the last patch
+    pub manifest_diff: ManifestDiff,
+    pub hash_cache: HashCache,
+    pub backup: Backup,
+    pub ignore_list: IgnoreList,
+    pub file_modes: FileModes,
+    /// What the current patch has done so far
+    pub report: PatchReport,
+    /// Why the current patch fell back to playing offline, if it did
+    went_offline: Cell<Option<ErrorCategory>>,
+    /// How the patches went, for the patch server's operators
+    telemetry: Telemetry,
+    pub retry_policy: RetryPolicy,
+    pub config: Config,
+    /// The game's exit code, once the worker waited for the game to exit
+    exit_code: Option<i32>,
 }
 
 impl PatchWorker {
     pub fn new(
         sender: Sender<PatchMessage>,
         receiver: Receiver<GUIMessage>,
+        server_override: Option<String>,
+        portable: bool,
     ) -> Result<Self, Box<dyn Error>> {
         let self_exe = std::env::current_exe()?;
-        let self_dir = self_exe
-            .parent()
-            .ok_or_else(|| "No parent directory for the launcher was found.".to_string())?
-            .to_path_buf();
+        let launcher_dir = launcher_dir()?;
+        let portable = is_portable(&launcher_dir, portable);
+
+        // A broken config file shouldn't prevent the game from being played,
+        // so fall back to the defaults
+        let mut config = Config::load(launcher_dir.join(CONFIG_FILE)).unwrap_or_else(|why| {
+            warn!("Could not load {CONFIG_FILE}, using defaults: {why}");
+            Config::default()
+        });
+        config.network.auth = session::load_credentials(&launcher_dir);
+
+        let server_override = server_override
+            .or_else(|| std::env::var(PATCH_SERVER_ENV).ok())
+            .filter(|server| !server.trim().is_empty());
+        Self::with_config(
+            sender,
+            receiver,
+            self_exe,
+            launcher_dir,
+            config,
+            server_override,
+            portable,
+        )
+    }
 
-        let server_url = reqwest::Url::parse(PATCH_SERVER)?;
+    /// Sets up a worker for the launcher at `self_exe`, which keeps its
+    /// files in `launcher_dir` and has already loaded `config`
+    fn with_config(
+        sender: Sender<PatchMessage>,
+        receiver: Receiver<GUIMessage>,
+        self_exe: PathBuf,
+        launcher_dir: PathBuf,
+        config: Config,
+        server_override: Option<String>,
+        portable: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let server_url = parse_server_url(
+            server_override
+                .as_deref()
+                .or(config.patch.server.as_deref())
+                .unwrap_or(PATCH_SERVER),
+        )?;
         let game_base_url = server_url.join(BASE_DIR)?;
         let game_zip_url = game_base_url.join(BASE_ZIP)?;
         let meta_url = server_url.join(META_DIR)?;
-        let patchlist_url = meta_url.join(PATCHLIST)?;
         let status_url = meta_url.join(STATUS)?;
-        let patch_url = server_url.join(PATCH_DIR)?;
+        let self_dir = match &config.patch.game_dir {
+            Some(game_dir) => launcher_dir.join(game_dir),
+            None => launcher_dir.clone(),
+        };
 
-        let client = reqwest::Client::builder().build()?;
+        let (patchlist_url, patch_url) =
+            channel_urls(&server_url, &config.patch.channel).or_else(|why| {
+                warn!("{why}, using the {STABLE_CHANNEL} channel");
+                channel_urls(&server_url, STABLE_CHANNEL)
+            })?;
+
+        if config.network.https_only && server_url.scheme() != "https" {
+            return Err(format!("Patch server {server_url} does not use HTTPS").into());
+        }
+
+        let client = build_client(&config.network, &launcher_dir)?;
         let runtime = tokio::runtime::Builder::new_multi_thread()
             .enable_all()
             .build()?;
 
-        Ok(Self {
+        let log_dir = crate::logging::log_dir(&launcher_dir, portable);
+        let mut worker = Self {
             tx: sender,
             rx: receiver,
+            state: Cell::new(WorkerState::Idle),
+            last_sent: Cell::new(Instant::now()),
+            close_requested: Cell::new(false),
+            patched: Cell::new(false),
+            auto_launch: false,
             self_dir,
+            launcher_dir,
+            portable,
+            big_picture: false,
+            may_elevate: true,
+            instance_lock: None,
+            log_dir,
             self_exe,
             client,
+            connections: Arc::new(Semaphore::new(config.simultaneous_downloads())),
+            hash_pool: build_hash_pool(&config)?,
+            throttle: Arc::new(Throttle::new(config.network.download_limit())),
+            faults: Arc::new(Faults::from_env()),
             server_url,
+            server_override,
             game_base_url,
             game_zip_url,
             patchlist_url,
@@ -78,20 +295,72 @@ impl PatchWorker {
             patch_url,
             runtime,
             updated_patcher: None,
-        })
+            separate_launcher_updates: false,
+            repair_summary: None,
+            preview: None,
+            journal: Journal::default(),
+            manifest_diff: ManifestDiff::default(),
+            hash_cache: HashCache::default(),
+            backup: Backup::default(),
+            ignore_list: IgnoreList::default(),
+            file_modes: FileModes::default(),
+            report: PatchReport::default(),
+            went_offline: Cell::new(None),
+            telemetry: Telemetry::default(),
+            retry_policy: RetryPolicy {
+                attempts: config.network.download_attempts.max(1),
+                ..RetryPolicy::default()
+            },
+            config,
+            exit_code: None,
+        };
+
+        if let Some(name) = worker.config.patch.profile.clone() {
+            if let Err(why) = worker.apply_profile(&name) {
+                warn!("Could not use profile '{name}': {why}");
+            }
+        }
+
+        Ok(worker)
     }
 
     /// Send a message to the GUI
     fn send(&self, message: PatchMessage) {
+        self.last_sent.set(Instant::now());
         if let Err(why) = self.tx.send(message) {
-            eprintln!("Could not send message from PatchWorker to GUI: {why}");
+            warn!("Could not send message from PatchWorker to GUI: {why}");
+        }
+    }
+
+    /// Lets the GUI know the worker is still getting somewhere, if it hasn't
+    /// heard anything else for a while. Without these, it offers to give up
+    /// on the worker.
+    pub fn heartbeat(&self) {
+        if self.last_sent.get().elapsed() >= HEARTBEAT_INTERVAL {
+            self.send(PatchMessage::Heartbeat);
         }
     }
 
-    /// Send an error to the GUI
-    pub fn send_error(&self, text: String) {
+    /// Waits until another request may be sent to the patch server, so no
+    /// more than `max_connections_per_host` are open at once. The request
+    /// may go on for as long as the returned permit is kept, which has to
+    /// include reading its body.
+    pub fn connection(&self) -> Result<OwnedSemaphorePermit, PatcherError> {
+        Ok(self
+            .runtime
+            .block_on(self.connections.clone().acquire_owned())?)
+    }
+
+    /// Keeps sending heartbeats until the returned guard is dropped, for
+    /// single steps which take long and can't call `heartbeat` themselves
+    pub fn keep_alive(&self) -> KeepAlive {
+        KeepAlive::start(self.tx.clone())
+    }
+
+    /// Send an error to the GUI, along with the details of what caused it
+    pub fn send_error(&self, text: String, why: &PatcherError) {
         self.send_status(PatchStatus::Error);
-        self.send(PatchMessage::Error(text));
+        self.send(PatchMessage::Error(text, error::details(why)));
     }
 
     /// Send download information to the GUI
@@ -99,160 +368,1208 @@ impl PatchWorker {
         self.send(PatchMessage::Downloading(text, percentage));
     }
 
+    /// Send download information along with the download speed to the GUI
+    pub fn send_transfer(&self, text: String, percentage: f32, rate: TransferRate) {
+        self.send(PatchMessage::Transfer(text, percentage, rate));
+    }
+
     /// Send misc information to the GUI
     pub fn send_info(&self, text: String) {
         self.send(PatchMessage::Info(text));
     }
 
-    /// Send information about the result of the patch routine to the GUI
+    /// Tells the GUI what happened to a file
+    pub fn send_file_result(&self, path: &Path, action: FileAction, bytes: u64) {
+        let path = path.strip_prefix(&self.self_dir).unwrap_or(path);
+        info!(path = %path.display(), bytes, "{action}");
+        self.send(PatchMessage::FileResult {
+            path: path.display().to_string(),
+            action,
+            bytes,
+        });
+    }
+
+    /// Send information about the result of the patch routine to the GUI,
+    /// along with what the worker is doing now
     pub fn send_status(&self, status: PatchStatus) {
+        let state = WorkerState::after(&status);
+        self.patched.set(matches!(status, PatchStatus::Finished));
         self.send(PatchMessage::PatchStatus(status));
-        self.clear_recv();
+        if let Some(state) = state {
+            self.set_state(state);
+        }
     }
 
-    fn recv(&self) -> Result<GUIMessage, std::sync::mpsc::RecvError> {
+    fn set_state(&self, state: WorkerState) {
+        if self.state.get().is_busy() && !state.is_busy() {
+            self.reject_queued();
+        }
+        if self.state.replace(state) != state {
+            self.send(PatchMessage::State(state));
+        }
+    }
+
+    fn recv(&self) -> Result<GUIMessage, RecvError> {
         self.rx.recv()
     }
 
-    fn clear_recv(&self) {
-        while self.rx.try_recv().is_ok() {}
+    /// Waits for the GUI, checking for new patches every so often in the
+    /// meantime in case the launcher is left open
+    fn wait_for_message(&self) -> Result<GUIMessage, RecvError> {
+        let interval = match self.config.patch.recheck_interval() {
+            Some(interval) => interval,
+            None => return self.recv(),
+        };
+
+        loop {
+            match self.rx.recv_timeout(interval) {
+                Ok(message) => return Ok(message),
+                Err(RecvTimeoutError::Disconnected) => return Err(RecvError),
+                Err(RecvTimeoutError::Timeout) => match self.update_available() {
+                    Ok(true) => {
+                        // Once is enough, the player knows now
+                        self.send(PatchMessage::UpdateAvailable);
+                        if let Ok(Some(version)) = game_version::available(self) {
+                            self.send_changelog(&version);
+                        }
+                        return self.recv();
+                    }
+                    Ok(false) => {}
+                    Err(why) => warn!("Could not check for a new patch: {why}"),
+                },
+            }
+        }
+    }
+
+    /// Checks whether the patch server has something newer than what the
+    /// last patch installed, without changing anything
+    fn update_available(&self) -> Result<bool, PatcherError> {
+        let last_patch = match last_patch::load(self) {
+            Some(last_patch) => last_patch,
+            None => return Ok(false),
+        };
+
+        let available_version = game_version::available(self).map_err(|why| why.internal_error)?;
+        if available_version.is_some() {
+            return Ok(!self.is_up_to_date(Some(&last_patch), available_version.as_deref()));
+        }
+
+        // Without versions, the patch lists themselves need to be compared
+        let patch = download::patch_metadata_quietly(self).map_err(|why| why.internal_error)?;
+        Ok(serde_json::to_value(&patch.root)? != serde_json::to_value(&last_patch.manifest)?)
     }
 
-    pub fn run(mut self) {
+    /// Drops whatever the GUI asked for while the worker was busy. Those
+    /// requests were made before knowing how the work would turn out, and
+    /// repeated clicks shouldn't run the same thing again. Only closing is
+    /// remembered.
+    fn reject_queued(&self) {
+        while let Ok(message) = self.rx.try_recv() {
+            if let GUIMessage::Close = message {
+                self.close_requested.set(true);
+            }
+        }
+    }
+
+    /// Runs the worker, starting with `first_message` as if the GUI had sent
+    /// it. Normally this is `GUIMessage::Retry`, which patches the game.
+    /// Returns the game's exit code if the worker closed after waiting for
+    /// the game, which the launcher exits with once the worker is dropped.
+    pub fn run(mut self, first_message: GUIMessage) -> Option<i32> {
+        self.auto_launch = self.config.game.auto_launch || self.big_picture;
+        self.send_settings();
+        self.send_server();
+        self.send(PatchMessage::LogDir(self.log_dir.clone()));
+        self.check_crash_reports();
+        self.send(PatchMessage::LaunchTargets(
+            LAUNCH_TARGETS
+                .iter()
+                .map(|target| (target.id.to_string(), target.name.to_string()))
+                .collect(),
+        ));
+        if !self.config.profiles.is_empty() {
+            self.send_profiles();
+        }
+        if !self.config.realms.is_empty() {
+            self.measure_realms();
+        }
+        if self.is_first_run() {
+            if let RunState::Close = self.first_run_setup() {
+                return None;
+            }
+        }
         // Main loop includes all message handling and patching operations
-        self.main_loop();
+        self.main_loop(first_message);
         // Since this takes ownership, once this ends, drop() will be called
         // and will communicate to the GUI that it should close
+        self.exit_code
     }
 
-    fn main_loop(&mut self) {
-        // Start by performing the patch check
-        let mut message = GUIMessage::Retry;
-        loop {
-            match message {
-                GUIMessage::Retry => {
-                    self.send_status(PatchStatus::Working);
-                    match self.patch_routine() {
-                        Ok(RunState::Continue) => {}
+    /// Whether the launcher has never been used here: there is no config
+    /// file and no game yet
+    fn is_first_run(&self) -> bool {
+        !self.launcher_dir.join(CONFIG_FILE).exists() && !self.is_game_present()
+    }
 
-                        // End if a state was encountered that requires the
-                        // program to close
-                        Ok(RunState::Close) => return,
+    /// Lets the player choose where and how to install the game before
+    /// anything is downloaded, and saves what they chose
+    fn first_run_setup(&mut self) -> RunState {
+        self.send(PatchMessage::FirstRun);
+        loop {
+            match self.recv() {
+                Ok(GUIMessage::FinishSetup {
+                    settings,
+                    create_shortcut,
+                }) => {
+                    let language = crate::i18n::resolve(&settings.language);
+                    self.config.patch.languages = vec![language.to_string()];
+                    if let Err(why) = self.update_settings(settings) {
+                        // Let the player try something else
+                        self.send_error(tr!("Could not save the settings"), &why);
+                        warn!("Could not save the settings: {why}");
+                        self.send(PatchMessage::FirstRun);
+                        continue;
+                    }
+                    self.send_settings();
+                    if create_shortcut {
+                        if let Err(why) = shortcut::create(&self.self_exe) {
+                            warn!("Could not create a shortcut: {why}");
+                        }
+                    }
+                    return RunState::Continue;
+                }
+                Ok(GUIMessage::Close) => return RunState::Close,
+                Ok(_) => warn!("Ignored a request while the launcher is being set up"),
+                Err(why) => {
+                    warn!("{why}");
+                    return RunState::Close;
+                }
+            }
+        }
+    }
 
-                        Err(why) => {
-                            // Communicate error status to the GUI
-                            self.send_status(PatchStatus::Error);
+    fn main_loop(&mut self, first_message: GUIMessage) {
+        let mut message = first_message;
+        while let RunState::Continue = self.handle_message(message) {
+            if self.close_requested.get() {
+                return;
+            }
+            message = match self.wait_for_message() {
+                Ok(m) => m,
+                Err(why) => {
+                    warn!("{why}");
+                    return;
+                }
+            };
+        }
+    }
 
-                            // Display error message
-                            match why.level {
-                                PatchErrorLevel::Low => self.send_info(why.friendly_message),
-                                PatchErrorLevel::High => self.send_error(why.friendly_message),
-                            }
+    /// Does what the GUI asked for, and tells whether to keep going
+    /// afterwards
+    fn handle_message(&mut self, message: GUIMessage) -> RunState {
+        if !self.state.get().accepts(&message) {
+            warn!("Ignored a request which doesn't fit what the launcher is doing");
+            return RunState::Continue;
+        }
 
-                            // Log more detailed error info to the terminal
-                            eprintln!("{:?}", why.internal_error);
+        match message {
+            GUIMessage::Retry => {
+                if let RunState::Close = self.check_for_patches() {
+                    return RunState::Close;
+                }
+            }
+            GUIMessage::Login { username, password } => {
+                let auth = AuthConfig::Basic { username, password };
+                if let Err(why) = session::save_credentials(&self.launcher_dir, &auth) {
+                    warn!("Could not save the credentials: {why}");
+                }
+                self.config.network.auth = Some(auth);
+                match build_client(&self.config.network, &self.launcher_dir) {
+                    Ok(client) => self.client = client,
+                    Err(why) => warn!("Could not use the new credentials: {why}"),
+                }
+                if let RunState::Close = self.check_for_patches() {
+                    return RunState::Close;
+                }
+            }
+            GUIMessage::Verify => {
+                self.repair_summary = Some(RepairSummary::default());
+                let run_state = self.check_for_patches();
+                self.repair_summary = None;
+                if let RunState::Close = run_state {
+                    return RunState::Close;
+                }
+            }
+            GUIMessage::Preview => {
+                self.preview = Some(PatchPreview::default());
+                let run_state = self.check_for_patches();
+                self.preview = None;
+                if let RunState::Close = run_state {
+                    return RunState::Close;
+                }
+            }
+            GUIMessage::SetChannel(channel) => {
+                if let Err(why) = self.set_channel(channel) {
+                    self.send_error(tr!("Could not switch channels"), &why);
+                    warn!("Could not switch channels: {why}");
+                } else if let RunState::Close = self.check_for_patches() {
+                    return RunState::Close;
+                }
+            }
+            GUIMessage::SetComponents(components) => {
+                self.config.patch.components = components;
+                if let Err(why) = self.config.save(self.launcher_dir.join(CONFIG_FILE)) {
+                    warn!("Could not save the chosen components: {why}");
+                }
+                if let RunState::Close = self.check_for_patches() {
+                    return RunState::Close;
+                }
+            }
+            GUIMessage::SetGameLanguages(languages) => {
+                self.config.patch.languages = languages;
+                if let Err(why) = self.config.save(self.launcher_dir.join(CONFIG_FILE)) {
+                    warn!("Could not save the chosen language packs: {why}");
+                }
+                if let RunState::Close = self.check_for_patches() {
+                    return RunState::Close;
+                }
+            }
+            GUIMessage::Rollback => {
+                // Putting the old files back while the game has them open
+                // would leave a mix of both versions
+                if self.is_game_running() {
+                    self.send_status(PatchStatus::Error);
+                    self.send_info(tr!("Close the game to restore the previous version"));
+                    return RunState::Continue;
+                }
+                self.send_status(PatchStatus::Working);
+                self.send_info(tr!("Restoring the previous version"));
+                match backup::restore(self).map_err(PatcherError::from) {
+                    Ok(restored) => {
+                        self.send_status(PatchStatus::Finished);
+                        self.send_download(
+                            tr!(
+                                "Restored the previous version ({restored} files)",
+                                restored = restored
+                            ),
+                            1.,
+                        );
+                    }
+                    Err(why) => {
+                        self.send_error(tr!("Could not restore the previous version"), &why);
+                        warn!("Could not restore the previous version: {why}");
+                    }
+                }
+            }
+            GUIMessage::ScanForeignFiles => {
+                self.send_status(PatchStatus::Working);
+                self.send_info(tr!("Scanning for unexpected files"));
+                match integrity::scan(self).map_err(PatcherError::from) {
+                    Ok(files) => {
+                        self.send_status(PatchStatus::Finished);
+                        self.send_download(
+                            tr!("Found {count} unexpected files", count = files.len()),
+                            1.,
+                        );
+                        if !files.is_empty() {
+                            let files = files
+                                .iter()
+                                .map(|path| path.to_string_lossy().into_owned())
+                                .collect();
+                            self.send(PatchMessage::ForeignFiles(files));
+                        }
+                    }
+                    Err(why) => {
+                        self.send_error(
+                            tr!("Could not scan the game files: {why}", why = why),
+                            &why,
+                        );
+                    }
+                }
+            }
+            GUIMessage::QuarantineFiles(files) => {
+                // Files the game has open can't be moved away
+                if self.is_game_running() {
+                    self.send_status(PatchStatus::Error);
+                    self.send_info(tr!("Close the game to quarantine files"));
+                    return RunState::Continue;
+                }
+                let files = files.into_iter().map(PathBuf::from).collect::<Vec<_>>();
+                match integrity::quarantine(self, &files).map_err(PatcherError::from) {
+                    Ok(moved) => self.send_download(
+                        tr!(
+                            "Moved {moved} files to {dir}",
+                            moved = moved,
+                            dir = QUARANTINE_DIR
+                        ),
+                        1.,
+                    ),
+                    Err(why) => {
+                        self.send_error(tr!("Could not quarantine files: {why}", why = why), &why);
+                    }
+                }
+            }
+            GUIMessage::Uninstall { everything } => {
+                // The game's files can't be removed while it has them open
+                if self.is_game_running() {
+                    self.send_status(PatchStatus::Error);
+                    self.send_info(tr!("Close the game to uninstall it"));
+                    return RunState::Continue;
+                }
+                self.send_status(PatchStatus::Working);
+                self.send_info(tr!("Uninstalling the game"));
+                match uninstall::run(self, everything).map_err(PatcherError::from) {
+                    Ok(removed) => {
+                        info!("Removed {removed} files and directories");
+                        self.send_status(PatchStatus::Uninstalled);
+                        self.send_download(tr!("The game was uninstalled"), 1.);
+                    }
+                    Err(why) => {
+                        self.send_error(tr!("Could not uninstall the game"), &why);
+                        warn!("Could not uninstall the game: {why}");
+                    }
+                }
+            }
+            GUIMessage::MoveInstall(target) => match self.move_game(&target) {
+                Ok(()) => {
+                    // Make sure everything arrived
+                    self.repair_summary = Some(RepairSummary::default());
+                    let run_state = self.check_for_patches();
+                    self.repair_summary = None;
+                    if let RunState::Close = run_state {
+                        return RunState::Close;
+                    }
+                }
+                Err(why) => {
+                    self.send_error(tr!("Could not move the game"), &why);
+                    warn!("Could not move the game: {why}");
+                }
+            },
+            GUIMessage::SelectProfile(name) => {
+                let result = self.apply_profile(&name).and_then(|_| {
+                    self.config.save(self.launcher_dir.join(CONFIG_FILE))?;
+                    Ok(())
+                });
+                self.send_profiles();
+                match result {
+                    Ok(()) => {
+                        if let RunState::Close = self.check_for_patches() {
+                            return RunState::Close;
+                        }
+                    }
+                    Err(why) => {
+                        self.send_error(
+                            tr!("Could not switch to profile '{name}'", name = name),
+                            &why,
+                        );
+                        warn!("Could not switch to profile '{name}': {why}");
+                    }
+                }
+            }
+            GUIMessage::ApproveHooks(hooks) => {
+                let approved = &mut self.config.game.approved_hooks;
+                for hook in hooks {
+                    if !approved.contains(&hook) {
+                        approved.push(hook);
+                    }
+                }
+                if let Err(why) = self.config.save(self.launcher_dir.join(CONFIG_FILE)) {
+                    // They're approved for now, and will be asked about again
+                    // next time
+                    warn!("Could not save the approved hooks: {why}");
+                }
+                return self.handle_message(GUIMessage::Play);
+            }
+            GUIMessage::SelectRealm(name) => {
+                self.config.game.realm = Some(name);
+                if let Err(why) = self.config.save(self.launcher_dir.join(CONFIG_FILE)) {
+                    warn!("Could not save the chosen realm: {why}");
+                    self.send_error(tr!("Could not save the settings"), &why.into());
+                }
+            }
+            GUIMessage::MeasureRealms => self.measure_realms(),
+            GUIMessage::TestConnection => {
+                self.send_info(tr!("Testing the connection..."));
+                let report = connection_test::run(self);
+                info!("{report}");
+                self.send(PatchMessage::ConnectionReport(report));
+            }
+            GUIMessage::OptimizeArchives => {
+                // Changing the archives while the game has them open would
+                // damage them
+                if self.is_game_running() {
+                    self.send_status(PatchStatus::Error);
+                    self.send_info(tr!("Close the game to optimize the archives"));
+                    return RunState::Continue;
+                }
+                self.send_status(PatchStatus::Working);
+                self.send_info(tr!("Optimizing archives"));
+                match fragmentation::optimize(self).map_err(PatcherError::from) {
+                    Ok((0, _)) => {
+                        self.send_status(PatchStatus::Finished);
+                        self.send_download(tr!("The archives are already optimized"), 1.);
+                    }
+                    Ok((optimized, freed)) => {
+                        self.send_status(PatchStatus::Finished);
+                        self.send_download(
+                            tr!(
+                                "Optimized {archives} archives, freeing {size}",
+                                archives = optimized,
+                                size = byte_string(freed)
+                            ),
+                            1.,
+                        );
+                    }
+                    Err(why) => {
+                        self.send_error(tr!("Could not optimize the archives"), &why);
+                        warn!("Could not optimize the archives: {why}");
+                    }
+                }
+            }
+            GUIMessage::CrashReports { send, remember } => {
+                if remember {
+                    self.config.diagnostics.crash_reports = if send {
+                        CrashReports::Send
+                    } else {
+                        CrashReports::Never
+                    };
+                    if let Err(why) = self.config.save(self.launcher_dir.join(CONFIG_FILE)) {
+                        // It will be asked again next time
+                        warn!("Could not save the crash report choice: {why}");
+                    }
+                    self.send_settings();
+                }
+                self.handle_crash_reports(send);
+            }
+            GUIMessage::UpdateSettings(settings) => {
+                // The game needs to be checked if it is somewhere else or
+                // on another channel now
+                let previous = self.config.settings();
+                let repatch =
+                    settings.game_dir != previous.game_dir || settings.channel != previous.channel;
+                let result = self.update_settings(settings);
+                self.send_settings();
+                if !self.config.profiles.is_empty() {
+                    self.send_profiles();
+                }
+                match result {
+                    Ok(()) if repatch => {
+                        if let RunState::Close = self.check_for_patches() {
+                            return RunState::Close;
                         }
                     }
+                    Ok(()) => self.send_download(tr!("Settings saved"), 1.),
+                    Err(why) => {
+                        self.send_error(tr!("Could not save the settings"), &why);
+                        warn!("Could not save the settings: {why}");
+                    }
                 }
-                GUIMessage::Play => {
-                    self.send_download("Starting game...".to_string(), 1.);
-                    match self.start_game() {
-                        Ok(_) => {
-                            // The game is running and we can exit
-                            self.send_download("Game has started!".to_string(), 1.);
-                            std::thread::sleep(std::time::Duration::from_secs(3));
-                            return;
+            }
+            // Only expected before the first patch, see first_run_setup()
+            GUIMessage::FinishSetup { .. } => {}
+            // The countdown has already stopped, see auto_launch_countdown()
+            GUIMessage::CancelAutoLaunch => {}
+            GUIMessage::AccountLogin { username, password } => {
+                let url = match &self.config.account.login_url {
+                    Some(url) => url.clone(),
+                    None => return RunState::Continue,
+                };
+                self.send_info(tr!("Logging in"));
+                match session::login(self, &url, &username, &password) {
+                    Ok(session) => {
+                        if let Err(why) = session::save(self, &session) {
+                            warn!("Could not save the session: {why}");
                         }
+                        return self.play(Some(session.token));
+                    }
+                    Err(why) => {
+                        warn!("Could not log in: {why}");
+                        self.send(PatchMessage::AccountLogin(Some(why.to_string())));
+                    }
+                }
+            }
+            GUIMessage::Play => {
+                // Games with accounts need a session to start with
+                let token = match &self.config.account.login_url {
+                    Some(_) => match session::load(self).filter(Session::is_valid) {
+                        Some(session) => Some(session.token),
+                        None => {
+                            self.send(PatchMessage::AccountLogin(None));
+                            return RunState::Continue;
+                        }
+                    },
+                    None => None,
+                };
+                return self.play(token);
+            }
+            GUIMessage::PlayTarget(id) => {
+                match LAUNCH_TARGETS.iter().find(|target| target.id == id) {
+                    Some(target) => match self.start_target(target) {
+                        Ok(()) => self.send_info(tr!(
+                            "{name} has started",
+                            name = crate::i18n::translate(target.name)
+                        )),
                         Err(why) => {
-                            // Could not launch the game, need to stay open to inform user
-                            self.send_status(PatchStatus::Error);
-                            self.send_error("Failed to launch the game".to_string());
-                            eprintln!("Failed to launch game: {why}");
+                            self.send_error(
+                                tr!(
+                                    "Failed to start {name}",
+                                    name = crate::i18n::translate(target.name)
+                                ),
+                                &why,
+                            );
+                            warn!("Failed to start {}: {why}", target.exe);
                         }
-                    }
+                    },
+                    None => warn!("Asked to start an unknown program: {id}"),
                 }
-                // Close if the GUI closes
-                GUIMessage::Close => return,
             }
+            // Close if the GUI closes
+            GUIMessage::Close => return RunState::Close,
+        }
 
-            message = match self.recv() {
-                Ok(m) => m,
-                Err(why) => {
-                    eprintln!("{why}");
-                    return;
+        RunState::Continue
+    }
+
+    /// Starts the game, with a game account session if there is one, and
+    /// closes the launcher once it is running
+    fn play(&mut self, token: Option<String>) -> RunState {
+        if self.is_game_running() {
+            self.send_info(tr!("The game is already running"));
+            return RunState::Continue;
+        }
+        let unapproved = self.unapproved_hooks();
+        if !unapproved.is_empty() {
+            self.send(PatchMessage::ConfirmHooks(unapproved));
+            return RunState::Continue;
+        }
+        if self.config.game.quick_check {
+            let damaged = self.quick_check();
+            if !damaged.is_empty() {
+                self.send(PatchMessage::RepairNeeded(damaged));
+                return RunState::Continue;
+            }
+        }
+        self.set_state(WorkerState::Launching);
+        self.send_download(tr!("Starting game..."), 1.);
+        self.run_hook("pre-launch", &self.config.game.pre_launch_hook);
+        match self.start_game(token.as_deref()) {
+            Ok(mut game) if self.big_picture => {
+                self.send_download(tr!("Game has started!"), 1.);
+                self.wait_for_game(&mut game)
+            }
+            Ok(mut game) if self.config.game.monitor_launch => {
+                self.send_download(tr!("Game has started!"), 1.);
+                self.monitor_game(&mut game)
+            }
+            Ok(mut game) if !self.config.game.post_exit_hook.is_empty() => {
+                self.send_download(tr!("Game has started!"), 1.);
+                self.after_game(&mut game);
+                RunState::Close
+            }
+            Ok(mut game) => {
+                // The game is running and we can exit
+                game.detach();
+                self.send_download(tr!("Game has started!"), 1.);
+                std::thread::sleep(std::time::Duration::from_secs(3));
+                RunState::Close
+            }
+            Err(why) => {
+                // Could not launch the game, need to stay open to inform user
+                self.send_error(tr!("Failed to launch the game"), &why);
+                warn!("Failed to launch game: {why}");
+                RunState::Continue
+            }
+        }
+    }
+
+    /// Does a single thing without a GUI, then stops. The worker is dropped
+    /// afterwards, which closes its channel.
+    pub fn run_once(mut self, message: GUIMessage) {
+        self.handle_message(message);
+    }
+
+    /// Whether the last patch already installed the available version of the
+    /// game with the chosen components, or the version the game is pinned to
+    fn is_up_to_date(
+        &self,
+        last_patch: Option<&LastPatch>,
+        available_version: Option<&str>,
+    ) -> bool {
+        // Repairing and previewing need to look at every file, and an
+        // interrupted patch needs to be finished
+        if self.repair_summary.is_some()
+            || self.preview.is_some()
+            || self.self_dir.join(JOURNAL).exists()
+        {
+            return false;
+        }
+
+        let (last_patch, available_version) = match (last_patch, available_version) {
+            (Some(last_patch), Some(available_version)) => (last_patch, available_version),
+            _ => return false,
+        };
+        let installed_version = match &last_patch.version {
+            Some(installed_version) => installed_version,
+            None => return false,
+        };
+
+        if last_patch.channel != self.config.patch.channel
+            || last_patch.components != self.config.patch.components
+            || last_patch.languages != self.config.patch.languages
+        {
+            return false;
+        }
+
+        if self.config.patch.pinned_version.as_ref() == Some(installed_version) {
+            info!("Staying on pinned version {installed_version}");
+            return true;
+        }
+
+        game_version::compare(installed_version, available_version) != Ordering::Less
+    }
+
+    /// Switches to another release channel, and remembers it for next time
+    fn set_channel(&mut self, channel: String) -> Result<(), PatcherError> {
+        let (patchlist_url, patch_url) = channel_urls(&self.server_url, &channel)?;
+        self.patchlist_url = patchlist_url;
+        self.patch_url = patch_url;
+        if let Some(profile) = self.active_profile() {
+            profile.channel = channel.clone();
+        }
+        self.config.patch.channel = channel;
+        self.config.save(self.launcher_dir.join(CONFIG_FILE))?;
+        Ok(())
+    }
+
+    fn active_profile(&mut self) -> Option<&mut Profile> {
+        let name = self.config.patch.profile.as_ref()?;
+        self.config.profiles.iter_mut().find(|p| &p.name == name)
+    }
+
+    /// Switches to the game directory, patch server and release channel of
+    /// an install profile
+    fn apply_profile(&mut self, name: &str) -> Result<(), PatcherError> {
+        let profile = self
+            .config
+            .profiles
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("There is no profile named '{name}'"))?
+            .clone();
+
+        let server_url = parse_server_url(
+            self.server_override
+                .as_deref()
+                .or(profile.server.as_deref())
+                .or(self.config.patch.server.as_deref())
+                .unwrap_or(PATCH_SERVER),
+        )?;
+        if self.config.network.https_only && server_url.scheme() != "https" {
+            return Err(format!("Patch server {server_url} does not use HTTPS").into());
+        }
+        let (patchlist_url, patch_url) = channel_urls(&server_url, &profile.channel)?;
+
+        self.game_base_url = server_url.join(BASE_DIR)?;
+        self.game_zip_url = self.game_base_url.join(BASE_ZIP)?;
+        self.status_url = server_url.join(META_DIR)?.join(STATUS)?;
+        self.server_url = server_url;
+        self.patchlist_url = patchlist_url;
+        self.patch_url = patch_url;
+        self.self_dir = self.launcher_dir.join(
+            profile
+                .game_dir
+                .unwrap_or_else(|| PathBuf::from(&profile.name)),
+        );
+        self.config.patch.channel = profile.channel;
+        self.config.patch.profile = Some(profile.name);
+        Ok(())
+    }
+
+    /// Takes over the settings from the settings screen, and remembers them
+    /// for next time
+    fn update_settings(&mut self, mut settings: Settings) -> Result<(), PatcherError> {
+        // Don't change anything if the channel doesn't exist
+        channel_urls(&self.server_url, &settings.channel)?;
+
+        settings.game_dir = settings.game_dir.map(|dir| self.stored_path(dir));
+
+        self.config.apply_settings(settings);
+        match self.config.patch.profile.clone() {
+            Some(name) => self.apply_profile(&name)?,
+            None => {
+                let (patchlist_url, patch_url) =
+                    channel_urls(&self.server_url, &self.config.patch.channel)?;
+                self.patchlist_url = patchlist_url;
+                self.patch_url = patch_url;
+                self.self_dir = match &self.config.patch.game_dir {
+                    Some(game_dir) => self.launcher_dir.join(game_dir),
+                    None => self.launcher_dir.clone(),
+                };
+            }
+        }
+        self.throttle
+            .set_limit(self.config.network.download_limit());
+        if self.hash_pool.current_num_threads() != self.config.hash_threads() {
+            self.hash_pool = build_hash_pool(&self.config)?;
+        }
+        // Downloads which are already waiting keep the old limit, which is
+        // fine since nothing is downloading while settings change
+        self.connections = Arc::new(Semaphore::new(self.config.simultaneous_downloads()));
+        crate::i18n::set_language(&self.config.ui.language);
+
+        self.config.save(self.launcher_dir.join(CONFIG_FILE))?;
+        Ok(())
+    }
+
+    /// Sends the patch notes of a version to the GUI, if there are any
+    fn send_changelog(&self, version: &str) {
+        match changelog::fetch(self, version) {
+            Ok(Some(text)) => self.send(PatchMessage::Changelog {
+                version: version.to_string(),
+                text,
+            }),
+            Ok(None) => {}
+            Err(why) => warn!("Could not get the patch notes for {version}: {why}"),
+        }
+    }
+
+    /// Tells the GUI which patch server is in use if it isn't the usual one
+    fn send_server(&self) {
+        let custom = self.server_url.as_str() != PATCH_SERVER;
+        self.send(PatchMessage::Server(
+            custom.then(|| self.server_url.to_string()),
+        ));
+    }
+
+    fn send_settings(&self) {
+        let mut settings = self.config.settings();
+        settings.torrent_client_found = torrent::client_found(&self.config.network.torrent_client);
+        self.send(PatchMessage::Settings(settings));
+    }
+
+    /// Sends reports of the launcher crashing since it last ran if the
+    /// player agreed to that, or asks them about it
+    fn check_crash_reports(&self) {
+        // Without anywhere to send them, they are only for support requests
+        if crash_report::pending(&self.log_dir).is_empty()
+            || crash_upload::report_url(&self.config.diagnostics).is_none()
+        {
+            return;
+        }
+        match self.config.diagnostics.crash_reports {
+            CrashReports::Ask => self.send(PatchMessage::CrashReports),
+            CrashReports::Send => self.handle_crash_reports(true),
+            CrashReports::Never => self.handle_crash_reports(false),
+        }
+    }
+
+    /// Sends the pending crash reports, or sets them aside so they aren't
+    /// offered again
+    fn handle_crash_reports(&self, send: bool) {
+        let reports = crash_report::pending(&self.log_dir);
+        let result = match crash_upload::report_url(&self.config.diagnostics) {
+            Some(url) if send => crash_upload::send(self, url, reports),
+            _ => reports
+                .iter()
+                .try_for_each(|report| crash_report::set_aside(report)),
+        };
+        if let Err(why) = result {
+            warn!("Could not deal with the crash reports: {why}");
+        }
+    }
+
+    fn measure_realms(&self) {
+        let selected = self.selected_realm().map(|realm| realm.name.clone());
+        realm::measure(&self.config.realms, selected, self.tx.clone());
+    }
+
+    /// The realm the player chose, or the first one if they haven't chosen
+    /// one which still exists
+    fn selected_realm(&self) -> Option<&Realm> {
+        let realms = &self.config.realms;
+        self.config
+            .game
+            .realm
+            .as_ref()
+            .and_then(|name| realms.iter().find(|realm| realm.name == *name))
+            .or_else(|| realms.first())
+    }
+
+    fn send_profiles(&self) {
+        self.send(PatchMessage::Profiles {
+            names: self
+                .config
+                .profiles
+                .iter()
+                .map(|p| p.name.clone())
+                .collect(),
+            active: self.config.patch.profile.clone(),
+            channel: self.config.patch.channel.clone(),
+        });
+    }
+
+    /// Runs the patch routine and reports how it went to the GUI
+    fn check_for_patches(&mut self) -> RunState {
+        self.send_status(PatchStatus::Working);
+        let started = Instant::now();
+        self.went_offline.set(None);
+        let result = self.patch_routine();
+        let (outcome, failure) = match (&result, self.went_offline.get()) {
+            (Ok(_), None) => (Outcome::Finished, None),
+            (Ok(_), Some(category)) => (Outcome::Offline, Some(category)),
+            (Err(why), _) => (
+                Outcome::Failed,
+                Some(error::details(&why.internal_error).category),
+            ),
+        };
+        self.telemetry.record(self, started, outcome, failure);
+
+        match result {
+            Ok(RunState::Continue) if self.auto_launch && self.patched.get() => {
+                self.auto_launch = false;
+                self.auto_launch_countdown()
+            }
+            Ok(run_state) => run_state,
+            Err(why) if why.is_unauthorized() => {
+                // Let the GUI ask for a username and password
+                self.send_status(PatchStatus::Unauthorized);
+                self.send_info(tr!("Please log in to the patch server"));
+                error!("{:?}", why.internal_error);
+                RunState::Continue
+            }
+            Err(why) if why.is_launcher_outdated() => {
+                // The status goes last, since sending the error marks the
+                // attempt as failed and would offer a retry instead of play
+                self.send_error(why.friendly_message, &why.internal_error);
+                self.send_status(PatchStatus::LauncherOutdated);
+                RunState::Continue
+            }
+            Err(why) => {
+                // Display the error, which also tells the GUI it failed
+                self.send_error(why.friendly_message, &why.internal_error);
+
+                // Log more detailed error info to the terminal
+                error!("{:?}", why.internal_error);
+                RunState::Continue
+            }
+        }
+    }
+
+    /// Counts down before starting the game by itself. Anything the GUI
+    /// asks for in the meantime stops the countdown and is done instead.
+    fn auto_launch_countdown(&mut self) -> RunState {
+        for remaining in (1..=AUTO_LAUNCH_SECONDS).rev() {
+            self.send(PatchMessage::AutoLaunch(Some(remaining)));
+            match self.rx.recv_timeout(std::time::Duration::from_secs(1)) {
+                Ok(message) => {
+                    self.send(PatchMessage::AutoLaunch(None));
+                    return self.handle_message(message);
                 }
-            };
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return RunState::Close,
+            }
         }
+        self.send(PatchMessage::AutoLaunch(None));
+        self.handle_message(GUIMessage::Play)
     }
 
     fn patch_routine(&mut self) -> Result<RunState, PatchError> {
+        let started = Instant::now();
+        self.report = PatchReport::default();
+
         if let RunState::Close = self.check_patcher_aecoupdate()? {
             return Ok(RunState::Close);
         }
 
-        self.send_info("Checking server status".to_string());
-        let server_status = download::server_status(self)?;
+        self.send_info(tr!("Checking server status"));
+        let (server_status, server_details) = match download::server_status(self) {
+            Ok(server_status) => server_status,
+            Err(why) => return self.fall_back_to_offline(why),
+        };
+        self.send(PatchMessage::ServerDetails(server_details));
+
+        // Fetched before anything else can fail, so that news about
+        // maintenance shows up during it
+        match news::fetch(self) {
+            Ok(news) => self.send(PatchMessage::News(news)),
+            Err(why) => warn!("Could not get the news: {why}"),
+        }
+
+        // The launcher updates itself first when the server releases it
+        // separately from the game, even if the game can't be patched now
+        match self_update::check_release(self) {
+            Ok(separate) => self.separate_launcher_updates = separate,
+            Err(why) => {
+                warn!("Could not check for launcher updates: {why}");
+                self.separate_launcher_updates = false;
+            }
+        }
+        if let RunState::Close = self.start_updated_launcher()? {
+            return Ok(RunState::Close);
+        }
 
         match server_status {
-            ServerStatus::Online => self.send_info("Server is online".to_string()),
+            ServerStatus::Online => self.send_info(tr!("Server is online")),
             ServerStatus::Maintenance => {
-                return Err(Box::<dyn Error>::from(format!(
-                    "Received server status {server_status:?}"
-                ))
-                .to_patch_error_level("Server is down for maintenance", PatchErrorLevel::Low));
+                // The GUI shows when it will be over
+                info!("Received server status {server_status:?}");
+                self.send_status(PatchStatus::Maintenance);
+                return Ok(RunState::Continue);
             }
         }
 
-        // Make sure the game is installed, and install it if not
+        // Make sure the game is installed, and install it if not. Nothing
+        // else can be previewed until it is.
+        if self.preview.is_some() && !self.is_game_present() {
+            let base_size = download::content_length(self, self.game_zip_url.clone())
+                .map_err(|why| why.to_patch_error("Failed to get base game size"))?;
+            self.finish_preview(PatchPreview {
+                base_game: true,
+                download_size: base_size.unwrap_or_default(),
+                ..PatchPreview::default()
+            });
+            return Ok(RunState::Continue);
+        }
+        if self.preview.is_none() && self.may_elevate && elevation::needed(&self.self_dir) {
+            return self.patch_elevated();
+        }
+        // Taken only now, since the elevated copy above takes it for itself
+        let _game_lock = self.lock_game_dir().map_err(|why| {
+            PatchError::from_error(why, "Another launcher is changing the game folder")
+        })?;
         self.ensure_game_installed()?;
 
+        let available_version = match game_version::available(self) {
+            Ok(available_version) => available_version,
+            Err(why) => return self.fall_back_to_offline(why),
+        };
+        let last_patch = last_patch::load(self);
+        self.send(PatchMessage::Versions {
+            installed: last_patch.as_ref().and_then(|p| p.version.clone()),
+            available: available_version.clone(),
+        });
+
+        // Checking every file isn't needed if nothing has changed since the
+        // last patch
+        if self.is_up_to_date(last_patch.as_ref(), available_version.as_deref()) {
+            self.send_status(PatchStatus::Finished);
+            self.check_eco_ini()
+                .map_err(|why| why.to_patch_error("Failed while checking eco.ini"))?;
+            return Ok(RunState::Continue);
+        }
+
+        // Show what the update changes while it is being installed
+        if let Some(version) = &available_version {
+            let is_newer = match last_patch.as_ref().and_then(|p| p.version.as_deref()) {
+                Some(installed) => game_version::compare(installed, version) == Ordering::Less,
+                None => true,
+            };
+            if is_newer {
+                self.send_changelog(version);
+            }
+        }
+
         // Get patch information from the patch server
-        let patch = download::patch_metadata(self)?;
+        let Patchlist { root: patch, modes } = match download::patch_metadata(self) {
+            Ok(patchlist) => patchlist,
+            Err(why) => return self.fall_back_to_offline(why),
+        };
 
-        // Apply patches for all platforms and for this specific platform
-        for platform in ["all", &get_platform()] {
-            // Compare local files against the patch data, and update files if needed
-            if let Some(platform_dir) = subdir_by_name(&patch, platform) {
-                check_platform_patches(self, platform_dir).map_err(|why| {
-                    why.to_patch_error(&format!("Failed to check files for platform '{platform}'"))
+        // Changing the archives while the game has them open would damage
+        // them
+        if self.preview.is_none() && self.is_game_running() {
+            self.send_status(PatchStatus::GameRunning);
+            self.send_info(tr!("Close the game to continue patching"));
+            return Ok(RunState::Continue);
+        }
+
+        // Continue where the last patch left off, if it was interrupted
+        self.journal = Journal::open(self);
+        self.hash_cache = HashCache::load(&self.self_dir.join(HASH_CACHE));
+        self.backup = Backup::new(self.journal.is_resumed());
+        self.ignore_list = IgnoreList::load(&self.self_dir);
+        self.file_modes = modes;
+
+        // Only the files which changed since the last patch need checking
+        self.manifest_diff = ManifestDiff::new(&self.self_dir, last_patch.as_ref());
+        if last_patch.is_some() && self.repair_summary.is_none() && self.preview.is_none() {
+            // Running out of space part way through would leave the game
+            // half patched, so make sure the update fits before touching
+            // anything
+            if let Some(update_size) = self.estimate_update_size(&patch) {
+                ensure_free_space(&self.self_dir, update_size).map_err(|why| {
+                    PatchError::from_error(why, "There is not enough space for the update")
                 })?;
-            } else {
-                println!("No patch directory found for platform \'{platform}\'");
             }
         }
 
-        self.send_status(PatchStatus::Finished);
+        // Fetch the changed files all at once if the server bundled them.
+        // Whatever isn't in a bundle is downloaded one file at a time.
+        let installed_version = last_patch.as_ref().and_then(|p| p.version.as_deref());
+        if self.repair_summary.is_none() && self.preview.is_none() {
+            if let Some(installed) = installed_version {
+                match bundle::prefetch(self, installed) {
+                    Ok(0) => {}
+                    Ok(cached) => info!("Got {cached} files from a bundle"),
+                    Err(why) => warn!("Could not use the bundle of changed files: {why}"),
+                }
+            }
+        }
 
-        // Open the new patcher if there is one
-        if let Some(p) = &self.updated_patcher {
-            match start_detached_process(&[p]) {
-                // Close the patcher if the new patcher opened successfully
-                Ok(_) => return Ok(RunState::Close),
-                Err(why) => return Err(why.to_patch_error("Could not start updated launcher")),
+        self.send(PatchMessage::Components {
+            available: available_components(&patch),
+            enabled: self.config.patch.components.clone(),
+        });
+        self.send(PatchMessage::GameLanguages {
+            available: available_languages(&patch),
+            enabled: self.config.patch.languages.clone(),
+        });
+
+        // Find out about anything which can't be changed before changing
+        // anything
+        if self.preview.is_none() {
+            preflight::validate(self, &patch)
+                .map_err(|why| PatchError::from_error(why, "Some game files can't be changed"))?;
+        }
+
+        // Apply patches for all platforms, for this specific platform, and
+        // for the optional components which were chosen
+        let platform_dirs =
+            installed_dirs(&self.config.patch.components, &self.config.patch.languages)
+                .into_iter()
+                .filter_map(|platform| {
+                    let platform_dir = subdir_by_name(&patch, &platform);
+                    if platform_dir.is_none() {
+                        info!("No patch directory found for platform \'{platform}\'");
+                    }
+                    platform_dir
+                })
+                .collect::<Vec<_>>();
+
+        // Progress carries on from one directory to the next instead of
+        // starting over for each
+        let total_files = platform_dirs
+            .iter()
+            .map(|dir| get_total_files_in_patch(dir))
+            .sum();
+        let mut checked_files = 0;
+        for platform_dir in platform_dirs {
+            // Compare local files against the patch data, and update files if needed
+            check_platform_patches(self, platform_dir, checked_files, total_files).map_err(
+                |why| {
+                    PatchError::from_error(
+                        why,
+                        &format!("Failed to check files for platform '{}'", platform_dir.name),
+                    )
+                },
+            )?;
+            checked_files += get_total_files_in_patch(platform_dir);
+        }
+        send_checked_summary(self, total_files);
+
+        if let Some(preview) = self.preview.take() {
+            self.finish_preview(preview);
+            return Ok(RunState::Continue);
+        }
+
+        // Everything was patched, so the downloads won't be needed again
+        if let Err(why) = cache::clear(self) {
+            warn!("Could not clear download cache: {why}");
+        }
+        if let Err(why) = staging::clear(self) {
+            warn!("Could not clear staging directory: {why}");
+        }
+
+        // Get rid of files which were removed from the game since it was last
+        // patched
+        if let Some(last_patch) = &last_patch {
+            match prune::removed_files(self, last_patch, &patch) {
+                Ok(0) => {}
+                Ok(removed) => info!("Removed {removed} obsolete files"),
+                Err(why) => warn!("Could not remove obsolete files: {why}"),
             }
         }
 
+        // Remember this so the game can still be played if the server can't
+        // be reached next time
+        if let Err(why) = last_patch::save(self, patch, available_version.clone()) {
+            warn!("Could not save patch result: {why}");
+        }
+
+        let mut patch_report = std::mem::take(&mut self.report);
+        if let Err(why) = report::finish(self, &mut patch_report, started, available_version) {
+            warn!("Could not save patch report: {why}");
+        }
+        self.send(PatchMessage::Summary(patch_report));
+
+        match self.repair_summary.take() {
+            Some(summary) => self.send_status(PatchStatus::Repaired(summary)),
+            None => self.send_status(PatchStatus::Finished),
+        }
+
+        // The next patch needs to check everything again
+        self.journal = Journal::default();
+        if let Err(why) = journal::clear(self) {
+            warn!("Could not clear patch journal: {why}");
+        }
+
+        // Open the new patcher if there is one
+        if let RunState::Close = self.start_updated_launcher()? {
+            return Ok(RunState::Close);
+        }
+
         self.check_eco_ini()
             .map_err(|why| why.to_patch_error("Failed while checking eco.ini"))?;
 
         Ok(RunState::Continue)
     }
 
+    /// Starts the launcher which was downloaded during this patch, if there
+    /// is one, and returns `RunState::Close` so this one closes
+    fn start_updated_launcher(&mut self) -> Result<RunState, PatchError> {
+        let update = match &self.updated_patcher {
+            Some(update) => update,
+            None => return Ok(RunState::Continue),
+        };
+        // It could have been changed since its signature was checked
+        if !update.is_intact() {
+            if let Err(why) = std::fs::remove_file(&update.path) {
+                warn!("Could not remove the changed launcher update: {why}");
+            }
+            let why = PatcherError::Verification(
+                "The launcher update was changed after it was verified".into(),
+            );
+            return Err(why.to_patch_error("Could not start updated launcher"));
+        }
+        // It takes the lock when it starts, and this one is done with it
+        let command_line = self_update::restart_command(&update.path);
+        self.instance_lock = None;
+        match start_detached_process(&command_line) {
+            // Close the patcher if the new patcher opened successfully
+            Ok(_) => Ok(RunState::Close),
+            Err(why) => Err(why.to_patch_error("Could not start updated launcher")),
+        }
+    }
+
+    fn is_game_running(&self) -> bool {
+        running::is_running(&self.self_dir.join(GAME_EXE))
+    }
+
     /// Checks whether the game is in the same directory as this program
     fn is_game_present(&self) -> bool {
         let game_path = self.self_dir.join(GAME_EXE);
         game_path.is_file()
     }
 
-    /// Unpacks the base game ZIP to the same directory as this program
-    fn unpack_base(&self, base_file: std::fs::File) -> Result<(), Box<dyn Error>> {
+    /// Unpacks the base game ZIP to the same directory as this program. The
+    /// files are extracted in parallel on the hash pool, so no more at once
+    /// than the configured hashing threads.
+    fn unpack_base(&self, base_file: std::fs::File) -> Result<(), PatcherError> {
         // Open base game archive
-        let mut archive = zip::read::ZipArchive::new(base_file)?;
+        let mut archive = zip::read::ZipArchive::new(SharedFile::new(base_file)?)?;
 
-        self.send_download("Extracting base game".to_string(), 0.);
+        self.send_download(tr!("Extracting base game"), 0.);
 
-        // Modified from zip/src/read.rs:extract
-        // to provide real-time feedback to the GUI
         let total_archive_count = archive.len();
 
         // Calculate the total number of bytes to be extracted
@@ -262,104 +1579,444 @@ impl PatchWorker {
             total_archive_bytes += file.size();
         }
 
+        // Make sure everything will fit on disk before extracting any of it
+        ensure_free_space(&self.self_dir, total_archive_bytes)?;
+
         // Get total number of bytes as a human readable string
         let pretty_total = byte_string(total_archive_bytes);
 
-        // Keep track of how many bytes have been decompressed so far
-        let mut decompressed_bytes = 0;
+        let extracted = unzip::extract_all(
+            &self.hash_pool,
+            &archive,
+            &self.self_dir,
+            |files_done, decompressed_bytes| {
+                // Report progress in terms of bytes extracted
+                let progress = decompressed_bytes as f32 / total_archive_bytes as f32;
+                let pretty_decompressed = byte_string(decompressed_bytes);
+                self.send_download(
+                    tr!(
+                        "Extracted {done} of {count} files ({decompressed} / {total})",
+                        done = files_done,
+                        count = total_archive_count,
+                        decompressed = pretty_decompressed,
+                        total = pretty_total,
+                    ),
+                    progress,
+                );
+            },
+        )?;
+
+        integrity::save_base_files(&self.self_dir, &extracted)?;
+        self.send_download(tr!("Finished installing base game"), 1.);
 
-        for file_number in 0..total_archive_count {
-            // Report progress in terms of bytes extracted
-            let progress = decompressed_bytes as f32 / total_archive_bytes as f32;
-            let pretty_decompressed = byte_string(decompressed_bytes);
-            self.send_download(
-                format!(
-                    "Extracting file {} of {} ({pretty_decompressed} / {pretty_total})",
-                    file_number + 1,
-                    total_archive_count
-                ),
-                progress,
+        Ok(())
+    }
+
+    /// Unpacks the base game ZIP to the same directory as this program while
+    /// it is being downloaded. Progress is reported by the reader.
+    fn unpack_base_stream(&self, mut reader: impl Read) -> Result<(), PatcherError> {
+        let mut extracted = Vec::new();
+        let mut dir_modes = DirModes::default();
+        while let Some(mut file) = zip::read::read_zipfile_from_stream(&mut reader)? {
+            extracted.extend(
+                unzip::extract_entry(&self.self_dir, &mut file, &mut dir_modes)
+                    .map_err(|why| why as Box<dyn Error>)?,
             );
+        }
+        dir_modes.apply()?;
 
-            // Get the next file from the archive
-            let mut file = archive.by_index(file_number)?;
+        integrity::save_base_files(&self.self_dir, &extracted)?;
+        self.send_download(tr!("Finished installing base game"), 1.);
 
-            // Get its path and figure out where it should go on the system
-            let filepath = file.enclosed_name().ok_or("Invalid file path")?;
-            let outpath = self.self_dir.join(filepath);
+        Ok(())
+    }
 
-            if file.name().ends_with('/') {
-                // Create directories if needed
-                std::fs::create_dir_all(&outpath)?;
-            } else {
-                // Create parent directories if needed
-                if let Some(p) = outpath.parent() {
-                    if !p.exists() {
-                        std::fs::create_dir_all(&p)?;
-                    }
-                }
+    /// Has a copy of the launcher with administrator rights do the patching,
+    /// so that this one can start the game without them
+    fn patch_elevated(&mut self) -> Result<RunState, PatchError> {
+        self.send_info(tr!("Patching with administrator rights"));
+        let command = if self.repair_summary.is_some() {
+            "verify"
+        } else {
+            "patch"
+        };
+        let mut args = vec![command.to_string(), "--no-elevate".to_string()];
+        if let Some(server) = &self.server_override {
+            args.extend(["--server".to_string(), server.clone()]);
+        }
+        if self.portable {
+            args.push("--portable".to_string());
+        }
+        // The elevated copy takes the lock for itself while it patches
+        let had_lock = self.instance_lock.take().is_some();
+        let elevated = elevation::run_elevated(&self.self_exe, &args);
+        if had_lock {
+            self.instance_lock = InstanceLock::acquire(&self.launcher_dir).unwrap_or_else(|why| {
+                warn!("Could not lock the launcher directory again: {why}");
+                None
+            });
+        }
+        elevated.map_err(|why| {
+            PatchError::from_error(
+                why,
+                "The game folder can only be changed with administrator rights",
+            )
+        })?;
+
+        let last_patch = last_patch::load(self);
+        self.send(PatchMessage::Versions {
+            installed: last_patch.as_ref().and_then(|p| p.version.clone()),
+            available: None,
+        });
+        self.send_status(PatchStatus::Finished);
+        Ok(RunState::Continue)
+    }
 
-                // Copy extracted file to disk
-                let mut outfile = std::fs::File::create(&outpath)?;
-                std::io::copy(&mut file, &mut outfile)?;
+    /// Checks whether the game is installed and installs it if not
+    fn ensure_game_installed(&self) -> Result<(), PatchError> {
+        self.send_download(tr!("Checking game installation"), 1.);
+        if !self.is_game_present() {
+            self.send_download(tr!("Downloading game since it is not installed"), 0.);
+
+            // Make sure the download will fit before starting it. If the server
+            // doesn't say how large it is, find out the hard way.
+            let base_size = download::content_length(self, self.game_zip_url.clone())
+                .map_err(|why| why.to_patch_error("Failed to get base game size"))?;
+            if let Some(base_size) = base_size {
+                ensure_free_space(&self.self_dir, base_size)
+                    .map_err(|why| PatchError::from_error(why, "Failed to check disk space"))?;
             }
 
-            // Get and Set permissions
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Some(mode) = file.unix_mode() {
-                    std::fs::set_permissions(&outpath, std::fs::Permissions::from_mode(mode))?;
+            if self.config.network.stream_base_game {
+                // Extracting while downloading writes the files straight to
+                // the game directory, so everything they unpack to has to
+                // fit before starting. Otherwise, unpacking checks for itself.
+                let unpacked_size = match base_size {
+                    Some(base_size) => zip_size::unpacked_size(self, &self.game_zip_url, base_size)
+                        .unwrap_or_else(|why| {
+                            warn!("Could not get the unpacked base game size: {why}");
+                            None
+                        }),
+                    None => None,
+                };
+                if let Some(unpacked_size) = unpacked_size {
+                    ensure_free_space(&self.self_dir, unpacked_size)
+                        .map_err(|why| PatchError::from_error(why, "Failed to check disk space"))?;
                 }
+
+                // Download and extract the base game at the same time
+                let reader = download::game_base_stream(self)
+                    .map_err(|why| why.to_patch_error("Failed while downloading base game"))?;
+                self.unpack_base_stream(reader).map_err(|why| {
+                    PatchError::from_error(why, "Failed while installing base game")
+                })?;
+            } else {
+                // Download the base game
+                let game_base_file = download::game_base(self)
+                    .map_err(|why| why.to_patch_error("Failed while downloading base game"))?;
+
+                // Extract the base game to disk
+                self.unpack_base(game_base_file).map_err(|why| {
+                    PatchError::from_error(why, "Failed while unpacking base game")
+                })?;
             }
+        }
 
-            // Keep track of how many bytes have been extracted so far
-            decompressed_bytes += file.size();
+        Ok(())
+    }
+
+    fn finish_preview(&self, preview: PatchPreview) {
+        info!("{preview}");
+        for archive in &preview.archives {
+            info!("Archive to update: {archive}");
         }
+        self.send_status(PatchStatus::Preview(preview));
+    }
 
-        self.send_download("Finished installing base game".to_string(), 1.);
+    /// Tells the GUI how much the files which changed since the last patch
+    /// add up to, before any of them are downloaded. Returns that size, if
+    /// it could be found out.
+    fn estimate_update_size(&self, patch: &Directory) -> Option<u64> {
+        let urls = match self.manifest_diff.changed_urls(
+            &self.self_dir,
+            patch,
+            &self.config.patch.components,
+            &self.config.patch.languages,
+            &self.patch_url,
+        ) {
+            Ok(urls) => urls,
+            Err(why) => {
+                warn!("Could not estimate the update size: {why}");
+                return None;
+            }
+        };
+        info!("{} files changed since the last patch", urls.len());
+        if urls.is_empty() {
+            return Some(0);
+        }
 
-        Ok(())
+        self.send_info(tr!("Estimating update size"));
+        match download::total_content_length(self, urls) {
+            Ok(size) => {
+                self.send(PatchMessage::UpdateSize(size));
+                Some(size)
+            }
+            Err(why) => {
+                warn!("Could not estimate the update size: {why}");
+                None
+            }
+        }
     }
 
-    /// Checks whether the game is installed and installs it if not
-    fn ensure_game_installed(&self) -> Result<(), PatchError> {
-        self.send_download("Checking game installation".to_string(), 1.);
-        if !self.is_game_present() {
-            self.send_download("Downloading game since it is not installed".to_string(), 0.);
+    /// Lets the game be played without patching when the patch server can't
+    /// be reached, as long as it was fully patched before. Otherwise, the
+    /// error is returned as is.
+    fn fall_back_to_offline(&self, why: PatchError) -> Result<RunState, PatchError> {
+        // Being turned away is not the same as the server being unreachable
+        if why.is_unauthorized()
+            || why.is_launcher_outdated()
+            || last_patch::load(self).is_none()
+            || !self.self_dir.join(GAME_EXE).exists()
+        {
+            return Err(why);
+        }
 
-            // Download the base game
-            let game_base_file = download::game_base(self)
-                .map_err(|why| why.to_patch_error("Failed while downloading base game"))?;
+        error!("{:?}", why.internal_error);
+        self.went_offline
+            .set(Some(error::details(&why.internal_error).category));
+        self.send_status(PatchStatus::Offline);
+        self.send_info(tr!(
+            "{why} - you can still play offline",
+            why = why.friendly_message
+        ));
 
-            // Extract the base game to disk
-            self.unpack_base(game_base_file)
-                .map_err(|why| why.to_patch_error("Failed while unpacking base game"))?;
+        Ok(RunState::Continue)
+    }
+
+    /// The configured hooks which the player hasn't agreed to run yet
+    fn unapproved_hooks(&self) -> Vec<String> {
+        let game = &self.config.game;
+        [&game.pre_launch_hook, &game.post_exit_hook]
+            .into_iter()
+            .filter(|command| !command.is_empty())
+            .map(|command| hook::command_line(command))
+            .filter(|line| !game.approved_hooks.contains(line))
+            .collect()
+    }
+
+    /// Runs a hook if there is one. The game starts or the launcher closes
+    /// whether it works or not.
+    fn run_hook(&self, name: &str, command: &[String]) {
+        if command.is_empty() {
+            return;
+        }
+        if let Err(why) = hook::run(name, command, &self.self_dir, self.config.hook_timeout()) {
+            warn!("{why}");
         }
+    }
+
+    /// Waits for the game to exit and runs the post-exit hook, if there is
+    /// one. Otherwise the game is left to run by itself.
+    fn after_game(&self, game: &mut subprocess::Popen) {
+        let command = &self.config.game.post_exit_hook;
+        if command.is_empty() {
+            game.detach();
+            return;
+        }
+        if let Err(why) = game.wait() {
+            warn!("Could not wait for the game: {why}");
+            game.detach();
+            return;
+        }
+        self.run_hook("post-exit", command);
+    }
 
+    /// Checks the programs in the game directory against the digests they
+    /// had when they were last patched, and gives those which changed. This
+    /// leaves out the game's data, which would take too long to read.
+    fn quick_check(&self) -> Vec<String> {
+        let cache = HashCache::load(&self.self_dir.join(HASH_CACHE));
+        let mut damaged = cache
+            .changed_files(|path| {
+                let is_program = path.extension().is_some_and(|extension| {
+                    extension.eq_ignore_ascii_case("exe") || extension.eq_ignore_ascii_case("dll")
+                });
+                is_program && path.parent() == Some(&self.self_dir) && *path != self.self_exe
+            })
+            .iter()
+            .map(|path| {
+                path.strip_prefix(&self.self_dir)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        if !self.self_dir.join(GAME_EXE).is_file() {
+            damaged.push(GAME_EXE.to_string());
+        }
+        damaged.sort();
+        damaged.dedup();
+        damaged
+    }
+
+    /// Starts the game. It is stopped if the Popen is dropped without being
+    /// detached.
+    fn start_game(&self, token: Option<&str>) -> Result<subprocess::Popen, PatcherError> {
+        let mut args = vec![OsStr::new("/launch")];
+        args.extend(self.config.game.launch_args.iter().map(OsStr::new));
+
+        let realm_args = self.selected_realm().map(|realm| {
+            realm
+                .args
+                .iter()
+                .map(|arg| arg.replace("{address}", &realm.address))
+                .collect::<Vec<_>>()
+        });
+        args.extend(realm_args.iter().flatten().map(OsStr::new));
+
+        let account = &self.config.account;
+        let token_arg = token
+            .filter(|_| !account.token_arg.is_empty())
+            .map(|token| account.token_arg.replace("{token}", token));
+        args.extend(token_arg.as_deref().map(OsStr::new));
+
+        let mut variables = Vec::new();
+        if let Some(token) = token.filter(|_| !account.token_env.is_empty()) {
+            variables.push((account.token_env.as_str(), token));
+        }
+        // Keep what the game complains about in case it crashes
+        let stderr = if self.config.game.monitor_launch {
+            subprocess::Redirection::File(std::fs::File::create(self.self_dir.join(GAME_OUTPUT))?)
+        } else {
+            subprocess::Redirection::None
+        };
+        let game = self.start_program(GAME_EXE, &args, variables, stderr)?;
+        if let Some(pid) = game.pid() {
+            let game_config = &self.config.game;
+            // The game runs fine without it
+            if let Err(why) = priority::apply(pid, game_config.priority, &game_config.cpu_affinity)
+            {
+                warn!("Could not change the game's priority or CPU cores: {why}");
+            }
+        }
+        Ok(game)
+    }
+
+    /// Starts one of the other programs which come with the game. It keeps
+    /// running after the launcher closes.
+    fn start_target(&self, target: &LaunchTarget) -> Result<(), PatcherError> {
+        let args = target.args.iter().map(OsStr::new).collect::<Vec<_>>();
+        self.start_program(target.exe, &args, Vec::new(), subprocess::Redirection::None)?
+            .detach();
         Ok(())
     }
 
-    fn start_game(&self) -> Result<(), Box<dyn Error>> {
-        let game_full_path = self.self_dir.join(GAME_EXE);
-        let eco = OsStr::new(&game_full_path);
-        let launch = OsStr::new("/launch");
+    /// Starts a program from the game directory with the configured
+    /// environment, and `variables` on top of it
+    fn start_program(
+        &self,
+        exe: &str,
+        args: &[&OsStr],
+        variables: Vec<(&str, &str)>,
+        stderr: subprocess::Redirection,
+    ) -> Result<subprocess::Popen, PatcherError> {
+        let full_path = self.self_dir.join(exe);
+        if !full_path.is_file() {
+            return Err(format!("{} does not exist", full_path.display()).into());
+        }
+        let exe = OsStr::new(&full_path);
         let wine = OsStr::new("wine");
-        let args = {
+        let mut command = {
             #[cfg(unix)]
             {
                 // TODO: On Unixlike systems, perhaps a new wineprefix should be created
                 // TODO: On Unixlike systems, help the user install Wine
-                [wine, eco, launch].to_vec()
+                [wine, exe].to_vec()
             }
             #[cfg(windows)]
             {
-                [eco, launch].to_vec()
+                [exe].to_vec()
             }
         };
+        command.extend_from_slice(args);
+
+        let environment = self
+            .config
+            .game
+            .environment
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()));
+        let mut env = subprocess::PopenConfig::current_env();
+        for (name, value) in environment.chain(variables) {
+            env.retain(|(existing, _)| existing != OsStr::new(name));
+            env.push((name.into(), value.into()));
+        }
+        let config = subprocess::PopenConfig {
+            env: Some(env),
+            cwd: Some(self.self_dir.clone().into_os_string()),
+            stderr,
+            ..subprocess::PopenConfig::default()
+        };
+        Ok(subprocess::Popen::create(&command, config)?)
+    }
 
-        std::env::set_current_dir(&self.self_dir)?;
-        Ok(start_detached_process(&args)?)
+    /// Waits to see whether the game survives starting up. If it exits with
+    /// an error in that time, the player is told so and can try again.
+    fn monitor_game(&self, game: &mut subprocess::Popen) -> RunState {
+        let status = match game.wait_timeout(GAME_STARTUP_TIME) {
+            Ok(Some(status)) => status,
+            Ok(None) => {
+                // It made it
+                self.after_game(game);
+                return RunState::Close;
+            }
+            Err(why) => {
+                warn!("Could not watch the game: {why}");
+                game.detach();
+                return RunState::Close;
+            }
+        };
+        self.after_game(game);
+        if status.success() {
+            return RunState::Close;
+        }
+
+        warn!("The game exited on startup: {status:?}");
+        self.send(PatchMessage::GameCrashed {
+            text: crash::describe(status),
+            output: crash::output_tail(&self.self_dir.join(GAME_OUTPUT)),
+        });
+        self.set_state(WorkerState::ReadyToPlay);
+        RunState::Continue
+    }
+
+    /// Stays open until the game exits, then closes with the game's exit code
+    /// for the launcher to exit with, so Steam knows how the session ended.
+    /// Crashes on startup are still reported if the launch is being watched.
+    fn wait_for_game(&mut self, game: &mut subprocess::Popen) -> RunState {
+        let started = Instant::now();
+        let status = match game.wait() {
+            Ok(status) => status,
+            Err(why) => {
+                warn!("Could not wait for the game: {why}");
+                game.detach();
+                return RunState::Close;
+            }
+        };
+        self.after_game(game);
+        let exit_code = crash::exit_code(status);
+        let on_startup = started.elapsed() < GAME_STARTUP_TIME;
+        if !status.success() && on_startup && self.config.game.monitor_launch {
+            warn!("The game exited on startup: {status:?}");
+            self.send(PatchMessage::GameCrashed {
+                text: crash::describe(status),
+                output: crash::output_tail(&self.self_dir.join(GAME_OUTPUT)),
+            });
+            self.set_state(WorkerState::ReadyToPlay);
+            return RunState::Continue;
+        }
+        self.exit_code = Some(exit_code);
+        RunState::Close
     }
 
     /// Checks for whether the current patcher is a temporary updated patcher.
@@ -368,20 +2025,15 @@ impl PatchWorker {
     /// location. `Ok(RunState::Close)` will be returned.
     ///
     /// If not, `Ok(RunState::Continue)` will be returned.
-    pub fn check_patcher_aecoupdate(&self) -> Result<RunState, PatchError> {
-        // Get current extension, or finish if there is none
-        let extension = match self.self_exe.extension() {
-            Some(ext) => ext,
-            None => {
-                // Remove aecoupdate launcher if there is one
-                self.remove_aecoupdate_file()
-                    .map_err(|why| why.to_patch_error("Failed to remove temporary launcher"))?;
-                return Ok(RunState::Continue);
-            }
-        };
-
+    pub fn check_patcher_aecoupdate(&mut self) -> Result<RunState, PatchError> {
         // Finish if the current extension isn't the update extension
-        if extension != UPDATE_FILE_EXTENSION {
+        if self.self_exe.extension() != Some(OsStr::new(UPDATE_FILE_EXTENSION)) {
+            // Remove aecoupdate launcher if there is one. It waits for this
+            // one to start, so it may not have closed yet, and the next
+            // start tries again.
+            if let Err(why) = self.remove_aecoupdate_file() {
+                warn!("Failed to remove temporary launcher: {why}");
+            }
             return Ok(RunState::Continue);
         }
 
@@ -395,55 +2047,76 @@ impl PatchWorker {
             path
         };
 
-        // Copy this program to the normal program filename
-        // Try a few times, it is possible that the old process hasn't shut
-        // down yet
-        let retries = 5;
-        for retry in 1..=retries {
-            if let Err(why) = std::fs::copy(&self.self_exe, &new_file_path) {
-                if retry == retries {
-                    return Err(why.to_patch_error("Failed to overwrite patcher"));
-                }
-                std::thread::sleep(std::time::Duration::from_millis(250));
-            } else {
-                break;
-            }
-        }
+        // Move this program to the normal program filename, keeping the old
+        // one to be put back if this one doesn't start
+        self_update::swap_in(&self.self_exe, &new_file_path)
+            .map_err(|why| PatchError::from_error(why, "Failed to overwrite patcher"))?;
 
         // Make sure the file is executable on unixlike systems
         set_executable(&new_file_path)
             .map_err(|why| why.to_patch_error("Failed to make patcher executable"))?;
 
-        // Open the restored launcher and close this one
-        start_detached_process(&[new_file_path])
+        // Open the restored launcher and close this one once it is up. It
+        // takes the lock when it starts, so this one can't wait with it.
+        self.instance_lock = None;
+        self_update::start_and_watch(&new_file_path, &self.self_exe)
             .map_err(|why| why.to_patch_error("Failed to start new launcher"))?;
 
         // Signal to stop the patcher
         Ok(RunState::Close)
     }
 
-    fn remove_aecoupdate_file(&self) -> Result<(), Box<dyn Error>> {
+    fn remove_aecoupdate_file(&self) -> Result<(), PatcherError> {
         let path = self.get_self_aecoupdate_path()?;
-        if path.exists() {
-            // Try a few times, it is possible that the old process hasn't shut
-            // down yet
-            let retries = 5;
-            for retry in 1..=retries {
-                if let Err(why) = std::fs::remove_file(&path) {
-                    if retry == retries {
-                        return Err(why.into());
-                    }
-                    std::thread::sleep(std::time::Duration::from_millis(250));
-                } else {
-                    break;
-                }
-            }
-        }
+        self_update::remove_leftover(&path)?;
+        Ok(())
+    }
 
+    /// Moves the game to another directory and patches it there from now on
+    fn move_game(&mut self, target: &Path) -> Result<(), PatcherError> {
+        self.send_status(PatchStatus::Working);
+        self.send_info(tr!("Moving the game"));
+        let game_dir = relocate::move_game(self, target)?;
+        let stored = self.stored_path(game_dir.clone());
+        match self.active_profile() {
+            Some(profile) => profile.game_dir = Some(stored),
+            None => self.config.patch.game_dir = Some(stored),
+        }
+        self.config.save(self.launcher_dir.join(CONFIG_FILE))?;
+        self.self_dir = game_dir;
         Ok(())
     }
 
-    pub fn get_self_aecoupdate_path(&self) -> Result<PathBuf, Box<dyn Error>> {
+    /// How a path is written to the config. In portable mode, paths inside
+    /// the launcher's directory are made relative to it, so they keep
+    /// working when the drive gets another letter or mount point.
+    fn stored_path(&self, path: PathBuf) -> PathBuf {
+        if !self.portable {
+            return path;
+        }
+        match path.strip_prefix(&self.launcher_dir) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => path,
+        }
+    }
+
+    /// Locks the game directory while the game is changed, unless it is the
+    /// launcher's own directory, which is locked already
+    pub fn lock_game_dir(&self) -> Result<Option<InstanceLock>, PatcherError> {
+        Ok(InstanceLock::for_game(&self.launcher_dir, &self.self_dir)?)
+    }
+
+    /// Whether a path is this program. The patch list has the launcher in the
+    /// game directory, but it can be elsewhere.
+    pub fn is_launcher(&self, path: &Path) -> bool {
+        path == self.self_exe
+            || self
+                .self_exe
+                .file_name()
+                .is_some_and(|name| path == self.self_dir.join(name))
+    }
+
+    pub fn get_self_aecoupdate_path(&self) -> Result<PathBuf, PatcherError> {
         let current_name = self
             .self_exe
             .file_name()
@@ -469,7 +2142,7 @@ impl PatchWorker {
     /// as not saving game-window size, using the incorrect in-game window
     /// themes, and having the game be totally silent due to the volume sliders
     /// being set to 0%.
-    fn check_eco_ini(&self) -> Result<(), Box<dyn Error>> {
+    fn check_eco_ini(&self) -> Result<(), PatcherError> {
         let ini_file = self.self_dir.join(GAME_INI);
         let ini_data = std::fs::read(&ini_file)?;
 
@@ -489,24 +2162,89 @@ impl PatchWorker {
 
 impl Drop for PatchWorker {
     fn drop(&mut self) {
+        // The GUI closes the launcher as soon as it hears about this
+        self.telemetry.send(self);
         self.send_status(PatchStatus::Close);
     }
 }
 
-/// Gets a Directory child from a Directory by name, if it is present
-fn subdir_by_name<'a>(dir: &'a Directory, name: &str) -> Option<&'a Directory> {
-    for child in &dir.children {
-        if let FSObject::Directory(d) = child {
-            if d.name == name {
-                return Some(d);
-            }
-        }
+/// How long the countdown before starting the game by itself is
+const AUTO_LAUNCH_SECONDS: u64 = 5;
+
+/// How long the game is watched for crashes after starting it
+const GAME_STARTUP_TIME: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The directory the launcher runs from, where its config and lock are
+pub fn launcher_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let self_exe = std::env::current_exe()?;
+    Ok(self_exe
+        .parent()
+        .ok_or_else(|| "No parent directory for the launcher was found.".to_string())?
+        .to_path_buf())
+}
+
+/// Whether the launcher keeps everything next to itself, because it was
+/// asked to or there is a marker file saying so
+pub fn is_portable(launcher_dir: &Path, portable: bool) -> bool {
+    portable || launcher_dir.join(PORTABLE_MARKER).is_file()
+}
+
+fn build_hash_pool(config: &Config) -> Result<rayon::ThreadPool, Box<dyn Error>> {
+    Ok(rayon::ThreadPoolBuilder::new()
+        .num_threads(config.hash_threads())
+        .thread_name(|index| format!("hash-{index}"))
+        .build()?)
+}
+
+/// Checks that a patch server URL is something the launcher can patch from.
+/// The URL is treated as a directory even without a trailing slash.
+fn parse_server_url(server: &str) -> Result<reqwest::Url, Box<dyn Error>> {
+    let mut url = reqwest::Url::parse(server.trim())
+        .map_err(|why| format!("Invalid patch server URL '{server}': {why}"))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(format!("Patch server '{server}' is not an HTTP or HTTPS URL").into());
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("Patch server '{server}' must not have a query or fragment").into());
+    }
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
+/// Gets the URLs of a channel's patch list and patch files. The stable
+/// channel's are at the top of the meta and patch directories, and every
+/// other channel has a subdirectory in each.
+fn channel_urls(
+    server_url: &reqwest::Url,
+    channel: &str,
+) -> Result<(reqwest::Url, reqwest::Url), Box<dyn Error>> {
+    let meta_url = server_url.join(META_DIR)?;
+    let patch_url = server_url.join(PATCH_DIR)?;
+    if channel == STABLE_CHANNEL {
+        return Ok((meta_url.join(PATCHLIST)?, patch_url));
     }
-    None
+
+    // The channel name becomes part of the URL
+    if channel.is_empty()
+        || !channel
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("Invalid channel name '{channel}'").into());
+    }
+
+    let channel_dir = format!("{channel}/");
+    Ok((
+        meta_url.join(&channel_dir)?.join(PATCHLIST)?,
+        patch_url.join(&channel_dir)?,
+    ))
 }
 
 /// Starts a new process and closes the current one.
-fn start_detached_process(args: &[impl AsRef<OsStr>]) -> Result<(), PopenError> {
+pub fn start_detached_process(args: &[impl AsRef<OsStr>]) -> Result<(), PopenError> {
     match subprocess::Popen::create(args, subprocess::PopenConfig::default()) {
         Ok(mut popen) => {
             // Close this program
@@ -516,3 +2254,187 @@ fn start_detached_process(args: &[impl AsRef<OsStr>]) -> Result<(), PopenError>
         Err(why) => Err(why),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Cursor, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::channel;
+    use std::sync::Mutex;
+
+    use aeco_patch_config::fsobject::FSObject;
+
+    use super::*;
+    use crate::patcher::utils::get_platform;
+
+    /// What the test patch server has, by path
+    type Files = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Serves `files` over plain HTTP on a local port, and answers anything
+    /// else with 404. Returns the server's URL.
+    fn serve(files: Files) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let files = files.clone();
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request = String::new();
+                    reader.read_line(&mut request).unwrap();
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap() > 2 {
+                        line.clear();
+                    }
+
+                    let mut parts = request.split_whitespace();
+                    let method = parts.next().unwrap_or_default();
+                    let path = parts.next().unwrap_or_default().trim_start_matches('/');
+                    let body = files.lock().unwrap().get(path).cloned();
+                    match body {
+                        Some(body) => {
+                            write!(
+                                stream,
+                                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                                body.len()
+                            )
+                            .unwrap();
+                            if method != "HEAD" {
+                                stream.write_all(&body).unwrap();
+                            }
+                        }
+                        None => write!(
+                            stream,
+                            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        )
+                        .unwrap(),
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// Publishes a version of the game, whose files are all in the `all`
+    /// directory of the patch list
+    fn publish(files: &Files, version: &str, game: &[(&str, &[u8])]) {
+        let all = Directory {
+            name: "all".to_string(),
+            children: game
+                .iter()
+                .map(|(name, data)| FSObject::File(PatchFile::new(name, data)))
+                .collect(),
+        };
+        let platform = Directory {
+            name: get_platform(),
+            children: Vec::new(),
+        };
+        let patchlist = Directory {
+            name: String::new(),
+            children: vec![FSObject::Directory(all), FSObject::Directory(platform)],
+        };
+
+        let mut files = files.lock().unwrap();
+        files.insert(
+            format!("{META_DIR}{STATUS}"),
+            serde_json::to_vec(&ServerStatus::Online).unwrap(),
+        );
+        files.insert(
+            format!("{META_DIR}{GAME_VERSION}"),
+            serde_json::to_vec(&serde_json::json!({ "version": version })).unwrap(),
+        );
+        files.insert(
+            format!("{META_DIR}{PATCHLIST}"),
+            serde_json::to_vec(&patchlist).unwrap(),
+        );
+        for (name, data) in game {
+            files.insert(format!("{PATCH_DIR}all/{name}"), data.to_vec());
+        }
+    }
+
+    /// Patches, failing with what the worker reported if it didn't finish
+    fn patch(worker: &mut PatchWorker, messages: &Receiver<PatchMessage>) {
+        let _ = worker.handle_message(GUIMessage::Retry);
+        let mut finished = false;
+        for message in messages.try_iter() {
+            match message {
+                PatchMessage::Error(text, details) => panic!("{text}\n{details}"),
+                PatchMessage::PatchStatus(PatchStatus::Finished) => finished = true,
+                _ => {}
+            }
+        }
+        assert!(finished, "the worker didn't finish patching");
+    }
+
+    fn assert_installed(dir: &Path, game: &[(&str, &[u8])]) {
+        for (name, data) in game {
+            let installed = std::fs::read(dir.join(name)).unwrap();
+            assert_eq!(
+                PatchFile::new(name, &installed).digest,
+                PatchFile::new(name, data).digest,
+                "{name} was not patched"
+            );
+        }
+    }
+
+    #[test]
+    fn installs_and_updates_from_a_patch_server() {
+        // Builds with a key refuse the unsigned files served here
+        if MANIFEST_PUBLIC_KEY.is_some() {
+            return;
+        }
+
+        let files = Files::default();
+        let server = serve(files.clone());
+        files.lock().unwrap().insert(
+            format!("{BASE_DIR}{BASE_ZIP}"),
+            zip(&[
+                (GAME_EXE, b"game 1"),
+                (GAME_INI, b"[Graphics]\r\n"),
+                ("data.txt", b"data 1"),
+            ]),
+        );
+        let first: &[(&str, &[u8])] = &[(GAME_EXE, b"game 1"), ("data.txt", b"data 2")];
+        publish(&files, "1.0", first);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.network.https_only = false;
+        let (tx, messages) = channel();
+        let (_gui_tx, rx) = channel();
+        let mut worker = PatchWorker::with_config(
+            tx,
+            rx,
+            dir.path().join("aeco-launcher"),
+            dir.path().to_path_buf(),
+            config,
+            Some(server),
+            false,
+        )
+        .unwrap();
+
+        // A fresh install gets the base game, then patches it
+        patch(&mut worker, &messages);
+        assert_installed(dir.path(), first);
+
+        let second: &[(&str, &[u8])] = &[
+            (GAME_EXE, b"game 2"),
+            ("data.txt", b"data 2"),
+            ("new.txt", b"new"),
+        ];
+        publish(&files, "1.1", second);
+        patch(&mut worker, &messages);
+        assert_installed(dir.path(), second);
+    }
+}
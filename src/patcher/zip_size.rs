@@ -0,0 +1,293 @@
+use std::error::Error;
+
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+
+use super::PatchWorker;
+
+const END_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_END_SIGNATURE: u32 = 0x0606_4b50;
+const ENTRY_SIGNATURE: u32 = 0x0201_4b50;
+/// The end of central directory record, without its comment
+const END_SIZE: u64 = 22;
+const ZIP64_LOCATOR_SIZE: u64 = 20;
+const ZIP64_END_SIZE: u64 = 56;
+/// The end record is followed by a comment of up to this many bytes
+const MAX_COMMENT: u64 = u16::MAX as u64;
+const ENTRY_SIZE: usize = 46;
+/// The extra field holding the sizes which don't fit in the entry
+const ZIP64_EXTRA: u16 = 0x0001;
+
+/// Finds out how large the files in a remote ZIP add up to once extracted,
+/// without downloading all of it. Only the central directory at the end of
+/// the ZIP is fetched, using range requests. Returns None if the server
+/// doesn't answer those, or the ZIP can't be read this way.
+pub fn unpacked_size(
+    worker: &PatchWorker,
+    url: &reqwest::Url,
+    zip_size: u64,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    if zip_size < END_SIZE {
+        return Ok(None);
+    }
+    let tail_start = zip_size.saturating_sub(END_SIZE + MAX_COMMENT + ZIP64_LOCATOR_SIZE);
+    let tail = match fetch_range(worker, url, tail_start, zip_size - 1)? {
+        Some(tail) => tail,
+        None => return Ok(None),
+    };
+    let (offset, size) = match central_directory(&tail) {
+        Some(Location::Directory { offset, size }) => (offset, size),
+        Some(Location::Zip64End { offset }) => {
+            let end = fetch_range(worker, url, offset, offset + ZIP64_END_SIZE - 1)?;
+            match end.as_deref().and_then(zip64_central_directory) {
+                Some(location) => location,
+                None => return Ok(None),
+            }
+        }
+        None => return Ok(None),
+    };
+    if offset.checked_add(size).is_none_or(|end| end > zip_size) {
+        return Ok(None);
+    }
+
+    let directory = if offset >= tail_start {
+        let start = (offset - tail_start) as usize;
+        tail.get(start..start + size as usize).map(<[u8]>::to_vec)
+    } else if size == 0 {
+        Some(Vec::new())
+    } else {
+        fetch_range(worker, url, offset, offset + size - 1)?
+    };
+    Ok(directory.as_deref().and_then(entries_size))
+}
+
+/// Downloads part of a file, or returns None if the server sent something
+/// other than that part
+fn fetch_range(
+    worker: &PatchWorker,
+    url: &reqwest::Url,
+    start: u64,
+    end: u64,
+) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let _connection = worker.connection()?;
+    let request = worker
+        .client
+        .get(url.clone())
+        .header(RANGE, format!("bytes={start}-{end}"));
+    let bytes = worker.runtime.block_on(async {
+        let response = request.send().await?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Ok(None);
+        }
+        response.bytes().await.map(Some)
+    })?;
+    Ok(bytes
+        .filter(|bytes| bytes.len() as u64 == end - start + 1)
+        .map(|bytes| bytes.to_vec()))
+}
+
+/// Where the central directory is, as the end of a ZIP tells it
+#[derive(Debug, PartialEq)]
+enum Location {
+    Directory {
+        offset: u64,
+        size: u64,
+    },
+    /// The ZIP is too large for the end record, so the real one is here
+    Zip64End {
+        offset: u64,
+    },
+}
+
+/// Reads the end of central directory record from the last bytes of a ZIP
+fn central_directory(tail: &[u8]) -> Option<Location> {
+    // The comment after the record could contain the signature too, so it
+    // is searched for from the end
+    let end = (0..=tail.len().checked_sub(END_SIZE as usize)?)
+        .rev()
+        .find(|&at| read_u32(tail, at) == Some(END_SIGNATURE))?;
+    let size = read_u32(tail, end + 12)?;
+    let offset = read_u32(tail, end + 16)?;
+    if size != u32::MAX && offset != u32::MAX {
+        return Some(Location::Directory {
+            offset: offset.into(),
+            size: size.into(),
+        });
+    }
+
+    let locator = end.checked_sub(ZIP64_LOCATOR_SIZE as usize)?;
+    if read_u32(tail, locator)? != ZIP64_LOCATOR_SIGNATURE {
+        return None;
+    }
+    Some(Location::Zip64End {
+        offset: read_u64(tail, locator + 8)?,
+    })
+}
+
+/// Reads where the central directory is from the ZIP64 end record
+fn zip64_central_directory(end: &[u8]) -> Option<(u64, u64)> {
+    if read_u32(end, 0)? != ZIP64_END_SIGNATURE {
+        return None;
+    }
+    Some((read_u64(end, 48)?, read_u64(end, 40)?))
+}
+
+/// Adds up the extracted sizes of every entry in a central directory
+fn entries_size(directory: &[u8]) -> Option<u64> {
+    let mut total = 0u64;
+    let mut at = 0;
+    while at < directory.len() {
+        if read_u32(directory, at)? != ENTRY_SIGNATURE {
+            return None;
+        }
+        let mut size = u64::from(read_u32(directory, at + 24)?);
+        let name_len = usize::from(read_u16(directory, at + 28)?);
+        let extra_len = usize::from(read_u16(directory, at + 30)?);
+        let comment_len = usize::from(read_u16(directory, at + 32)?);
+        let extra_start = at + ENTRY_SIZE + name_len;
+        if size == u64::from(u32::MAX) {
+            let extra = directory.get(extra_start..extra_start + extra_len)?;
+            size = zip64_size(extra)?;
+        }
+        total = total.checked_add(size)?;
+        at = extra_start + extra_len + comment_len;
+    }
+    Some(total)
+}
+
+/// Gets the extracted size from an entry's ZIP64 extra field. It comes first
+/// whenever the entry itself doesn't have it.
+fn zip64_size(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let id = read_u16(extra, 0)?;
+        let len = usize::from(read_u16(extra, 2)?);
+        if id == ZIP64_EXTRA {
+            return read_u64(extra.get(4..4 + len)?, 0);
+        }
+        extra = extra.get(4 + len..)?;
+    }
+    None
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::FileOptions;
+
+    use super::*;
+
+    /// Builds a ZIP with files of the given sizes and an archive comment
+    fn zip(sizes: &[usize], comment: &str) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory("data/", FileOptions::default())
+            .unwrap();
+        for (index, &size) in sizes.iter().enumerate() {
+            writer
+                .start_file(format!("data/{index}.bin"), FileOptions::default())
+                .unwrap();
+            writer.write_all(&vec![b'x'; size]).unwrap();
+        }
+        writer.set_comment(comment);
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Adds up the sizes of the files in a whole ZIP, the way
+    /// `unpacked_size` does with the parts it downloads
+    fn unpacked(zip: &[u8]) -> Option<u64> {
+        match central_directory(zip)? {
+            Location::Directory { offset, size } => {
+                let directory = zip.get(offset as usize..(offset + size) as usize)?;
+                entries_size(directory)
+            }
+            Location::Zip64End { .. } => None,
+        }
+    }
+
+    #[test]
+    fn adds_up_the_extracted_sizes() {
+        assert_eq!(unpacked(&zip(&[10, 2000, 0, 12345], "")), Some(14355));
+    }
+
+    #[test]
+    fn finds_the_end_record_behind_a_comment() {
+        let comment = "Made by the patch server. ".repeat(100);
+        assert_eq!(unpacked(&zip(&[7, 8], &comment)), Some(15));
+    }
+
+    #[test]
+    fn an_empty_zip_has_nothing_to_extract() {
+        assert_eq!(unpacked(&zip(&[], "")), Some(0));
+    }
+
+    #[test]
+    fn refuses_what_isnt_a_zip() {
+        assert_eq!(central_directory(b"not a zip file at all, just text"), None);
+        assert_eq!(central_directory(&[]), None);
+        assert_eq!(entries_size(b"PK\x01\x02 too short"), None);
+    }
+
+    #[test]
+    fn reads_zip64_locations() {
+        let mut tail = Vec::new();
+        tail.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+        tail.extend_from_slice(&0u32.to_le_bytes());
+        tail.extend_from_slice(&0x1_2345_6789u64.to_le_bytes());
+        tail.extend_from_slice(&1u32.to_le_bytes());
+        tail.extend_from_slice(&END_SIGNATURE.to_le_bytes());
+        tail.extend_from_slice(&[0xff; 4]);
+        tail.extend_from_slice(&[0xff; 4]);
+        tail.extend_from_slice(&u32::MAX.to_le_bytes());
+        tail.extend_from_slice(&u32::MAX.to_le_bytes());
+        tail.extend_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            central_directory(&tail),
+            Some(Location::Zip64End {
+                offset: 0x1_2345_6789
+            })
+        );
+
+        let mut end = vec![0; ZIP64_END_SIZE as usize];
+        end[..4].copy_from_slice(&ZIP64_END_SIGNATURE.to_le_bytes());
+        end[40..48].copy_from_slice(&500u64.to_le_bytes());
+        end[48..56].copy_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        assert_eq!(zip64_central_directory(&end), Some((0x1_0000_0000, 500)));
+    }
+
+    #[test]
+    fn reads_zip64_entry_sizes() {
+        let name = b"big.bin";
+        let mut extra = Vec::new();
+        // Another extra field comes first
+        extra.extend_from_slice(&0x5455u16.to_le_bytes());
+        extra.extend_from_slice(&1u16.to_le_bytes());
+        extra.push(0);
+        extra.extend_from_slice(&ZIP64_EXTRA.to_le_bytes());
+        extra.extend_from_slice(&16u16.to_le_bytes());
+        extra.extend_from_slice(&0x2_0000_0000u64.to_le_bytes());
+        extra.extend_from_slice(&0x1_0000_0000u64.to_le_bytes());
+
+        let mut entry = vec![0; ENTRY_SIZE];
+        entry[..4].copy_from_slice(&ENTRY_SIGNATURE.to_le_bytes());
+        entry[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+        entry[28..30].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        entry[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+        entry.extend_from_slice(name);
+        entry.extend_from_slice(&extra);
+        assert_eq!(entries_size(&entry), Some(0x2_0000_0000));
+    }
+}
@@ -83,8 +83,11 @@ impl Widget for ProgressBar {
 
         let desired_width =
             desired_width.unwrap_or_else(|| ui.available_size_before_wrap().x.at_least(96.0));
-        let (outer_rect, response) =
-            ui.allocate_exact_size(vec2(desired_width, height), Sense::hover());
+        // Screen readers can focus the bar to hear the status text
+        let (outer_rect, mut response) = ui.allocate_exact_size(
+            vec2(desired_width, height),
+            Sense::focusable_noninteractive(),
+        );
 
         if ui.is_rect_visible(response.rect) {
             if animate {
@@ -147,6 +150,7 @@ impl Widget for ProgressBar {
                         format!("{}%", (progress * 100.0) as usize).into()
                     }
                 };
+                announce(ui, &mut response, text.text(), progress);
                 let galley = text.into_galley(ui, Some(false), f32::INFINITY, TextStyle::Button);
                 let text_pos = outer_rect.left_center() - Vec2::new(0.0, galley.size().y / 2.0)
                     + vec2(ui.spacing().item_spacing.x, 0.0);
@@ -164,3 +168,19 @@ impl Widget for ProgressBar {
         response
     }
 }
+
+/// Tells screen readers what the progress bar says. Changes to the text are
+/// only read out while the bar has focus, since it changes with every file.
+fn announce(ui: &Ui, response: &mut Response, text: &str, progress: f32) {
+    let previous = ui.data().get_temp::<String>(response.id);
+    if previous.as_deref() != Some(text) {
+        ui.data().insert_temp(response.id, text.to_string());
+        if response.has_focus() {
+            response.mark_changed();
+        }
+    }
+    response.widget_info(|| WidgetInfo {
+        value: Some(progress as f64),
+        ..WidgetInfo::labeled(WidgetType::Other, text)
+    });
+}
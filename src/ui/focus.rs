@@ -0,0 +1,62 @@
+use super::WINDOW_TITLE;
+use std::process::Command;
+use tracing::warn;
+
+/// Brings the window of the copy of the launcher which is already running to
+/// the front. Nothing happens if it can't be found, as the window is still
+/// there for the player to switch to.
+pub fn raise_running() {
+    #[cfg(windows)]
+    let result = {
+        use std::os::windows::process::CommandExt;
+        // Don't flash a console window
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        // The title is passed through the environment so it doesn't need to
+        // be quoted for PowerShell
+        Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                "(New-Object -ComObject WScript.Shell).AppActivate($env:AECO_WINDOW_TITLE)",
+            ])
+            .env("AECO_WINDOW_TITLE", WINDOW_TITLE)
+            .creation_flags(CREATE_NO_WINDOW)
+            .status()
+    };
+    #[cfg(target_os = "macos")]
+    let result = {
+        // Windows can't be looked up by title without accessibility access,
+        // but the launcher's process is found by its file name
+        let name = std::env::current_exe()
+            .ok()
+            .and_then(|exe| {
+                exe.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
+        Command::new("osascript")
+            .args([
+                "-e",
+                "on run argv",
+                "-e",
+                "tell application \"System Events\" to set frontmost of (first process whose name is item 1 of argv) to true",
+                "-e",
+                "end run",
+                &name,
+            ])
+            .status()
+    };
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let result = Command::new("xdotool")
+        .args(["search", "--name", WINDOW_TITLE, "windowactivate"])
+        .status();
+
+    match result {
+        Ok(status) if !status.success() => {
+            warn!("Could not bring the running launcher to the front: {status}")
+        }
+        Ok(_) => {}
+        Err(why) => warn!("Could not bring the running launcher to the front: {why}"),
+    }
+}
@@ -0,0 +1,39 @@
+use eframe::egui;
+use tracing::warn;
+
+/// Fonts with Japanese characters which come with the operating system, in
+/// order of preference. egui's own fonts don't have them.
+const CJK_FONTS: [&str; 7] = [
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "C:\\Windows\\Fonts\\meiryo.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+];
+
+/// Adds a system font with Japanese characters as a fallback for the
+/// launcher's text, if there is one
+pub fn install(ctx: &egui::Context) {
+    let data = match CJK_FONTS.iter().find_map(|path| std::fs::read(path).ok()) {
+        Some(data) => data,
+        None => {
+            warn!("No Japanese font was found, Japanese text won't show");
+            return;
+        }
+    };
+
+    let mut fonts = egui::FontDefinitions::default();
+    fonts
+        .font_data
+        .insert("cjk".to_string(), egui::FontData::from_owned(data));
+    for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+        fonts
+            .families
+            .entry(family)
+            .or_default()
+            .push("cjk".to_string());
+    }
+    ctx.set_fonts(fonts);
+}
@@ -0,0 +1,60 @@
+use eframe::egui;
+
+/// Shows the parts of Markdown that patch notes use: headings, lists and
+/// paragraphs. Inline formatting is shown as plain text.
+pub fn show(ui: &mut egui::Ui, text: &str) {
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            ui.add_space(6.);
+        } else if let Some(heading) = heading(line) {
+            ui.label(egui::RichText::new(plain(heading)).heading());
+        } else if let Some(item) = list_item(line) {
+            ui.horizontal_wrapped(|ui| {
+                ui.label("•");
+                ui.label(plain(item));
+            });
+        } else {
+            ui.label(plain(line));
+        }
+    }
+}
+
+/// The text of a `#` heading of any level
+fn heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    (text.len() < line.len() && text.starts_with(' ')).then(|| text.trim())
+}
+
+/// The text of a `-`, `*` or `+` list item
+fn list_item(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+}
+
+/// Removes emphasis and code markers, and turns links into their text
+fn plain(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        plain.push_str(&rest[..start]);
+        let link = &rest[start..];
+        match link.find("](").and_then(|middle| {
+            let end = link[middle..].find(')')? + middle;
+            Some((&link[1..middle], end))
+        }) {
+            Some((link_text, end)) => {
+                plain.push_str(link_text);
+                rest = &link[end + 1..];
+            }
+            None => {
+                plain.push('[');
+                rest = &link[1..];
+            }
+        }
+    }
+    plain.push_str(rest);
+    plain.replace("**", "").replace("__", "").replace('`', "")
+}
@@ -1,8 +1,29 @@
-use crate::message::{GUIMessage, PatchMessage, PatchStatus};
+use crate::config::{self, CrashReports, Priority, Settings, Theme};
+use crate::crash_report;
+use crate::i18n::LANGUAGES;
+use crate::message::{
+    ConnectionReport, ErrorDetails, FileAction, GUIMessage, NewsItem, NewsKind, PatchMessage,
+    PatchReport, PatchStatus, RealmInfo, ServerDetails, WorkerState,
+};
 use crate::version::version_summary;
 use eframe::{egui, emath::Vec2};
+use std::collections::VecDeque;
 use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
+use tracing::warn;
 mod atomix;
+mod focus;
+mod fonts;
+mod markdown;
+mod motion;
+mod notification;
+mod presence;
+mod theme;
+mod window;
+pub use focus::raise_running;
+use presence::{Activity, Presence};
+use theme::Palette;
+pub use window::SavedWindow;
 
 fn load_image_from_memory(image_data: &[u8]) -> Result<egui::ColorImage, image::ImageError> {
     let image = image::load_from_memory(image_data)?;
@@ -15,6 +36,207 @@ fn load_image_from_memory(image_data: &[u8]) -> Result<egui::ColorImage, image::
     ))
 }
 
+/// The title of the launcher's window, which is how a second copy of the
+/// launcher finds it
+const WINDOW_TITLE: &str = "Atomix ECO Launcher";
+
+/// How many files the file log remembers. Older entries are dropped.
+const FILE_LOG_LENGTH: usize = 5000;
+
+/// The release channels offered in the channel selector
+const CHANNELS: [&str; 3] = ["stable", "beta", "ptr"];
+/// How long the worker may say nothing while patching before the player is
+/// offered to give up on it. It sends a heartbeat every few seconds while
+/// it is getting somewhere.
+const WORKER_TIMEOUT: Duration = Duration::from_secs(60);
+/// The most simultaneous downloads the settings screen offers. The config
+/// file can allow more.
+const MAX_DOWNLOADS: usize = 16;
+
+/// The settings screen's copy of the settings while they are being edited
+struct SettingsForm {
+    game_dir: String,
+    max_download_kbps: u64,
+    hash_threads: usize,
+    max_downloads: usize,
+    low_impact: bool,
+    channel: String,
+    language: String,
+    theme: Theme,
+    accent: egui::Color32,
+    notifications: bool,
+    screen_reader: bool,
+    discord_presence: bool,
+    launch_args: String,
+    /// One `NAME=value` per line
+    environment: String,
+    monitor_launch: bool,
+    auto_launch: bool,
+    quick_check: bool,
+    priority: Priority,
+    /// CPU core numbers separated by commas
+    cpu_affinity: String,
+    crash_reports: CrashReports,
+    telemetry: bool,
+    torrent: bool,
+    torrent_client_found: bool,
+}
+
+impl SettingsForm {
+    fn new(settings: &Settings) -> Self {
+        Self {
+            game_dir: settings
+                .game_dir
+                .as_ref()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            max_download_kbps: settings.max_download_kbps,
+            hash_threads: settings.hash_threads,
+            max_downloads: settings.max_downloads,
+            low_impact: settings.low_impact,
+            channel: settings.channel.clone(),
+            language: settings.language.clone(),
+            theme: settings.theme,
+            accent: theme::accent_color(&settings.accent_color),
+            notifications: settings.notifications,
+            screen_reader: settings.screen_reader,
+            discord_presence: settings.discord_presence,
+            launch_args: settings.launch_args.join(" "),
+            environment: settings
+                .environment
+                .iter()
+                .map(|(name, value)| format!("{name}={value}\n"))
+                .collect(),
+            monitor_launch: settings.monitor_launch,
+            auto_launch: settings.auto_launch,
+            quick_check: settings.quick_check,
+            priority: settings.priority,
+            cpu_affinity: settings
+                .cpu_affinity
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            crash_reports: settings.crash_reports,
+            telemetry: settings.telemetry,
+            torrent: settings.torrent,
+            torrent_client_found: settings.torrent_client_found,
+        }
+    }
+
+    fn to_settings(&self) -> Settings {
+        let game_dir = self.game_dir.trim();
+        Settings {
+            game_dir: (!game_dir.is_empty()).then(|| game_dir.into()),
+            max_download_kbps: self.max_download_kbps,
+            hash_threads: self.hash_threads,
+            max_downloads: self.max_downloads,
+            low_impact: self.low_impact,
+            channel: self.channel.clone(),
+            language: self.language.trim().to_string(),
+            theme: self.theme,
+            accent_color: theme::accent_setting(self.accent),
+            notifications: self.notifications,
+            screen_reader: self.screen_reader,
+            discord_presence: self.discord_presence,
+            launch_args: self
+                .launch_args
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            environment: self
+                .environment
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+                .filter(|(name, _)| !name.is_empty())
+                .collect(),
+            monitor_launch: self.monitor_launch,
+            auto_launch: self.auto_launch,
+            quick_check: self.quick_check,
+            priority: self.priority,
+            cpu_affinity: self
+                .cpu_affinity
+                .split(',')
+                .filter_map(|core| core.trim().parse().ok())
+                .collect(),
+            crash_reports: self.crash_reports,
+            telemetry: self.telemetry,
+            torrent: self.torrent,
+            torrent_client_found: self.torrent_client_found,
+        }
+    }
+}
+
+/// The first-run setup screen's choices while they are being made
+struct SetupForm {
+    settings: SettingsForm,
+    create_shortcut: bool,
+}
+
+/// A line of the file log
+struct FileLogEntry {
+    path: String,
+    action: FileAction,
+    bytes: u64,
+}
+
+/// Chooses a release channel
+fn channel_combo_box(ui: &mut egui::Ui, id_source: &str, channel: &mut String) {
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(channel.as_str())
+        .show_ui(ui, |ui| {
+            for option in CHANNELS {
+                ui.selectable_value(channel, option.to_string(), option);
+            }
+        });
+}
+
+/// Chooses the launcher's language, and tells whether it changed
+fn language_combo_box(ui: &mut egui::Ui, id_source: &str, language: &mut String) -> bool {
+    let previous = language.clone();
+    let selected = LANGUAGES
+        .iter()
+        .find(|(code, _)| *code == language.as_str())
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| tr!("Automatic"));
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(selected)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(language, String::new(), tr!("Automatic"));
+            for (code, name) in LANGUAGES {
+                ui.selectable_value(language, code.to_string(), name);
+            }
+        });
+    *language != previous
+}
+
+fn crash_reports_name(choice: CrashReports) -> String {
+    match choice {
+        CrashReports::Ask => tr!("Ask each time"),
+        CrashReports::Send => tr!("Always send"),
+        CrashReports::Never => tr!("Never send"),
+    }
+}
+
+fn priority_name(priority: Priority) -> String {
+    match priority {
+        Priority::Idle => tr!("Lowest"),
+        Priority::BelowNormal => tr!("Below normal"),
+        Priority::Normal => tr!("Normal"),
+        Priority::AboveNormal => tr!("Above normal"),
+        Priority::High => tr!("Highest"),
+    }
+}
+
+fn theme_name(theme: Theme) -> String {
+    match theme {
+        Theme::Auto => tr!("Automatic"),
+        Theme::Dark => tr!("Dark"),
+        Theme::Light => tr!("Light"),
+    }
+}
+
 enum ProgressBarState {
     Downloading(String, f32),
     Connecting(String),
@@ -25,6 +247,8 @@ enum PlayButtonState {
     Disabled,
     Play,
     Retry,
+    Login,
+    Update,
 }
 
 impl ProgressBarState {
@@ -32,17 +256,17 @@ impl ProgressBarState {
         egui::Color32::GRAY
     }
 
-    pub fn foreground_color(&self) -> egui::Color32 {
+    pub fn foreground_color(&self, palette: &Palette) -> egui::Color32 {
         match &self {
-            ProgressBarState::Downloading(_, _) => egui::Color32::from_rgb(0x4e, 0x80, 0x4e),
+            ProgressBarState::Downloading(_, _) => palette.accent,
             ProgressBarState::Connecting(_) => egui::Color32::from_rgb(0xF0, 0xD0, 0x90),
             ProgressBarState::Error(_) => egui::Color32::from_rgb(0xD0, 0x80, 0x80),
         }
     }
 
-    pub fn text_color(&self) -> egui::Color32 {
+    pub fn text_color(&self, palette: &Palette) -> egui::Color32 {
         match &self {
-            ProgressBarState::Downloading(_, _) => egui::Color32::WHITE,
+            ProgressBarState::Downloading(_, _) => palette.accent_text,
             ProgressBarState::Connecting(_) => egui::Color32::DARK_GRAY,
             ProgressBarState::Error(_) => egui::Color32::WHITE,
         }
@@ -69,13 +293,116 @@ pub struct PatcherUI {
     tx: Sender<GUIMessage>,
     rx: Receiver<PatchMessage>,
     background_handle: Option<egui::TextureHandle>,
-    link_bar_color: egui::Color32,
+    /// The colors in use, which follow the settings screen while it is open
+    palette: Palette,
     username: String,
     password: String,
     progress_bar_state: ProgressBarState,
     play_button_state: PlayButtonState,
     program_version: String,
+    /// Which version of the game is installed and which is available
+    game_version: Option<String>,
     use_login: bool,
+    /// The patch server wants a username and password
+    server_login: bool,
+    /// The release channel being patched to
+    channel: String,
+    /// Optional components the patch server offers
+    available_components: Vec<String>,
+    enabled_components: Vec<String>,
+    /// Language packs of the game the patch server offers
+    available_languages: Vec<String>,
+    enabled_languages: Vec<String>,
+    /// The other programs which come with the game, as their IDs and their
+    /// untranslated names
+    launch_targets: Vec<(String, String)>,
+    /// Where the launcher's logs are, once the worker says
+    log_dir: Option<std::path::PathBuf>,
+    /// Unexpected files found by a scan, waiting for the player to decide
+    /// what to do with them
+    foreign_files: Vec<String>,
+    /// What the patch which is about to finish did
+    patch_report: Option<PatchReport>,
+    /// Asking the player whether to really uninstall
+    confirm_uninstall: bool,
+    uninstall_everything: bool,
+    /// Where to move the game, while asking the player
+    move_target: Option<String>,
+    /// Realms to choose from, and the one chosen
+    realms: Vec<RealmInfo>,
+    realm: Option<String>,
+    /// Whether the pings to the realms are still being measured
+    measuring_realms: bool,
+    /// Install profiles to choose from, and the one in use
+    profiles: Vec<String>,
+    profile: Option<String>,
+    /// How much the patch in progress downloads
+    update_size: Option<String>,
+    /// The settings as the worker last reported them
+    settings: Option<Settings>,
+    /// Set while the settings screen is open
+    settings_form: Option<SettingsForm>,
+    /// Set while the launcher is being set up for the first time
+    setup_form: Option<SetupForm>,
+    /// The patch server in use, if it isn't the usual one
+    custom_server: Option<String>,
+    /// Announcements from the patch server
+    news: Vec<NewsItem>,
+    /// The patch notes of the version being updated to, and its version
+    changelog: Option<(String, String)>,
+    show_changelog: bool,
+    /// The message of the day, maintenance times and so on
+    server_details: ServerDetails,
+    /// The game server is down for maintenance
+    maintenance: bool,
+    /// Asking the player to close the game so it can be patched
+    game_running: bool,
+    /// How the game crashed on startup, and the end of its error output
+    game_crash: Option<(String, String)>,
+    /// Damaged game files found just before starting the game
+    repair_needed: Vec<String>,
+    /// Hook commands waiting for the player to agree to run them
+    confirm_hooks: Vec<String>,
+    /// Asking whether to send reports of the launcher crashing
+    crash_reports: bool,
+    remember_crash_choice: bool,
+    /// Seconds until the game starts by itself
+    auto_launch: Option<u64>,
+    /// Set while asking the player to log in to their game account, with
+    /// why the last attempt failed if it did
+    account_login: Option<Option<String>>,
+    account_username: String,
+    account_password: String,
+    /// What happened to each file during the last patch
+    file_log: VecDeque<FileLogEntry>,
+    show_file_log: bool,
+    /// Whether the settings turned on the screen reader
+    screen_reader: bool,
+    saved_window: SavedWindow,
+    /// The results of the last connection test, while they are shown
+    connection_report: Option<ConnectionReport>,
+    /// More about the last error, for support requests
+    error_details: Option<ErrorDetails>,
+    show_error_details: bool,
+    /// What the worker is doing, which decides what can be asked of it
+    worker_state: WorkerState,
+    /// When the worker last sent anything, to notice it getting stuck
+    last_heard: Instant,
+    /// Fill the screen with only what is needed to play, for Steam's Big
+    /// Picture mode
+    big_picture: bool,
+    /// The Discord presence, if this build has a Discord application
+    presence: Option<Presence>,
+    /// Run once the worker has closed, before the window does
+    worker_closed: Option<Box<dyn FnOnce()>>,
+}
+
+/// What the rest of the launcher needs to do at points in the window's life
+pub struct Hooks {
+    /// Once the window is open
+    pub started: Box<dyn FnOnce()>,
+    /// Once the worker has closed. This may end the launcher itself.
+    pub worker_closed: Box<dyn FnOnce()>,
 }
 
 impl PatcherUI {
@@ -83,31 +410,99 @@ impl PatcherUI {
         sender: Sender<GUIMessage>,
         receiver: Receiver<PatchMessage>,
         use_login: bool,
+        channel: String,
+        saved_window: SavedWindow,
+        big_picture: bool,
+        worker_closed: Box<dyn FnOnce()>,
     ) -> PatcherUI {
         PatcherUI {
             tx: sender,
             rx: receiver,
             background_handle: None,
-            link_bar_color: egui::Color32::from_rgba_unmultiplied(0, 0, 0, 240),
+            palette: Palette::new(Theme::Auto, theme::DEFAULT_ACCENT, None),
             username: String::new(),
             password: String::new(),
-            progress_bar_state: ProgressBarState::Connecting(
-                "Waiting for patch server...".to_string(),
-            ),
+            progress_bar_state: ProgressBarState::Connecting(tr!("Waiting for patch server...")),
             play_button_state: PlayButtonState::Disabled,
             program_version: version_summary(),
+            game_version: None,
             use_login,
+            server_login: false,
+            channel,
+            available_components: Vec::new(),
+            enabled_components: Vec::new(),
+            available_languages: Vec::new(),
+            enabled_languages: Vec::new(),
+            launch_targets: Vec::new(),
+            log_dir: None,
+            foreign_files: Vec::new(),
+            patch_report: None,
+            confirm_uninstall: false,
+            uninstall_everything: false,
+            move_target: None,
+            realms: Vec::new(),
+            realm: None,
+            measuring_realms: false,
+            profiles: Vec::new(),
+            profile: None,
+            update_size: None,
+            settings: None,
+            settings_form: None,
+            setup_form: None,
+            custom_server: None,
+            news: Vec::new(),
+            changelog: None,
+            show_changelog: false,
+            server_details: ServerDetails::default(),
+            maintenance: false,
+            game_running: false,
+            game_crash: None,
+            repair_needed: Vec::new(),
+            confirm_hooks: Vec::new(),
+            crash_reports: false,
+            remember_crash_choice: false,
+            auto_launch: None,
+            account_login: None,
+            account_username: String::new(),
+            account_password: String::new(),
+            file_log: VecDeque::new(),
+            show_file_log: false,
+            screen_reader: false,
+            saved_window,
+            connection_report: None,
+            error_details: None,
+            show_error_details: false,
+            worker_state: WorkerState::Idle,
+            last_heard: Instant::now(),
+            big_picture,
+            presence: Presence::start(),
+            worker_closed: Some(worker_closed),
         }
     }
 
-    pub fn run(sender: Sender<GUIMessage>, receiver: Receiver<PatchMessage>, use_login: bool) {
+    pub fn run(
+        sender: Sender<GUIMessage>,
+        receiver: Receiver<PatchMessage>,
+        use_login: bool,
+        channel: String,
+        saved_window: SavedWindow,
+        big_picture: bool,
+        hooks: Hooks,
+    ) {
         let window_size = Some(Vec2 {
             x: 1000.0,
             y: 600.0,
         });
-        eframe::run_native(
-            "Atomix ECO Launcher",
+        let options = if big_picture {
             eframe::NativeOptions {
+                initial_window_size: window_size,
+                maximized: true,
+                decorated: false,
+                ..eframe::NativeOptions::default()
+            }
+        } else {
+            eframe::NativeOptions {
+                initial_window_pos: saved_window.initial_position(),
                 initial_window_size: window_size,
                 min_window_size: window_size,
                 max_window_size: window_size,
@@ -117,68 +512,1358 @@ impl PatcherUI {
                 // To have rounded corners we need transparency:
                 transparent: true,
                 ..eframe::NativeOptions::default()
-            },
-            Box::new(move |_cc| Box::new(PatcherUI::new(sender, receiver, use_login))),
+            }
+        };
+        eframe::run_native(
+            WINDOW_TITLE,
+            options,
+            Box::new(move |cc| {
+                fonts::install(&cc.egui_ctx);
+                if motion::reduced() {
+                    let mut style = (*cc.egui_ctx.style()).clone();
+                    style.animation_time = 0.;
+                    cc.egui_ctx.set_style(style);
+                }
+                // The window is open by now
+                (hooks.started)();
+                Box::new(PatcherUI::new(
+                    sender,
+                    receiver,
+                    use_login,
+                    channel,
+                    saved_window,
+                    big_picture,
+                    hooks.worker_closed,
+                ))
+            }),
         );
     }
 
-    fn load_background(&mut self, ui: &mut egui::Ui) -> egui::TextureHandle {
-        if let Some(handle) = &self.background_handle {
-            return handle.clone();
+    fn load_background(&mut self, ui: &mut egui::Ui) -> egui::TextureHandle {
+        if let Some(handle) = &self.background_handle {
+            return handle.clone();
+        }
+        let bg_img = load_image_from_memory(include_bytes!("../../assets/top_bg.png"))
+            .expect("Background texture should be valid");
+        let handle = ui
+            .ctx()
+            .load_texture("background", bg_img, egui::TextureFilter::Linear);
+        self.background_handle = Some(handle);
+        self.background_handle
+            .as_ref()
+            .expect("Background was just loaded")
+            .clone()
+    }
+
+    fn handle_messages(&mut self, frame: &mut eframe::Frame) {
+        while let Ok(message) = self.rx.try_recv() {
+            self.last_heard = Instant::now();
+            match message {
+                PatchMessage::State(state) => {
+                    self.worker_state = state;
+                }
+                PatchMessage::Error(message, details) => {
+                    self.notify(tr!("Patching failed"), message.clone());
+                    self.progress_bar_state = ProgressBarState::Error(message);
+                    self.error_details = Some(details);
+                }
+                PatchMessage::Downloading(message, progress) => {
+                    self.progress_bar_state = ProgressBarState::Downloading(message, progress);
+                }
+                PatchMessage::Transfer(message, progress, rate) => {
+                    self.progress_bar_state =
+                        ProgressBarState::Downloading(format!("{message} - {rate}"), progress);
+                }
+                PatchMessage::Info(message) => {
+                    self.progress_bar_state = ProgressBarState::Connecting(message);
+                }
+                PatchMessage::Versions {
+                    installed,
+                    available,
+                } => {
+                    self.game_version = match (installed, available) {
+                        (Some(installed), Some(available)) if installed != available => Some(tr!(
+                            "Installed {installed} → Available {available}",
+                            installed = installed,
+                            available = available
+                        )),
+                        (_, Some(version)) | (Some(version), None) => {
+                            Some(tr!("Game version {version}", version = version))
+                        }
+                        (None, None) => None,
+                    };
+                }
+                PatchMessage::Components { available, enabled } => {
+                    self.available_components = available;
+                    self.enabled_components = enabled;
+                }
+                PatchMessage::GameLanguages { available, enabled } => {
+                    self.available_languages = available;
+                    self.enabled_languages = enabled;
+                }
+                PatchMessage::ForeignFiles(files) => {
+                    self.foreign_files = files;
+                }
+                PatchMessage::Summary(report) => {
+                    self.patch_report = Some(report);
+                }
+                PatchMessage::ConnectionReport(report) => {
+                    self.connection_report = Some(report);
+                }
+                PatchMessage::Profiles {
+                    names,
+                    active,
+                    channel,
+                } => {
+                    self.profiles = names;
+                    self.profile = active;
+                    self.channel = channel;
+                }
+                PatchMessage::Realms {
+                    realms,
+                    selected,
+                    measuring,
+                } => {
+                    self.realms = realms;
+                    self.realm = selected;
+                    self.measuring_realms = measuring;
+                }
+                PatchMessage::Settings(settings) => {
+                    self.channel = settings.channel.clone();
+                    self.settings = Some(settings);
+                }
+                PatchMessage::ServerDetails(details) => {
+                    self.server_details = details;
+                }
+                PatchMessage::AccountLogin(error) => {
+                    self.account_login = Some(error);
+                }
+                PatchMessage::AutoLaunch(seconds) => {
+                    self.auto_launch = seconds;
+                }
+                PatchMessage::LogDir(dir) => self.log_dir = Some(dir),
+                PatchMessage::LaunchTargets(targets) => {
+                    self.launch_targets = targets;
+                }
+                PatchMessage::CrashReports => self.crash_reports = true,
+                PatchMessage::Heartbeat => {}
+                PatchMessage::ConfirmHooks(hooks) => {
+                    self.confirm_hooks = hooks;
+                }
+                PatchMessage::RepairNeeded(files) => {
+                    self.progress_bar_state = ProgressBarState::Error(tr!("Repair needed"));
+                    self.play_button_state = PlayButtonState::Play;
+                    self.repair_needed = files;
+                }
+                PatchMessage::GameCrashed { text, output } => {
+                    self.progress_bar_state =
+                        ProgressBarState::Error(tr!("The game crashed on startup"));
+                    self.play_button_state = PlayButtonState::Play;
+                    self.game_crash = Some((text, output));
+                }
+                PatchMessage::FirstRun => {
+                    self.setup_form = self.settings.as_ref().map(|settings| SetupForm {
+                        settings: SettingsForm::new(settings),
+                        create_shortcut: true,
+                    });
+                    self.progress_bar_state =
+                        ProgressBarState::Connecting(tr!("Choose how to install the game"));
+                }
+                PatchMessage::Changelog { version, text } => {
+                    self.changelog = Some((version, text));
+                    self.show_changelog = true;
+                }
+                PatchMessage::News(news) => {
+                    self.news = news;
+                }
+                PatchMessage::Server(server) => {
+                    self.custom_server = server;
+                }
+                PatchMessage::FileResult {
+                    path,
+                    action,
+                    bytes,
+                } => {
+                    if self.file_log.len() == FILE_LOG_LENGTH {
+                        self.file_log.pop_front();
+                    }
+                    self.file_log.push_back(FileLogEntry {
+                        path,
+                        action,
+                        bytes,
+                    });
+                }
+                PatchMessage::UpdateSize(size) => {
+                    let size = byte_unit::Byte::from_bytes(size.into()).get_appropriate_unit(true);
+                    self.update_size = Some(tr!("Update size: {size}", size = size));
+                }
+                PatchMessage::UpdateAvailable => {
+                    self.progress_bar_state =
+                        ProgressBarState::Downloading(tr!("A new update is available"), 1.);
+                    self.play_button_state = PlayButtonState::Update;
+                }
+                PatchMessage::PatchStatus(status) => {
+                    if !matches!(status, PatchStatus::Working) {
+                        self.update_size = None;
+                    }
+                    self.maintenance = matches!(status, PatchStatus::Maintenance);
+                    self.game_running = matches!(status, PatchStatus::GameRunning);
+                    match status {
+                        PatchStatus::Finished => {
+                            let text = match self.patch_report.take() {
+                                Some(report) => {
+                                    // Only patches which ran are worth
+                                    // telling the player about
+                                    self.notify(tr!("The game is ready"), report.to_string());
+                                    tr!("Ready! {report}", report = report)
+                                }
+                                None => tr!("Ready!"),
+                            };
+                            self.progress_bar_state = ProgressBarState::Downloading(text, 1.);
+                            self.play_button_state = PlayButtonState::Play;
+                        }
+                        PatchStatus::Offline => {
+                            // The worker explains why in the progress bar
+                            self.play_button_state = PlayButtonState::Play;
+                        }
+                        PatchStatus::Repaired(summary) => {
+                            // The repair summary says more
+                            self.patch_report = None;
+                            if summary.failed == 0 {
+                                self.progress_bar_state =
+                                    ProgressBarState::Downloading(summary.to_string(), 1.);
+                                self.play_button_state = PlayButtonState::Play;
+                            } else {
+                                self.progress_bar_state =
+                                    ProgressBarState::Error(summary.to_string());
+                                self.play_button_state = PlayButtonState::Retry;
+                            }
+                        }
+                        PatchStatus::Preview(preview) => {
+                            self.progress_bar_state =
+                                ProgressBarState::Downloading(preview.to_string(), 1.);
+                            self.play_button_state = if preview.is_up_to_date() {
+                                PlayButtonState::Play
+                            } else {
+                                PlayButtonState::Update
+                            };
+                        }
+                        PatchStatus::Unauthorized => {
+                            self.server_login = true;
+                            self.play_button_state = PlayButtonState::Login;
+                        }
+                        PatchStatus::Working => {
+                            // A new patch is starting
+                            if !matches!(self.play_button_state, PlayButtonState::Disabled) {
+                                self.file_log.clear();
+                            }
+                            self.error_details = None;
+                            self.show_error_details = false;
+                            self.play_button_state = PlayButtonState::Disabled;
+                        }
+                        PatchStatus::Error => {
+                            self.play_button_state = PlayButtonState::Retry;
+                        }
+                        PatchStatus::Uninstalled => {
+                            // Retrying installs the game again
+                            self.play_button_state = PlayButtonState::Retry;
+                        }
+                        PatchStatus::Maintenance => {
+                            // The text is kept up to date with the time left
+                            self.play_button_state = PlayButtonState::Retry;
+                        }
+                        PatchStatus::GameRunning => {
+                            self.play_button_state = PlayButtonState::Retry;
+                        }
+                        PatchStatus::LauncherOutdated => {
+                            // Retrying won't help until the launcher is
+                            // replaced, but the game can still be played as
+                            // it is
+                            self.play_button_state = PlayButtonState::Play;
+                        }
+                        PatchStatus::Close => {
+                            // We are done! The launcher may exit from here,
+                            // without the window being dropped.
+                            if let Some(worker_closed) = self.worker_closed.take() {
+                                self.saved_window.save();
+                                worker_closed();
+                            }
+                            frame.close();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn window(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.handle_messages(frame);
+        // The window fills the screen, which isn't worth remembering
+        if !self.big_picture {
+            self.saved_window.update(frame);
+        }
+        self.apply_settings(ctx, frame);
+        self.update_presence();
+        if self.maintenance {
+            self.maintenance_countdown(ctx);
+        }
+        atomix::window_frame(ctx, frame, "Atomix ECO Launcher", |ui| {
+            self.background(ui);
+            self.central_panel(ui);
+        });
+        if !self.foreign_files.is_empty() {
+            self.foreign_files_window(ctx);
+        }
+        if self.confirm_uninstall {
+            self.uninstall_window(ctx);
+        }
+        if self.game_running {
+            self.game_running_window(ctx);
+        }
+        if self.game_crash.is_some() {
+            self.game_crash_window(ctx);
+        }
+        if !self.repair_needed.is_empty() {
+            self.repair_needed_window(ctx);
+        }
+        if !self.confirm_hooks.is_empty() {
+            self.confirm_hooks_window(ctx);
+        }
+        // The worker can only act on the answer once it isn't busy
+        if self.crash_reports && !self.worker_state.is_busy() {
+            self.crash_reports_window(ctx);
+        }
+        if self.auto_launch.is_some() {
+            self.auto_launch_window(ctx);
+        }
+        if self.worker_stuck() {
+            self.worker_stuck_window(ctx, frame);
+        }
+        if self.account_login.is_some() {
+            self.account_login_window(ctx);
+        }
+        if self.move_target.is_some() {
+            self.move_window(ctx);
+        }
+        if self.settings_form.is_some() {
+            self.settings_window(ctx);
+        }
+        if self.setup_form.is_some() {
+            self.setup_window(ctx);
+        }
+        if self.show_changelog {
+            self.changelog_window(ctx);
+        }
+        if self.show_file_log {
+            self.file_log_window(ctx);
+        }
+        if self.show_error_details {
+            self.error_details_window(ctx);
+        }
+        if self.connection_report.is_some() {
+            self.connection_report_window(ctx);
+        }
+        self.keyboard_shortcuts(ctx, frame);
+    }
+
+    /// Shows how each step of reaching the patch server went, and lets the
+    /// player copy it into a support request
+    fn connection_report_window(&mut self, ctx: &egui::Context) {
+        let report = match &self.connection_report {
+            Some(report) => report,
+            None => return,
+        };
+        let mut open = true;
+        let mut copy = false;
+        egui::Window::new(tr!("Connection test"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                egui::Grid::new("connection_report_grid")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for check in &report.checks {
+                            if check.ok {
+                                ui.colored_label(self.palette.accent, "✔");
+                            } else {
+                                ui.colored_label(self.palette.warning(), "✘");
+                            }
+                            ui.label(&check.name);
+                            ui.label(match check.millis {
+                                Some(millis) => format!("{} ({millis} ms)", check.detail),
+                                None => check.detail.clone(),
+                            });
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                if report.is_ok() {
+                    ui.label(tr!(
+                        "Everything worked, so the connection to the patch server is fine."
+                    ));
+                }
+                copy = ui.button(tr!("Copy to clipboard")).clicked();
+            });
+
+        if copy {
+            ctx.output().copied_text = format!("{}\n{report}", self.program_version);
+        }
+        if !open {
+            self.connection_report = None;
+        }
+    }
+
+    /// Shows why the last error happened, and lets the player copy it into
+    /// a support request
+    fn error_details_window(&mut self, ctx: &egui::Context) {
+        let details = match &self.error_details {
+            Some(details) => details,
+            None => return,
+        };
+        let mut open = true;
+        let mut copy = false;
+        egui::Window::new(tr!("Error details"))
+            .id(egui::Id::new("error_details_window"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                if let Some(advice) = details.category.advice() {
+                    ui.label(advice);
+                    ui.separator();
+                }
+                egui::Grid::new("error_details_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (index, cause) in details.causes.iter().enumerate() {
+                            match index {
+                                0 => ui.label(tr!("Error")),
+                                _ => ui.label(tr!("Caused by")),
+                            };
+                            ui.label(cause);
+                            ui.end_row();
+                        }
+                        if let Some(url) = &details.url {
+                            ui.label(tr!("URL"));
+                            ui.label(url);
+                            ui.end_row();
+                        }
+                        if let Some(status) = details.http_status {
+                            ui.label(tr!("HTTP status"));
+                            ui.label(status.to_string());
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                copy = ui.button(tr!("Copy to clipboard")).clicked();
+            });
+
+        if copy {
+            // Support needs to know which launcher it was too
+            ctx.output().copied_text = format!("{}\n{details}", self.program_version);
+        }
+        self.show_error_details = open;
+    }
+
+    /// Enter presses the play button, as long as nothing else has the
+    /// keyboard and no dialog is open. Tab moves between the buttons, and
+    /// Enter or Space presses the one with focus. In Big Picture mode, Escape
+    /// quits, since Steam maps the controller's B button to it.
+    fn keyboard_shortcuts(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let dialog_open = self.settings_form.is_some()
+            || self.setup_form.is_some()
+            || self.confirm_uninstall
+            || self.game_running
+            || self.game_crash.is_some()
+            || !self.repair_needed.is_empty()
+            || !self.confirm_hooks.is_empty()
+            || self.crash_reports
+            || self.worker_stuck()
+            || self.account_login.is_some()
+            || self.move_target.is_some()
+            || !self.foreign_files.is_empty()
+            || self.show_changelog
+            || self.show_error_details
+            || self.connection_report.is_some();
+        if dialog_open || ctx.memory().focus().is_some() {
+            return;
+        }
+        if ctx.input().key_pressed(egui::Key::Enter) {
+            self.press_play_button();
+        }
+        if self.big_picture && ctx.input().key_pressed(egui::Key::Escape) {
+            frame.close();
+        }
+    }
+
+    /// Lists what happened to each file as patching goes
+    fn file_log_window(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        egui::Window::new(tr!("Details"))
+            .id(egui::Id::new("file_log_window"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::LEFT_BOTTOM, [30., -170.])
+            .show(ctx, |ui| {
+                if self.file_log.is_empty() {
+                    ui.label(tr!("No files have been changed yet"));
+                    return;
+                }
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                egui::ScrollArea::vertical()
+                    .max_height(300.)
+                    .auto_shrink([false, true])
+                    .stick_to_bottom(true)
+                    .show_rows(ui, row_height, self.file_log.len(), |ui, rows| {
+                        for entry in self.file_log.range(rows) {
+                            ui.horizontal(|ui| {
+                                let color = match entry.action {
+                                    FileAction::Downloaded => ui.visuals().text_color(),
+                                    FileAction::Repaired => self.palette.highlight(),
+                                    FileAction::Skipped => ui.visuals().weak_text_color(),
+                                    FileAction::Failed => self.palette.warning(),
+                                };
+                                ui.colored_label(color, entry.action.to_string());
+                                ui.label(&entry.path);
+                                if entry.bytes > 0 {
+                                    let size = byte_unit::Byte::from_bytes(entry.bytes.into())
+                                        .get_appropriate_unit(true);
+                                    ui.weak(size.to_string());
+                                }
+                            });
+                        }
+                    });
+            });
+        self.show_file_log = open;
+    }
+
+    /// Switches to the colors of the current settings, or of the settings
+    /// being edited so that they can be previewed, and turns the screen
+    /// reader on or off
+    fn apply_settings(&mut self, ctx: &egui::Context, frame: &eframe::Frame) {
+        let (theme, accent) = match (&self.settings_form, &self.settings) {
+            (Some(form), _) => (form.theme, form.accent),
+            (None, Some(settings)) => (settings.theme, theme::accent_color(&settings.accent_color)),
+            (None, None) => (Theme::Auto, theme::DEFAULT_ACCENT),
+        };
+        self.palette = Palette::new(theme, accent, frame.info().system_theme);
+        let visuals = self.palette.visuals();
+        if ctx.style().visuals != visuals {
+            ctx.set_visuals(visuals);
+        }
+
+        // Only switch it when the setting changes, so it stays on if it was
+        // turned on with EFRAME_SCREEN_READER instead
+        let screen_reader = matches!(&self.settings, Some(settings) if settings.screen_reader);
+        if screen_reader != self.screen_reader {
+            self.screen_reader = screen_reader;
+            ctx.options().screen_reader = screen_reader;
+        }
+    }
+
+    /// Shows how long maintenance has left, and checks again once it should
+    /// be over
+    fn maintenance_countdown(&mut self, ctx: &egui::Context) {
+        let mut text = tr!("The server is down for maintenance");
+        match self.server_details.maintenance_remaining() {
+            Some(remaining) if remaining.is_zero() => {
+                self.maintenance = false;
+                self.send(GUIMessage::Retry);
+                return;
+            }
+            Some(remaining) => {
+                let secs = remaining.as_secs();
+                let time = format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60);
+                text.push_str(&tr!(" - back in {time}", time = time));
+                ctx.request_repaint_after(std::time::Duration::from_secs(1));
+            }
+            None => {}
+        }
+        if let Some(message) = &self.server_details.message {
+            text = format!("{text}: {message}");
+        }
+        self.progress_bar_state = ProgressBarState::Connecting(text);
+    }
+
+    fn changelog_window(&mut self, ctx: &egui::Context) {
+        let (version, text) = match &self.changelog {
+            Some(changelog) => changelog,
+            None => return,
+        };
+        let mut close = false;
+        egui::Window::new(tr!("What's new in {version}", version = version))
+            .id(egui::Id::new("changelog_window"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(350.)
+                    .show(ui, |ui| markdown::show(ui, text));
+                ui.separator();
+                if ui.button(tr!("Close")).clicked() {
+                    close = true;
+                }
+            });
+        if close {
+            self.show_changelog = false;
+        }
+    }
+
+    fn settings_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        let mut test_connection = false;
+        if let Some(form) = &mut self.settings_form {
+            egui::Window::new(tr!("Settings"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+                .show(ctx, |ui| {
+                    egui::Grid::new("settings_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label(tr!("Game folder"));
+                            ui.add(
+                                egui::TextEdit::singleline(&mut form.game_dir)
+                                    .hint_text(tr!("Next to the launcher")),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Download limit"));
+                            ui.add(
+                                egui::DragValue::new(&mut form.max_download_kbps)
+                                    .suffix(" KiB/s")
+                                    .speed(16),
+                            )
+                            .on_hover_text(tr!("0 means unlimited"));
+                            ui.end_row();
+
+                            // Values from the config file can be beyond what
+                            // the sliders usually offer
+                            let max_threads = config::cpu_cores().max(form.hash_threads);
+                            let max_downloads = MAX_DOWNLOADS.max(form.max_downloads);
+
+                            ui.label(tr!("Hashing threads"));
+                            ui.add_enabled(
+                                !form.low_impact,
+                                egui::Slider::new(&mut form.hash_threads, 0..=max_threads)
+                                    .custom_formatter(|threads, _| match threads as usize {
+                                        0 => tr!("Automatic"),
+                                        threads => threads.to_string(),
+                                    }),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Simultaneous downloads"));
+                            ui.add_enabled(
+                                !form.low_impact,
+                                egui::Slider::new(&mut form.max_downloads, 1..=max_downloads),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("BitTorrent"));
+                            let torrent = ui.add_enabled(
+                                form.torrent_client_found,
+                                egui::Checkbox::new(
+                                    &mut form.torrent,
+                                    tr!("Download the base game over BitTorrent"),
+                                ),
+                            );
+                            if form.torrent_client_found {
+                                torrent.on_hover_text(tr!("Uses aria2, and falls back to HTTP"));
+                            } else {
+                                torrent.on_disabled_hover_text(tr!(
+                                    "Needs aria2 (aria2c), which was not found. Install it to use this."
+                                ));
+                            }
+                            ui.end_row();
+
+                            ui.label(tr!("Low impact mode"));
+                            ui.checkbox(
+                                &mut form.low_impact,
+                                tr!("Keep the computer responsive while patching"),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Channel"));
+                            channel_combo_box(ui, "settings_channel", &mut form.channel);
+                            ui.end_row();
+
+                            ui.label(tr!("Language"));
+                            language_combo_box(ui, "settings_language", &mut form.language);
+                            ui.end_row();
+
+                            ui.label(tr!("Theme"));
+                            egui::ComboBox::from_id_source("settings_theme")
+                                .selected_text(theme_name(form.theme))
+                                .show_ui(ui, |ui| {
+                                    for theme in [Theme::Auto, Theme::Dark, Theme::Light] {
+                                        ui.selectable_value(
+                                            &mut form.theme,
+                                            theme,
+                                            theme_name(theme),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label(tr!("Accent color"));
+                            ui.horizontal(|ui| {
+                                egui::color_picker::color_edit_button_srgba(
+                                    ui,
+                                    &mut form.accent,
+                                    egui::color_picker::Alpha::Opaque,
+                                );
+                                if ui
+                                    .add_enabled(
+                                        form.accent != theme::DEFAULT_ACCENT,
+                                        egui::Button::new(tr!("Default")),
+                                    )
+                                    .clicked()
+                                {
+                                    form.accent = theme::DEFAULT_ACCENT;
+                                }
+                            });
+                            ui.end_row();
+
+                            ui.label(tr!("Notifications"));
+                            ui.checkbox(
+                                &mut form.notifications,
+                                tr!("Notify me when patching finishes or fails"),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Accessibility"));
+                            ui.checkbox(
+                                &mut form.screen_reader,
+                                tr!("Read out what has focus (screen reader)"),
+                            );
+                            ui.end_row();
+
+                            if self.presence.is_some() {
+                                ui.label(tr!("Discord"));
+                                ui.checkbox(
+                                    &mut form.discord_presence,
+                                    tr!("Show what I'm doing on my Discord profile"),
+                                );
+                                ui.end_row();
+                            }
+
+                            ui.label(tr!("Launch arguments"));
+                            ui.text_edit_singleline(&mut form.launch_args);
+                            ui.end_row();
+
+                            ui.label(tr!("Game priority"));
+                            egui::ComboBox::from_id_source("settings_priority")
+                                .selected_text(priority_name(form.priority))
+                                .show_ui(ui, |ui| {
+                                    for priority in [
+                                        Priority::Idle,
+                                        Priority::BelowNormal,
+                                        Priority::Normal,
+                                        Priority::AboveNormal,
+                                        Priority::High,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut form.priority,
+                                            priority,
+                                            priority_name(priority),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label(tr!("CPU cores"));
+                            ui.add(
+                                egui::TextEdit::singleline(&mut form.cpu_affinity)
+                                    .hint_text(tr!("All")),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Environment variables"));
+                            ui.add(
+                                egui::TextEdit::multiline(&mut form.environment)
+                                    .desired_rows(2)
+                                    .hint_text("NAME=value"),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Auto-play"));
+                            ui.checkbox(
+                                &mut form.auto_launch,
+                                tr!("Launch automatically when up to date"),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Crashes"));
+                            ui.checkbox(
+                                &mut form.monitor_launch,
+                                tr!("Watch the game start and tell me if it crashes"),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Quick check"));
+                            ui.checkbox(
+                                &mut form.quick_check,
+                                tr!("Check the game's programs before starting it"),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Crash reports"));
+                            egui::ComboBox::from_id_source("settings_crash_reports")
+                                .selected_text(crash_reports_name(form.crash_reports))
+                                .show_ui(ui, |ui| {
+                                    for choice in
+                                        [CrashReports::Ask, CrashReports::Send, CrashReports::Never]
+                                    {
+                                        ui.selectable_value(
+                                            &mut form.crash_reports,
+                                            choice,
+                                            crash_reports_name(choice),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label(tr!("Statistics"));
+                            ui.checkbox(
+                                &mut form.telemetry,
+                                tr!("Share anonymous statistics about how patching went"),
+                            )
+                            .on_hover_text(tr!(
+                                "How long patches take, how much they download and why they fail. Nothing which says who you are is sent."
+                            ));
+                            ui.end_row();
+                        });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button(tr!("Save")).clicked() {
+                            answer = Some(true);
+                        }
+                        if ui.button(tr!("Cancel")).clicked() {
+                            answer = Some(false);
+                        }
+                        if let Some(log_dir) = &self.log_dir {
+                            if ui.button(tr!("Open log folder")).clicked() {
+                                open::that(log_dir).ok();
+                            }
+                        }
+                        if ui
+                            .add_enabled(
+                                !self.worker_state.is_busy(),
+                                egui::Button::new(tr!("Test connection")),
+                            )
+                            .clicked()
+                        {
+                            test_connection = true;
+                        }
+                    });
+                });
+        }
+
+        if test_connection {
+            self.send(GUIMessage::TestConnection);
+        }
+        if let Some(save) = answer {
+            let form = self.settings_form.take();
+            if let (true, Some(form)) = (save, form) {
+                let settings = form.to_settings();
+                if self.settings.as_ref() != Some(&settings) {
+                    self.send(GUIMessage::UpdateSettings(settings));
+                }
+            }
+        }
+    }
+
+    /// Asks how to install the game the first time the launcher runs,
+    /// before anything is downloaded
+    fn setup_window(&mut self, ctx: &egui::Context) {
+        let mut finished = false;
+        if let Some(setup) = &mut self.setup_form {
+            egui::Window::new(tr!("Welcome"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+                .show(ctx, |ui| {
+                    ui.label(tr!("Choose how to install the game. Everything can be changed later in the settings."));
+                    ui.add_space(6.);
+                    let form = &mut setup.settings;
+                    egui::Grid::new("setup_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label(tr!("Game folder"));
+                            ui.add(
+                                egui::TextEdit::singleline(&mut form.game_dir)
+                                    .hint_text(tr!("Next to the launcher")),
+                            );
+                            ui.end_row();
+
+                            ui.label(tr!("Channel"));
+                            channel_combo_box(ui, "setup_channel", &mut form.channel);
+                            ui.end_row();
+
+                            ui.label(tr!("Language"));
+                            if language_combo_box(ui, "setup_language", &mut form.language) {
+                                // Show the rest of the setup in that language
+                                crate::i18n::set_language(&form.language);
+                            }
+                            ui.end_row();
+
+                            ui.label(tr!("Download limit"));
+                            ui.add(
+                                egui::DragValue::new(&mut form.max_download_kbps)
+                                    .suffix(" KiB/s")
+                                    .speed(16),
+                            )
+                            .on_hover_text(tr!("0 means unlimited"));
+                            ui.end_row();
+                        });
+                    ui.checkbox(
+                        &mut setup.create_shortcut,
+                        tr!("Create a desktop shortcut"),
+                    );
+                    ui.separator();
+                    if ui.button(tr!("Install")).clicked() {
+                        finished = true;
+                    }
+                });
+        }
+
+        if finished {
+            if let Some(setup) = self.setup_form.take() {
+                self.send(GUIMessage::FinishSetup {
+                    settings: setup.settings.to_settings(),
+                    create_shortcut: setup.create_shortcut,
+                });
+            }
+        }
+    }
+
+    /// Tells the player that the game crashed as it started, with what it
+    /// said about it
+    fn game_crash_window(&mut self, ctx: &egui::Context) {
+        let (text, output) = match &self.game_crash {
+            Some(crash) => crash,
+            None => return,
+        };
+        let mut open = true;
+        let mut copy = false;
+        let mut repair = false;
+        egui::Window::new(tr!("The game crashed on startup"))
+            .open(&mut open)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(text);
+                if output.is_empty() {
+                    ui.label(tr!("The game didn't say why."));
+                } else {
+                    egui::ScrollArea::vertical()
+                        .max_height(200.)
+                        .stick_to_bottom(true)
+                        .show(ui, |ui| {
+                            ui.label(egui::RichText::new(output).monospace());
+                        });
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Copy to clipboard")).clicked() {
+                        copy = true;
+                    }
+                    if ui.button(tr!("Repair")).clicked() {
+                        repair = true;
+                    }
+                });
+            });
+
+        if copy {
+            ctx.output().copied_text = format!("{}\n{text}\n{output}", self.program_version);
+        }
+        if repair {
+            // Damaged files are a common cause
+            self.send(GUIMessage::Verify);
+        }
+        if !open || repair {
+            self.game_crash = None;
+        }
+    }
+
+    /// Asks the player whether to run hook commands which haven't been run
+    /// before, since anyone who could change the config could have put them
+    /// there
+    fn confirm_hooks_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        egui::Window::new(tr!("Run these commands?"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(tr!(
+                    "The launcher is set up to run these commands around the game. Only allow them if you set them up yourself."
+                ));
+                for hook in &self.confirm_hooks {
+                    ui.label(egui::RichText::new(hook).monospace());
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Allow")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(tr!("Cancel")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        if let Some(allow) = answer {
+            let hooks = std::mem::take(&mut self.confirm_hooks);
+            if allow {
+                self.send(GUIMessage::ApproveHooks(hooks));
+            }
+        }
+    }
+
+    /// Asks the player whether to send reports of the launcher crashing to
+    /// its developers. Nothing is sent without their say-so.
+    fn crash_reports_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        egui::Window::new(tr!("The launcher crashed"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(tr!(
+                    "The launcher closed unexpectedly last time. Send a crash report so it can be fixed? It contains the error, the launcher's version and your operating system."
+                ));
+                ui.checkbox(&mut self.remember_crash_choice, tr!("Don't ask again"));
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Send")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(tr!("Don't send")).clicked() {
+                        answer = Some(false);
+                    }
+                    if let Some(log_dir) = &self.log_dir {
+                        if ui.button(tr!("Show report")).clicked() {
+                            open::that(crash_report::crash_dir(log_dir)).ok();
+                        }
+                    }
+                });
+            });
+
+        if let Some(send) = answer {
+            self.crash_reports = false;
+            self.send(GUIMessage::CrashReports {
+                send,
+                remember: self.remember_crash_choice,
+            });
+        }
+    }
+
+    /// The worker hasn't sent anything for so long while patching that it
+    /// is likely stuck, like on a download which stopped without an error
+    fn worker_stuck(&self) -> bool {
+        self.worker_state == WorkerState::Patching && self.last_heard.elapsed() >= WORKER_TIMEOUT
+    }
+
+    /// Offers to start the launcher again when the worker seems stuck. The
+    /// worker can't be stopped from here, so this copy of the launcher
+    /// closes; the next one picks up where the patch left off.
+    fn worker_stuck_window(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let mut answer = None;
+        egui::Window::new(tr!("Patching seems stuck"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(tr!(
+                    "Nothing has happened for {seconds} seconds. The connection to the patch server may have stopped. Start the launcher again and continue the patch?",
+                    seconds = WORKER_TIMEOUT.as_secs()
+                ));
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Cancel and retry")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(tr!("Keep waiting")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        match answer {
+            Some(true) => match crate::patcher::restart() {
+                Ok(()) => frame.close(),
+                Err(why) => {
+                    self.last_heard = Instant::now();
+                    self.progress_bar_state = ProgressBarState::Error(tr!(
+                        "Could not start the launcher again: {error}",
+                        error = why.to_string()
+                    ));
+                }
+            },
+            // Ask again if it stays quiet
+            Some(false) => self.last_heard = Instant::now(),
+            None => {}
+        }
+    }
+
+    /// Says which game files were found damaged before starting the game,
+    /// and offers to repair them
+    fn repair_needed_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        egui::Window::new(tr!("Repair needed"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(tr!(
+                    "These game files are damaged, so the game would likely crash. Repair them before playing."
+                ));
+                egui::ScrollArea::vertical()
+                    .max_height(150.)
+                    .show(ui, |ui| {
+                        for file in &self.repair_needed {
+                            ui.label(file);
+                        }
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Repair")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(tr!("Cancel")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        if let Some(repair) = answer {
+            self.repair_needed.clear();
+            if repair {
+                self.send(GUIMessage::Verify);
+            }
+        }
+    }
+
+    /// Asks for the game account's username and password before starting
+    /// the game
+    fn account_login_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        egui::Window::new(tr!("Log in"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                if let Some(Some(error)) = &self.account_login {
+                    ui.colored_label(self.palette.warning(), error);
+                }
+                egui::Grid::new("account_login_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label(tr!("Username"));
+                        ui.text_edit_singleline(&mut self.account_username);
+                        ui.end_row();
+
+                        ui.label(tr!("Password"));
+                        let password = ui.add(
+                            egui::TextEdit::singleline(&mut self.account_password).password(true),
+                        );
+                        if password.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                            answer = Some(true);
+                        }
+                        ui.end_row();
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Log in")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(tr!("Cancel")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        if let Some(log_in) = answer {
+            self.account_login = None;
+            // Don't keep the password around for longer than needed
+            let password = std::mem::take(&mut self.account_password);
+            if log_in {
+                self.send(GUIMessage::AccountLogin {
+                    username: self.account_username.clone(),
+                    password,
+                });
+            }
+        }
+    }
+
+    /// Counts down until the game starts by itself, and lets the player stop
+    /// it. Pressing the play button starts the game right away.
+    fn auto_launch_window(&mut self, ctx: &egui::Context) {
+        let seconds = match self.auto_launch {
+            Some(seconds) => seconds,
+            None => return,
+        };
+        let mut cancel = false;
+        egui::Window::new(tr!("Starting the game"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(tr!(
+                    "The game starts in {seconds} seconds.",
+                    seconds = seconds
+                ));
+                if ui.button(tr!("Cancel")).clicked() {
+                    cancel = true;
+                }
+            });
+        // The countdown moves along once a second
+        ctx.request_repaint_after(std::time::Duration::from_millis(250));
+
+        if cancel {
+            self.auto_launch = None;
+            self.send(GUIMessage::CancelAutoLaunch);
+        }
+    }
+
+    /// Asks the player to close the game before patching it
+    fn game_running_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        egui::Window::new(tr!("The game is running"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(tr!(
+                    "The game has to be closed before it can be patched, or its files could be damaged."
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Try again")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(tr!("Cancel")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        if let Some(retry) = answer {
+            self.game_running = false;
+            if retry {
+                self.send(GUIMessage::Retry);
+            }
         }
-        let bg_img = load_image_from_memory(include_bytes!("../../assets/top_bg.png"))
-            .expect("Background texture should be valid");
-        let handle = ui
-            .ctx()
-            .load_texture("background", bg_img, egui::TextureFilter::Linear);
-        self.background_handle = Some(handle);
-        self.background_handle
-            .as_ref()
-            .expect("Background was just loaded")
-            .clone()
     }
 
-    fn handle_messages(&mut self, frame: &mut eframe::Frame) {
-        while let Ok(message) = self.rx.try_recv() {
-            match message {
-                PatchMessage::Error(message) => {
-                    self.progress_bar_state = ProgressBarState::Error(message);
-                }
-                PatchMessage::Downloading(message, progress) => {
-                    self.progress_bar_state = ProgressBarState::Downloading(message, progress);
-                }
-                PatchMessage::Info(message) => {
-                    self.progress_bar_state = ProgressBarState::Connecting(message);
-                }
-                PatchMessage::PatchStatus(status) => {
-                    match status {
-                        PatchStatus::Finished => {
-                            self.progress_bar_state =
-                                ProgressBarState::Downloading("Ready!".to_string(), 1.);
-                            self.play_button_state = PlayButtonState::Play;
-                        }
-                        PatchStatus::Working => {
-                            self.play_button_state = PlayButtonState::Disabled;
-                        }
-                        PatchStatus::Error => {
-                            self.play_button_state = PlayButtonState::Retry;
+    fn move_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        if let Some(target) = &mut self.move_target {
+            egui::Window::new(tr!("Move game"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+                .show(ctx, |ui| {
+                    ui.label(tr!("Folder to move the game to:"));
+                    ui.text_edit_singleline(target);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let has_target = !target.trim().is_empty();
+                        if ui
+                            .add_enabled(has_target, egui::Button::new(tr!("Move")))
+                            .clicked()
+                        {
+                            answer = Some(true);
                         }
-                        PatchStatus::Close => {
-                            // We are done!
-                            frame.close();
+                        if ui.button(tr!("Cancel")).clicked() {
+                            answer = Some(false);
                         }
+                    });
+                });
+        }
+
+        if let Some(confirmed) = answer {
+            let target = self.move_target.take().unwrap_or_default();
+            if confirmed {
+                self.send(GUIMessage::MoveInstall(target.trim().into()));
+            }
+        }
+    }
+
+    fn uninstall_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        egui::Window::new(tr!("Uninstall"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(tr!("Remove the game from this computer?"));
+                ui.checkbox(
+                    &mut self.uninstall_everything,
+                    tr!("Also remove my settings and saved login"),
+                );
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Uninstall")).clicked() {
+                        answer = Some(true);
                     }
-                }
+                    if ui.button(tr!("Cancel")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        if let Some(uninstall) = answer {
+            self.confirm_uninstall = false;
+            if uninstall {
+                self.send(GUIMessage::Uninstall {
+                    everything: self.uninstall_everything,
+                });
             }
         }
     }
 
-    fn window(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        self.handle_messages(frame);
-        atomix::window_frame(ctx, frame, "Atomix ECO Launcher", |ui| {
-            self.background(ui);
-            self.central_panel(ui);
-        });
+    /// Lists the files found by a scan, and lets the player choose whether
+    /// to move them out of the game directory
+    fn foreign_files_window(&mut self, ctx: &egui::Context) {
+        let mut answer = None;
+        egui::Window::new(tr!("Unexpected files"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(tr!("These files are not part of the game or the launcher:"));
+                egui::ScrollArea::vertical()
+                    .max_height(250.)
+                    .show(ui, |ui| {
+                        for file in &self.foreign_files {
+                            ui.monospace(file);
+                        }
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr!("Move to quarantine")).clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button(tr!("Keep them")).clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+
+        match answer {
+            Some(true) => {
+                let files = std::mem::take(&mut self.foreign_files);
+                self.send(GUIMessage::QuarantineFiles(files));
+            }
+            Some(false) => self.foreign_files.clear(),
+            None => {}
+        }
     }
 
     fn background(&mut self, ui: &mut egui::Ui) {
@@ -209,11 +1894,54 @@ impl PatcherUI {
 
     fn central_panel(&mut self, ui: &mut egui::Ui) {
         self.bottom_panel(ui);
-        if self.use_login {
+        if self.use_login || self.server_login {
             self.login_panel(ui);
+        } else if !self.news.is_empty() || self.server_details.message.is_some() {
+            self.news_panel(ui);
         }
     }
 
+    fn news_panel(&mut self, ui: &mut egui::Ui) {
+        egui::SidePanel::left("news_panel")
+            .resizable(false)
+            .default_width(360.)
+            .frame(
+                egui::Frame::none()
+                    .fill(self.palette.panel)
+                    .outer_margin(egui::style::Margin::same(25.))
+                    .inner_margin(15.)
+                    .rounding(15.),
+            )
+            .show_inside(ui, |ui| {
+                ui.heading(tr!("News"));
+                ui.separator();
+                if let Some(message) = &self.server_details.message {
+                    ui.label(egui::RichText::new(message).italics());
+                    ui.separator();
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for item in &self.news {
+                        let color = match item.kind {
+                            NewsKind::Announcement => ui.visuals().strong_text_color(),
+                            NewsKind::Maintenance => self.palette.warning(),
+                            NewsKind::PatchNotes => self.palette.highlight(),
+                        };
+                        ui.label(egui::RichText::new(&item.title).strong().color(color));
+                        if let Some(date) = &item.date {
+                            ui.small(date);
+                        }
+                        if !item.body.is_empty() {
+                            ui.label(&item.body);
+                        }
+                        if let Some(url) = &item.url {
+                            ui.hyperlink_to(tr!("Read more"), url);
+                        }
+                        ui.add_space(10.);
+                    }
+                });
+            });
+    }
+
     fn login_panel(&mut self, ui: &mut egui::Ui) {
         egui::TopBottomPanel::top("login_panel_top")
             .frame(
@@ -241,12 +1969,12 @@ impl PatcherUI {
 
                 ui.style_mut().visuals.extreme_bg_color = egui::Color32::LIGHT_GRAY;
 
-                ui.add(egui::Label::new("Username"));
+                ui.add(egui::Label::new(tr!("Username")));
                 ui.add(
                     egui::TextEdit::singleline(&mut self.username).text_color(egui::Color32::BLACK),
                 );
 
-                ui.add(egui::Label::new("Password"));
+                ui.add(egui::Label::new(tr!("Password")));
                 ui.add(
                     egui::TextEdit::singleline(&mut self.password)
                         .text_color(egui::Color32::BLACK)
@@ -259,7 +1987,7 @@ impl PatcherUI {
         egui::TopBottomPanel::bottom("bottom_panel")
             .frame(
                 egui::Frame::none()
-                    .fill(self.link_bar_color)
+                    .fill(self.palette.panel)
                     .rounding(egui::Rounding {
                         nw: 0.,
                         ne: 0.,
@@ -271,11 +1999,14 @@ impl PatcherUI {
             .min_height(144.)
             .show_inside(ui, |ui| {
                 self.progress_panel(ui);
-                self.links_panel(ui);
+                // Too many small buttons to get around with a controller
+                if !self.big_picture {
+                    self.links_panel(ui);
+                }
             });
     }
 
-    fn links_panel(&self, ui: &mut egui::Ui) {
+    fn links_panel(&mut self, ui: &mut egui::Ui) {
         egui::TopBottomPanel::top("links_panel")
             .frame(egui::Frame::none().inner_margin(15.))
             .show_inside(ui, |ui| {
@@ -299,7 +2030,10 @@ impl PatcherUI {
 
                     // Control panel link
                     if ui
-                        .add(egui::Button::new("Control Panel").fill(egui::Color32::TRANSPARENT))
+                        .add(
+                            egui::Button::new(tr!("Control Panel"))
+                                .fill(egui::Color32::TRANSPARENT),
+                        )
                         .clicked()
                     {
                         open::that("https://ecocp.atomixro.com").ok();
@@ -309,27 +2043,377 @@ impl PatcherUI {
 
                     // Registration link
                     if ui
-                        .add(egui::Button::new("Register").fill(egui::Color32::TRANSPARENT))
+                        .add(egui::Button::new(tr!("Register")).fill(egui::Color32::TRANSPARENT))
                         .clicked()
                     {
                         open::that("https://ecocp.atomixro.com/register").ok();
                     }
 
+                    ui.separator();
+
+                    // None of these can start while patching is already
+                    // underway
+                    let idle = !self.worker_state.is_busy() && self.setup_form.is_none();
+                    if ui
+                        .add_enabled(
+                            idle,
+                            egui::Button::new(tr!("Preview update"))
+                                .fill(egui::Color32::TRANSPARENT),
+                        )
+                        .clicked()
+                    {
+                        self.send(GUIMessage::Preview);
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add_enabled(
+                            idle,
+                            egui::Button::new(tr!("Repair")).fill(egui::Color32::TRANSPARENT),
+                        )
+                        .clicked()
+                    {
+                        self.send(GUIMessage::Verify);
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add_enabled(
+                            idle,
+                            egui::Button::new(tr!("Roll back")).fill(egui::Color32::TRANSPARENT),
+                        )
+                        .clicked()
+                    {
+                        self.send(GUIMessage::Rollback);
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add_enabled(
+                            idle,
+                            egui::Button::new(tr!("Scan files")).fill(egui::Color32::TRANSPARENT),
+                        )
+                        .clicked()
+                    {
+                        self.send(GUIMessage::ScanForeignFiles);
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add_enabled(
+                            idle,
+                            egui::Button::new(tr!("Optimize archives"))
+                                .fill(egui::Color32::TRANSPARENT),
+                        )
+                        .on_hover_text(tr!(
+                            "Give back the space left in the game's archives by replaced files"
+                        ))
+                        .clicked()
+                    {
+                        self.send(GUIMessage::OptimizeArchives);
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add_enabled(
+                            idle,
+                            egui::Button::new(tr!("Uninstall")).fill(egui::Color32::TRANSPARENT),
+                        )
+                        .clicked()
+                    {
+                        self.confirm_uninstall = true;
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add_enabled(
+                            idle,
+                            egui::Button::new(tr!("Move game")).fill(egui::Color32::TRANSPARENT),
+                        )
+                        .clicked()
+                    {
+                        self.move_target = Some(String::new());
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add_enabled(
+                            idle && self.settings.is_some(),
+                            egui::Button::new(tr!("Settings")).fill(egui::Color32::TRANSPARENT),
+                        )
+                        .clicked()
+                    {
+                        self.settings_form = self.settings.as_ref().map(SettingsForm::new);
+                    }
+
+                    ui.separator();
+
+                    if self.error_details.is_some() {
+                        if ui
+                            .add(
+                                egui::Button::new(tr!("Error details"))
+                                    .fill(egui::Color32::TRANSPARENT),
+                            )
+                            .clicked()
+                        {
+                            self.show_error_details = true;
+                        }
+                        ui.separator();
+                    }
+
+                    if ui
+                        .add(egui::Button::new(tr!("Details")).fill(egui::Color32::TRANSPARENT))
+                        .clicked()
+                    {
+                        self.show_file_log = !self.show_file_log;
+                    }
+
+                    ui.separator();
+
+                    if self.changelog.is_some() {
+                        if ui
+                            .add(
+                                egui::Button::new(tr!("What's new"))
+                                    .fill(egui::Color32::TRANSPARENT),
+                            )
+                            .clicked()
+                        {
+                            self.show_changelog = true;
+                        }
+                        ui.separator();
+                    }
+
+                    if !self.profiles.is_empty() {
+                        self.profile_selector(ui, idle);
+                        ui.separator();
+                    }
+
+                    self.channel_selector(ui, idle);
+
+                    if !self.realms.is_empty() {
+                        ui.separator();
+                        self.realm_selector(ui, idle);
+                    }
+
+                    if !self.available_components.is_empty() {
+                        ui.separator();
+                        self.component_selector(ui, idle);
+                    }
+
+                    if !self.available_languages.is_empty() {
+                        ui.separator();
+                        self.language_selector(ui, idle);
+                    }
+
+                    if !self.launch_targets.is_empty() {
+                        ui.separator();
+                        let ready = self.worker_state == WorkerState::ReadyToPlay;
+                        self.tools_menu(ui, ready);
+                    }
+
                     // Version string
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Max), |ui| {
                         ui.label(&self.program_version);
+                        if let Some(game_version) = &self.game_version {
+                            ui.separator();
+                            ui.label(game_version);
+                        }
+                        if let Some(update_size) = &self.update_size {
+                            ui.separator();
+                            ui.label(update_size);
+                        }
+                        if let Some(population) = self.server_details.population {
+                            ui.separator();
+                            ui.label(tr!("Population: {population}", population = population));
+                        }
+                        // Make it obvious when the game isn't coming from
+                        // the official server
+                        if let Some(server) = &self.custom_server {
+                            ui.separator();
+                            ui.colored_label(self.palette.warning(), tr!("Custom server"))
+                                .on_hover_text(server);
+                        }
                     });
                 });
             });
     }
 
+    fn channel_selector(&mut self, ui: &mut egui::Ui, enabled: bool) {
+        let mut selected = self.channel.clone();
+        ui.add_enabled_ui(enabled, |ui| {
+            egui::ComboBox::from_id_source("channel_selector")
+                .selected_text(tr!("Channel: {channel}", channel = selected))
+                .show_ui(ui, |ui| {
+                    for channel in CHANNELS {
+                        ui.selectable_value(&mut selected, channel.to_string(), channel);
+                    }
+                });
+        });
+
+        if selected != self.channel {
+            self.channel = selected.clone();
+            self.send(GUIMessage::SetChannel(selected));
+        }
+    }
+
+    /// Picks the realm the game connects to, showing how long connecting to
+    /// each one takes
+    fn realm_selector(&mut self, ui: &mut egui::Ui, enabled: bool) {
+        let mut selected = self.realm.clone();
+        let mut measure = false;
+        let ping_text = |realm: &RealmInfo| match realm.ping {
+            Some(ping) => tr!("{ms} ms", ms = ping.as_millis()),
+            None if self.measuring_realms => "…".to_string(),
+            None => tr!("No answer"),
+        };
+        ui.add_enabled_ui(enabled, |ui| {
+            let current = self
+                .realms
+                .iter()
+                .find(|realm| Some(&realm.name) == selected.as_ref());
+            let text = match current {
+                Some(realm) => tr!(
+                    "Realm: {realm} ({ping})",
+                    realm = realm.name,
+                    ping = ping_text(realm)
+                ),
+                None => tr!("Realm"),
+            };
+            egui::ComboBox::from_id_source("realm_selector")
+                .selected_text(text)
+                .show_ui(ui, |ui| {
+                    for realm in &self.realms {
+                        ui.selectable_value(
+                            &mut selected,
+                            Some(realm.name.clone()),
+                            format!("{} ({})", realm.name, ping_text(realm)),
+                        );
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(
+                            !self.measuring_realms,
+                            egui::Button::new(tr!("Measure again")),
+                        )
+                        .clicked()
+                    {
+                        measure = true;
+                    }
+                });
+        });
+
+        if selected != self.realm {
+            if let Some(name) = selected.clone() {
+                self.realm = selected;
+                self.send(GUIMessage::SelectRealm(name));
+            }
+        }
+        if measure {
+            self.send(GUIMessage::MeasureRealms);
+        }
+    }
+
+    fn profile_selector(&mut self, ui: &mut egui::Ui, enabled: bool) {
+        let mut selected = self.profile.clone();
+        ui.add_enabled_ui(enabled, |ui| {
+            let text = match &selected {
+                Some(profile) => tr!("Profile: {profile}", profile = profile),
+                None => tr!("Profile: default"),
+            };
+            egui::ComboBox::from_id_source("profile_selector")
+                .selected_text(text)
+                .show_ui(ui, |ui| {
+                    for profile in &self.profiles {
+                        ui.selectable_value(&mut selected, Some(profile.clone()), profile);
+                    }
+                });
+        });
+
+        if selected != self.profile {
+            if let Some(profile) = selected {
+                self.send(GUIMessage::SelectProfile(profile));
+            }
+        }
+    }
+
+    fn component_selector(&mut self, ui: &mut egui::Ui, enabled: bool) {
+        let mut changed = false;
+        ui.add_enabled_ui(enabled, |ui| {
+            ui.menu_button(tr!("Components"), |ui| {
+                for component in &self.available_components {
+                    let mut installed = self.enabled_components.contains(component);
+                    if ui.checkbox(&mut installed, component).changed() {
+                        if installed {
+                            self.enabled_components.push(component.clone());
+                        } else {
+                            self.enabled_components.retain(|c| c != component);
+                        }
+                        changed = true;
+                    }
+                }
+            });
+        });
+
+        if changed {
+            self.send(GUIMessage::SetComponents(self.enabled_components.clone()));
+        }
+    }
+
+    fn language_selector(&mut self, ui: &mut egui::Ui, enabled: bool) {
+        let mut changed = false;
+        ui.add_enabled_ui(enabled, |ui| {
+            ui.menu_button(tr!("Game language"), |ui| {
+                for language in &self.available_languages {
+                    let mut installed = self.enabled_languages.contains(language);
+                    if ui.checkbox(&mut installed, language).changed() {
+                        if installed {
+                            self.enabled_languages.push(language.clone());
+                        } else {
+                            self.enabled_languages.retain(|l| l != language);
+                        }
+                        changed = true;
+                    }
+                }
+            });
+        });
+
+        if changed {
+            self.send(GUIMessage::SetGameLanguages(self.enabled_languages.clone()));
+        }
+    }
+
+    fn tools_menu(&mut self, ui: &mut egui::Ui, enabled: bool) {
+        let mut chosen = None;
+        ui.add_enabled_ui(enabled, |ui| {
+            ui.menu_button(tr!("Tools"), |ui| {
+                for (id, name) in &self.launch_targets {
+                    if ui.button(crate::i18n::translate(name)).clicked() {
+                        chosen = Some(id.clone());
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+
+        if let Some(id) = chosen {
+            self.send(GUIMessage::PlayTarget(id));
+        }
+    }
+
     fn progress_panel(&mut self, ui: &mut egui::Ui) {
         egui::TopBottomPanel::bottom("progress_panel_inner")
             .resizable(false)
             .min_height(80.)
             .frame(
                 egui::Frame::none()
-                    .fill(egui::Color32::LIGHT_GRAY)
+                    .fill(self.palette.progress_panel)
                     .outer_margin(egui::style::Margin::same(0.))
                     .inner_margin(egui::style::Margin::same(15.))
                     .rounding(egui::Rounding::same(25.)),
@@ -368,44 +2452,99 @@ impl PatcherUI {
             });
     }
 
+    /// Keeps the Discord presence in step with what the launcher is doing
+    fn update_presence(&mut self) {
+        let enabled = matches!(&self.settings, Some(settings) if settings.discord_presence);
+        let activity = enabled.then(|| match (self.worker_state, &self.progress_bar_state) {
+            (WorkerState::Launching, _) => Activity {
+                details: tr!("Playing AECO"),
+                state: None,
+            },
+            (WorkerState::Patching, ProgressBarState::Downloading(_, progress)) => Activity {
+                details: tr!("In the launcher"),
+                state: Some(tr!(
+                    "Updating ({percent}%)",
+                    percent = (progress * 100.) as u32
+                )),
+            },
+            _ => Activity {
+                details: tr!("In the launcher"),
+                state: None,
+            },
+        });
+        if let Some(presence) = &mut self.presence {
+            presence.set(activity);
+        }
+    }
+
+    /// Shows a desktop notification, unless they are turned off
+    fn notify(&self, summary: String, body: String) {
+        let enabled = match &self.settings {
+            Some(settings) => settings.notifications,
+            None => true,
+        };
+        if enabled {
+            notification::show(summary, body);
+        }
+    }
+
     fn send(&self, message: GUIMessage) {
         if let Err(why) = self.tx.send(message) {
-            eprintln!("Could not send message from GUI to PatchWorker: {why}");
+            warn!("Could not send message from GUI to PatchWorker: {why}");
         }
     }
 
     fn play_button(&mut self, ui: &mut egui::Ui) {
-        let rounding = 25.;
-        match self.play_button_state {
+        let text = match self.play_button_state {
             PlayButtonState::Disabled => {
                 ui.add(
-                    atomix::RoundButton::new("WAIT")
-                        .rounding(rounding)
+                    atomix::RoundButton::new(tr!("WAIT"))
+                        .rounding(25.)
                         .sense(egui::Sense::hover()),
                 );
+                return;
             }
-            PlayButtonState::Play => {
-                if ui
-                    .add(atomix::RoundButton::new("PLAY").rounding(rounding))
-                    .clicked()
-                {
-                    self.send(GUIMessage::Play);
-                }
-            }
-            PlayButtonState::Retry => {
-                if ui
-                    .add(atomix::RoundButton::new("RETRY").rounding(rounding))
-                    .clicked()
-                {
-                    self.send(GUIMessage::Retry);
-                }
-            }
+            PlayButtonState::Play => tr!("PLAY"),
+            PlayButtonState::Retry => tr!("RETRY"),
+            PlayButtonState::Update => tr!("UPDATE"),
+            PlayButtonState::Login => tr!("LOGIN"),
         };
+        if ui.add(self.accent_button(text)).clicked() {
+            self.press_play_button();
+        }
+    }
+
+    /// Does what the play button says
+    fn press_play_button(&mut self) {
+        // The worker would ignore it
+        if self.worker_state.is_busy() {
+            return;
+        }
+        match self.play_button_state {
+            PlayButtonState::Disabled => {}
+            PlayButtonState::Play => self.send(GUIMessage::Play),
+            PlayButtonState::Retry | PlayButtonState::Update => self.send(GUIMessage::Retry),
+            PlayButtonState::Login => {
+                self.server_login = false;
+                self.send(GUIMessage::Login {
+                    username: self.username.clone(),
+                    password: self.password.clone(),
+                });
+            }
+        }
+    }
+
+    /// A play button in the accent color
+    fn accent_button(&self, text: String) -> atomix::RoundButton {
+        atomix::RoundButton::new(egui::RichText::new(text).color(self.palette.accent_text))
+            .fill(self.palette.accent)
+            .rounding(25.)
     }
 
     fn patch_progress_bar(&mut self, ui: &mut egui::Ui) {
         // Progress bar primary color
-        ui.style_mut().visuals.selection.bg_fill = self.progress_bar_state.foreground_color();
+        ui.style_mut().visuals.selection.bg_fill =
+            self.progress_bar_state.foreground_color(&self.palette);
         // Progress bar secondary color
         ui.style_mut().visuals.extreme_bg_color = self.progress_bar_state.background_color();
         ui.style_mut().text_styles = [(
@@ -413,7 +2552,8 @@ impl PatcherUI {
             egui::FontId::new(32.0, egui::FontFamily::Proportional),
         )]
         .into();
-        ui.style_mut().visuals.override_text_color = Some(self.progress_bar_state.text_color());
+        ui.style_mut().visuals.override_text_color =
+            Some(self.progress_bar_state.text_color(&self.palette));
         ui.add(
             atomix::ProgressBar::new(self.progress_bar_state.amount())
                 .height(72.)
@@ -436,6 +2576,7 @@ impl eframe::App for PatcherUI {
 
 impl Drop for PatcherUI {
     fn drop(&mut self) {
+        self.saved_window.save();
         self.send(GUIMessage::Close);
     }
 }
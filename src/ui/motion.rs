@@ -0,0 +1,37 @@
+use std::process::Command;
+
+/// Whether the player has asked the operating system to cut down on
+/// animations
+pub fn reduced() -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // Don't flash a console window
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        // "Show animations in Windows" turns this off along with the rest
+        let output = Command::new("reg")
+            .args([
+                "query",
+                r"HKCU\Control Panel\Desktop\WindowMetrics",
+                "/v",
+                "MinAnimate",
+            ])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output();
+        matches!(output, Ok(output) if String::from_utf8_lossy(&output.stdout).trim_end().ends_with('0'))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let output = Command::new("defaults")
+            .args(["read", "com.apple.universalaccess", "reduceMotion"])
+            .output();
+        matches!(output, Ok(output) if output.stdout.starts_with(b"1"))
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let output = Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.interface", "enable-animations"])
+            .output();
+        matches!(output, Ok(output) if output.stdout.starts_with(b"false"))
+    }
+}
@@ -0,0 +1,16 @@
+use tracing::warn;
+
+/// Shows a desktop notification. This can take a moment, so it happens on
+/// its own thread.
+pub fn show(summary: String, body: String) {
+    std::thread::spawn(move || {
+        let result = notify_rust::Notification::new()
+            .appname("Atomix ECO Launcher")
+            .summary(&summary)
+            .body(&body)
+            .show();
+        if let Err(why) = result {
+            warn!("Could not show a notification: {why}");
+        }
+    });
+}
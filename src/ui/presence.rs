@@ -0,0 +1,197 @@
+use serde_json::json;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// The Discord application the presence is shown for, given at build time.
+/// Builds without one don't show a presence.
+const DISCORD_CLIENT_ID: Option<&str> = option_env!("AECO_DISCORD_CLIENT_ID");
+
+/// Discord drops updates which come more often than this
+const UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait before looking for Discord again once it isn't running
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What the player is shown doing on Discord
+#[derive(Clone, PartialEq, Eq)]
+pub struct Activity {
+    pub details: String,
+    pub state: Option<String>,
+}
+
+/// Shows what the launcher is doing on the player's Discord profile. Talking
+/// to Discord happens on its own thread, and only the latest activity is
+/// sent, no more often than Discord allows.
+pub struct Presence {
+    tx: Sender<Option<Activity>>,
+    current: Option<Activity>,
+}
+
+impl Presence {
+    /// Starts the presence thread, or gives `None` if this build has no
+    /// Discord application
+    pub fn start() -> Option<Self> {
+        let client_id = DISCORD_CLIENT_ID?;
+        let (tx, rx) = channel();
+        std::thread::spawn(move || run(client_id, rx));
+        Some(Self { tx, current: None })
+    }
+
+    /// Shows an activity, or clears it with `None`
+    pub fn set(&mut self, activity: Option<Activity>) {
+        if activity != self.current {
+            self.current = activity.clone();
+            self.tx.send(activity).ok();
+        }
+    }
+}
+
+fn run(client_id: &str, rx: Receiver<Option<Activity>>) {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut connection = None;
+    let mut last_attempt: Option<Instant> = None;
+    while let Ok(mut activity) = rx.recv() {
+        // Only the latest of the activities which come in quick succession
+        // is sent
+        let deadline = Instant::now() + UPDATE_INTERVAL;
+        loop {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(newer) => activity = newer,
+                Err(RecvTimeoutError::Timeout) => break,
+                // The launcher is closing, and Discord clears the activity
+                // once the connection closes
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let retry = match last_attempt {
+            Some(attempt) => attempt.elapsed() >= RECONNECT_INTERVAL,
+            None => true,
+        };
+        if connection.is_none() && retry {
+            last_attempt = Some(Instant::now());
+            connection = Connection::open(client_id)
+                .map_err(|why| warn!("Could not connect to Discord: {why}"))
+                .ok();
+        }
+        if let Some(discord) = &mut connection {
+            if let Err(why) = discord.set_activity(activity.as_ref(), started) {
+                warn!("Could not update the Discord presence: {why}");
+                connection = None;
+            }
+        }
+    }
+}
+
+/// Discord's local IPC socket, which takes frames of an opcode, a length and
+/// a JSON payload
+struct Connection {
+    #[cfg(unix)]
+    stream: std::os::unix::net::UnixStream,
+    #[cfg(windows)]
+    stream: std::fs::File,
+    nonce: u64,
+}
+
+impl Connection {
+    const HANDSHAKE: u32 = 0;
+    const FRAME: u32 = 1;
+
+    fn open(client_id: &str) -> Result<Self, Box<dyn Error>> {
+        let mut connection = Self {
+            stream: open_socket()?,
+            nonce: 0,
+        };
+        connection.write(Self::HANDSHAKE, &json!({ "v": 1, "client_id": client_id }))?;
+        // Discord answers with a READY event
+        connection.read()?;
+        Ok(connection)
+    }
+
+    fn set_activity(
+        &mut self,
+        activity: Option<&Activity>,
+        started: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let activity = activity.map(|activity| {
+            json!({
+                "details": activity.details,
+                "state": activity.state,
+                "timestamps": { "start": started },
+            })
+        });
+        self.nonce += 1;
+        self.write(
+            Self::FRAME,
+            &json!({
+                "cmd": "SET_ACTIVITY",
+                "args": { "pid": std::process::id(), "activity": activity },
+                "nonce": self.nonce.to_string(),
+            }),
+        )?;
+        self.read()?;
+        Ok(())
+    }
+
+    fn write(&mut self, opcode: u32, payload: &serde_json::Value) -> Result<(), Box<dyn Error>> {
+        let payload = serde_json::to_vec(payload)?;
+        let mut frame = Vec::with_capacity(8 + payload.len());
+        frame.extend_from_slice(&opcode.to_le_bytes());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&payload);
+        self.stream.write_all(&frame)?;
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<serde_json::Value, Box<dyn Error>> {
+        let mut header = [0; 8];
+        self.stream.read_exact(&mut header)?;
+        let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let mut payload = vec![0; length as usize];
+        self.stream.read_exact(&mut payload)?;
+        let payload: serde_json::Value = serde_json::from_slice(&payload)?;
+        if payload["evt"] == "ERROR" {
+            return Err(format!("Discord said: {}", payload["data"]["message"]).into());
+        }
+        Ok(payload)
+    }
+}
+
+/// Discord listens on the first free one of ten sockets
+#[cfg(unix)]
+fn open_socket() -> Result<std::os::unix::net::UnixStream, Box<dyn Error>> {
+    let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(|name| std::env::var_os(name).filter(|dir| !dir.is_empty()))
+        .unwrap_or_else(|| "/tmp".into());
+    let dir = std::path::PathBuf::from(dir);
+    for index in 0..10 {
+        let socket = dir.join(format!("discord-ipc-{index}"));
+        if let Ok(stream) = std::os::unix::net::UnixStream::connect(socket) {
+            return Ok(stream);
+        }
+    }
+    Err("Discord is not running".into())
+}
+
+/// Discord listens on the first free one of ten named pipes
+#[cfg(windows)]
+fn open_socket() -> Result<std::fs::File, Box<dyn Error>> {
+    for index in 0..10 {
+        let pipe = format!(r"\\.\pipe\discord-ipc-{index}");
+        if let Ok(file) = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(pipe)
+        {
+            return Ok(file);
+        }
+    }
+    Err("Discord is not running".into())
+}
@@ -0,0 +1,115 @@
+use crate::config::Theme;
+use eframe::egui::{self, Color32};
+
+/// The launcher's own green, used when no accent color is configured
+pub const DEFAULT_ACCENT: Color32 = Color32::from_rgb(0x4e, 0x80, 0x4e);
+
+/// The colors the launcher is drawn with
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub dark: bool,
+    /// The progress bar, the play button and selected widgets
+    pub accent: Color32,
+    /// Text drawn on top of the accent color
+    pub accent_text: Color32,
+    /// Behind the links bar and the news panel
+    pub panel: Color32,
+    /// Behind the progress bar and the play button
+    pub progress_panel: Color32,
+}
+
+impl Palette {
+    /// Works out the colors for a theme and accent color. `Auto` uses the
+    /// system's theme, or the dark theme if it isn't known.
+    pub fn new(theme: Theme, accent: Color32, system_theme: Option<eframe::Theme>) -> Self {
+        let dark = match theme {
+            Theme::Dark => true,
+            Theme::Light => false,
+            Theme::Auto => system_theme != Some(eframe::Theme::Light),
+        };
+        let (panel, progress_panel) = if dark {
+            (
+                Color32::from_rgba_unmultiplied(0, 0, 0, 240),
+                Color32::LIGHT_GRAY,
+            )
+        } else {
+            (
+                Color32::from_rgba_unmultiplied(255, 255, 255, 235),
+                Color32::from_gray(235),
+            )
+        };
+        Self {
+            dark,
+            accent,
+            accent_text: text_on(accent),
+            panel,
+            progress_panel,
+        }
+    }
+
+    pub fn visuals(&self) -> egui::Visuals {
+        let mut visuals = if self.dark {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        };
+        visuals.selection.bg_fill = self.accent;
+        visuals.selection.stroke.color = self.accent_text;
+        visuals
+    }
+
+    /// A color for warnings which can be read on the panels
+    pub fn warning(&self) -> Color32 {
+        if self.dark {
+            Color32::YELLOW
+        } else {
+            Color32::from_rgb(0xA0, 0x70, 0x00)
+        }
+    }
+
+    /// A color for highlights which can be read on the panels
+    pub fn highlight(&self) -> Color32 {
+        if self.dark {
+            Color32::LIGHT_BLUE
+        } else {
+            Color32::from_rgb(0x20, 0x50, 0xA0)
+        }
+    }
+}
+
+/// The accent color for the `accent_color` setting. Settings which aren't a
+/// color use the default one.
+pub fn accent_color(hex: &str) -> Color32 {
+    parse_color(hex).unwrap_or(DEFAULT_ACCENT)
+}
+
+/// The `accent_color` setting for an accent color, which is left empty for
+/// the default one
+pub fn accent_setting(color: Color32) -> String {
+    if color == DEFAULT_ACCENT {
+        String::new()
+    } else {
+        format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+    }
+}
+
+/// Reads a color written as "#rrggbb" or "rrggbb"
+fn parse_color(hex: &str) -> Option<Color32> {
+    let hex = hex.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color32::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Black or white, whichever is easier to read on a color
+fn text_on(color: Color32) -> Color32 {
+    let luma = 0.299 * color.r() as f32 + 0.587 * color.g() as f32 + 0.114 * color.b() as f32;
+    if luma > 150. {
+        Color32::BLACK
+    } else {
+        Color32::WHITE
+    }
+}
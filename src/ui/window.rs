@@ -0,0 +1,78 @@
+use crate::config::{Config, WindowState};
+use eframe::egui::{Pos2, Vec2};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Remembers where the launcher's window is, so that it opens in the same
+/// place next time
+pub struct SavedWindow {
+    /// The config file the window's state is saved to
+    config_path: PathBuf,
+    /// The state from the config file, until it has been checked against
+    /// the monitor the window opened on
+    saved: Option<WindowState>,
+    /// The window's state as of the last frame
+    current: Option<WindowState>,
+}
+
+impl SavedWindow {
+    pub fn new(config_path: PathBuf, saved: Option<WindowState>) -> Self {
+        Self {
+            config_path,
+            saved,
+            current: None,
+        }
+    }
+
+    /// Where to open the window, if it was open before
+    pub fn initial_position(&self) -> Option<Pos2> {
+        self.saved.map(|window| Pos2::new(window.x, window.y))
+    }
+
+    /// Keeps track of where the window is. On the first frame, the window is
+    /// moved back onto the screen if the monitor it was last on is gone or
+    /// has changed size.
+    pub fn update(&mut self, frame: &mut eframe::Frame) {
+        let info = frame.info().window_info;
+        if let Some(saved) = self.saved.take() {
+            let monitor = Vec2::new(saved.monitor_width, saved.monitor_height);
+            if info.monitor_size != Some(monitor) {
+                let position = Pos2::new(saved.x, saved.y);
+                frame.set_window_pos(on_screen(position, info.size, info.monitor_size));
+            }
+        }
+
+        if let Some(position) = info.position {
+            let monitor = info.monitor_size.unwrap_or_default();
+            self.current = Some(WindowState {
+                x: position.x,
+                y: position.y,
+                width: info.size.x,
+                height: info.size.y,
+                monitor_width: monitor.x,
+                monitor_height: monitor.y,
+            });
+        }
+    }
+
+    /// Saves where the window is to the config file
+    pub fn save(&self) {
+        if let Some(window) = self.current {
+            if let Err(why) = Config::save_window_state(&self.config_path, window) {
+                warn!("Could not save the window position: {why}");
+            }
+        }
+    }
+}
+
+/// Moves a window so that all of it is on its monitor, or near the corner of
+/// the main monitor if it isn't on any
+fn on_screen(position: Pos2, size: Vec2, monitor: Option<Vec2>) -> Pos2 {
+    match monitor {
+        Some(monitor) => Pos2::new(
+            position.x.clamp(0., (monitor.x - size.x).max(0.)),
+            position.y.clamp(0., (monitor.y - size.y).max(0.)),
+        ),
+        None => Pos2::new(50., 50.),
+    }
+}